@@ -22,7 +22,6 @@ OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 SOFTWARE.
 */
 
-use cloudfront_policy_signer;
 use cloudfront_policy_signer::CloudFrontCannedPolicySigner;
 
 fn main() {
@@ -48,7 +47,7 @@ fn main() {
 
     println!("Signed URL is {}", signed_url);
 
-    let signed_url_struct = CloudFrontCannedPolicySigner::new("examples/key.pem", key_pair_id.to_string())
+    let signed_url_struct = CloudFrontCannedPolicySigner::new("examples/key.pem", key_pair_id)
         .unwrap()
         .create_canned_policy_signature_url(resource, expiry)
         .unwrap();
@@ -0,0 +1,43 @@
+/*
+MIT License
+
+Copyright (c) 2020 Martin Karlsen Jensen
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+
+//! Parses arbitrary policy documents and checks that generated policies parse back unchanged
+
+#![no_main]
+
+use cloudfront_policy_signer::Policy;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|bytes: &[u8]| {
+    if let Ok(policy) = Policy::from_json(bytes) {
+        assert_eq!(Policy::from_json(policy.to_json()).unwrap(), policy);
+    }
+
+    if let Ok(resource) = std::str::from_utf8(bytes) {
+        let policy = Policy::canned(resource, 1700000000);
+
+        assert_eq!(Policy::from_json(policy.to_json()).unwrap(), policy);
+    }
+});
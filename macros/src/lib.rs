@@ -0,0 +1,183 @@
+/*
+MIT License
+
+Copyright (c) 2020 Martin Karlsen Jensen
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, LitStr};
+
+/// Creates a `cloudfront_policy_signer::Resource` from a string literal that is validated at compile time
+///
+/// The literal must be an absolute `http` or `https` URL with a host. It must not contain
+/// whitespace, non-ASCII characters or other characters that have to be percent-encoded, and the
+/// path must not contain empty segments. `*` and `?` are accepted as wildcards.
+///
+/// # Example
+/// ```ignore
+/// use cloudfront_policy_signer::resource;
+///
+/// let resource = resource!("https://example.cloudfront.net/videos/*");
+/// ```
+#[proc_macro]
+pub fn resource(input: TokenStream) -> TokenStream {
+    let literal = parse_macro_input!(input as LitStr);
+    let value = literal.value();
+
+    match validate_resource(&value) {
+        Ok(()) => quote! {
+            ::cloudfront_policy_signer::Resource::raw(#value)
+        }
+        .into(),
+        Err(message) => syn::Error::new(literal.span(), message)
+            .to_compile_error()
+            .into(),
+    }
+}
+
+/// Creates a `cloudfront_policy_signer::CannedPolicyTemplate` for a resource that is validated at compile time
+///
+/// The resource is validated like [`resource!`] and the canned policy document up to the expiry
+/// is embedded, so signing only has to splice in the expiry. CloudFront does not support
+/// wildcards in canned policies, so `*` and a `?` other than the one starting the query string
+/// are refused.
+///
+/// # Example
+/// ```ignore
+/// use cloudfront_policy_signer::{canned_policy, CannedPolicyTemplate};
+///
+/// static FLOWERPOT: CannedPolicyTemplate =
+///     canned_policy!("https://example.cloudfront.net/flowerpot.png");
+/// ```
+#[proc_macro]
+pub fn canned_policy(input: TokenStream) -> TokenStream {
+    let literal = parse_macro_input!(input as LitStr);
+    let value = literal.value();
+
+    match validate_resource(&value).and_then(|()| validate_canned_resource(&value)) {
+        Ok(()) => {
+            // Validated resources contain no characters that have to be escaped in JSON
+            let prefix = format!(
+                "{{\"Statement\":[{{\"Resource\":\"{}\",\"Condition\":{{\"DateLessThan\":{{\"AWS:EpochTime\":",
+                value
+            );
+
+            quote! {
+                ::cloudfront_policy_signer::CannedPolicyTemplate::from_parts(#value, #prefix)
+            }
+            .into()
+        }
+        Err(message) => syn::Error::new(literal.span(), message)
+            .to_compile_error()
+            .into(),
+    }
+}
+
+/// Checks that a resource that passed [`validate_resource`] has no wildcards, which canned policies do not support
+///
+/// # Arguments
+/// * `resource` - The resource as written in the macro invocation
+fn validate_canned_resource(resource: &str) -> Result<(), String> {
+    let custom_policy = "canned policies do not support wildcards, use a custom policy eg. \
+                         Policy::builder for wildcard resources";
+
+    if resource.contains('*') {
+        return Err(format!("resource contains `*`: {}", custom_policy));
+    }
+
+    // A `?` starts the query string, any later `?` or one followed by a path can only be a wildcard
+    if let Some((_, query)) = resource.split_once('?') {
+        if query.contains(['?', '/']) {
+            return Err(format!(
+                "resource contains `?` as a wildcard: {}",
+                custom_policy
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks the shape of the resource and returns a description of the first problem found
+///
+/// # Arguments
+/// * `resource` - The resource as written in the macro invocation
+fn validate_resource(resource: &str) -> Result<(), String> {
+    let rest = resource
+        .strip_prefix("https://")
+        .or_else(|| resource.strip_prefix("http://"))
+        .ok_or_else(|| {
+            format!(
+                "resource `{}` must start with https:// or http://",
+                resource
+            )
+        })?;
+
+    if let Some(c) = resource
+        .chars()
+        .find(|c| !c.is_ascii() || c.is_ascii_whitespace() || c.is_ascii_control())
+    {
+        return Err(format!(
+            "resource contains {:?} which must be percent-encoded",
+            c
+        ));
+    }
+
+    if let Some(c) = resource.chars().find(|c| {
+        matches!(
+            c,
+            '"' | '<' | '>' | '\\' | '^' | '`' | '{' | '|' | '}' | '#'
+        )
+    }) {
+        return Err(format!(
+            "resource contains {:?} which must be percent-encoded",
+            c
+        ));
+    }
+
+    let (host, path) = match rest.find('/') {
+        Some(index) => rest.split_at(index),
+        None => (rest, ""),
+    };
+
+    if host.is_empty() {
+        return Err("resource is missing a host".to_string());
+    }
+
+    if host.contains('*') && !host.starts_with("*.") && host != "*" {
+        return Err(
+            "a wildcard in the host is only allowed as the first label eg. *.example.com"
+                .to_string(),
+        );
+    }
+
+    let path = path.split('?').next().unwrap_or_default();
+
+    if path.contains("//") {
+        return Err(
+            "resource path contains an empty segment (`//`) which will not match the requested URL"
+                .to_string(),
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,194 @@
+/*
+MIT License
+
+Copyright (c) 2020 Martin Karlsen Jensen
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+//! The base64 variant CloudFront uses in signed URLs and cookies
+//!
+//! CloudFront replaces the characters of standard base64 that are not safe in URLs: `+` becomes
+//! `-`, `/` becomes `~` and the padding `=` becomes `_`.
+//!
+//! ```
+//! use cloudfront_policy_signer::base64;
+//!
+//! assert_eq!(base64::encode(b"\xfb\xff"), "-~8_");
+//! assert_eq!(base64::decode("-~8_").unwrap(), b"\xfb\xff");
+//! ```
+//!
+//! Every byte string has exactly one encoding, so bits left over before the padding must be zero:
+//!
+//! ```
+//! use cloudfront_policy_signer::base64;
+//!
+//! assert_eq!(base64::decode("AA__").unwrap(), b"\x00");
+//! assert!(base64::decode("AB__").is_err());
+//! ```
+
+use crate::Error;
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-~";
+const PADDING: u8 = b'_';
+
+/// Encodes bytes in the URL safe base64 variant of CloudFront
+///
+/// # Arguments
+/// * `bytes` - The bytes to encode, eg. a policy or signature
+pub fn encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(encoded_len(bytes.len()));
+
+    encode_into(&mut encoded, bytes);
+
+    encoded
+}
+
+/// Appends the encoding of the bytes to a string
+fn encode_into(encoded: &mut String, bytes: &[u8]) {
+    encode_with(bytes, |c| encoded.push(c as char));
+}
+
+/// Returns the length of the encoding of a number of bytes
+pub(crate) fn encoded_len(len: usize) -> usize {
+    len.div_ceil(3) * 4
+}
+
+/// Writes the encoding of the bytes to the start of a buffer of at least [`encoded_len`] bytes
+#[cfg(feature = "sign")]
+pub(crate) fn encode_to_slice(bytes: &[u8], out: &mut [u8]) -> usize {
+    let mut written = 0;
+
+    encode_with(bytes, |c| {
+        out[written] = c;
+        written += 1;
+    });
+
+    written
+}
+
+/// Passes every character of the encoding of the bytes to a closure
+fn encode_with<F: FnMut(u8)>(bytes: &[u8], mut push: F) {
+    for chunk in bytes.chunks(3) {
+        let group = (u32::from(chunk[0]) << 16)
+            | (u32::from(chunk.get(1).copied().unwrap_or(0)) << 8)
+            | u32::from(chunk.get(2).copied().unwrap_or(0));
+
+        for index in 0..4 {
+            if index <= chunk.len() {
+                push(ALPHABET[(group >> (18 - 6 * index)) as usize & 0x3f]);
+            } else {
+                push(PADDING);
+            }
+        }
+    }
+}
+
+/// Encodes bytes that arrive in chunks, producing the same output as [`encode`] on all bytes
+#[cfg(feature = "sign")]
+#[derive(Default)]
+pub(crate) struct Encoder {
+    pending: Vec<u8>,
+    encoded: String,
+}
+
+#[cfg(feature = "sign")]
+impl Encoder {
+    /// Encodes every complete group of three bytes and keeps the rest for the next chunk
+    pub(crate) fn update(&mut self, mut bytes: &[u8]) {
+        if !self.pending.is_empty() {
+            let take = (3 - self.pending.len()).min(bytes.len());
+
+            self.pending.extend_from_slice(&bytes[..take]);
+            bytes = &bytes[take..];
+
+            if self.pending.len() < 3 {
+                return;
+            }
+
+            encode_into(&mut self.encoded, &self.pending);
+            self.pending.clear();
+        }
+
+        let complete = bytes.len() / 3 * 3;
+
+        encode_into(&mut self.encoded, &bytes[..complete]);
+        self.pending.extend_from_slice(&bytes[complete..]);
+    }
+
+    /// Encodes the remaining bytes with padding and returns the encoding
+    pub(crate) fn finish(mut self) -> String {
+        encode_into(&mut self.encoded, &self.pending);
+
+        self.encoded
+    }
+}
+
+/// Decodes a string in the URL safe base64 variant of CloudFront
+///
+/// Fails if the bits before the padding that do not make up a byte are not zero, so the encoding
+/// of a signature or policy is unique.
+///
+/// # Arguments
+/// * `encoded` - The encoded string including its padding
+pub fn decode(encoded: &str) -> Result<Vec<u8>, Error> {
+    let invalid = |reason: &str| {
+        error!("Could not decode base64 due to {}", reason);
+        Error::InvalidSignedUrl("a parameter is not valid base64".to_string())
+    };
+    let encoded = encoded.as_bytes();
+
+    if !encoded.len().is_multiple_of(4) {
+        return Err(invalid("a length that is not a multiple of 4"));
+    }
+
+    let mut decoded = Vec::with_capacity(encoded.len() / 4 * 3);
+
+    for (index, chunk) in encoded.chunks(4).enumerate() {
+        let last = index == encoded.len() / 4 - 1;
+        let padding = chunk.iter().rev().take_while(|&&c| c == PADDING).count();
+
+        if padding > 2 || (padding > 0 && !last) {
+            return Err(invalid("misplaced padding"));
+        }
+
+        let mut group = 0u32;
+
+        for &c in &chunk[..4 - padding] {
+            let value = ALPHABET
+                .iter()
+                .position(|&a| a == c)
+                .ok_or_else(|| invalid("an invalid character"))?;
+
+            group = (group << 6) | value as u32;
+        }
+
+        if group & ((1 << (2 * padding)) - 1) != 0 {
+            return Err(invalid("bits after the last byte that are not zero"));
+        }
+
+        group <<= 6 * padding as u32;
+
+        let bytes = group.to_be_bytes();
+
+        decoded.extend_from_slice(&bytes[1..4 - padding]);
+    }
+
+    Ok(decoded)
+}
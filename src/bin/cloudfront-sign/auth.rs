@@ -0,0 +1,237 @@
+/*
+MIT License
+
+Copyright (c) 2020 Martin Karlsen Jensen
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+//! Authentication of callers of the signing service with API keys or TLS client certificates
+//!
+//! API keys are read from a file with a table per principal:
+//!
+//! ```toml
+//! [frontend]
+//! key = "e3b5c1f0a9d84c7e"
+//! max_ttl = "1h"
+//! prefixes = "/tenant-a/, /shared/"
+//! distributions = "d111111abcdef8.cloudfront.net"
+//! ```
+//!
+//! `prefixes` and `distributions` are comma separated lists restricting the paths and hosts the
+//! principal may sign for, so a leaked key of one tenant cannot sign content of another. Prefixes
+//! should end with `/`, `/tenant-a` would also cover `/tenant-ab`.
+//!
+//! Callers send the key as `Authorization: Bearer <key>` or `X-Api-Key: <key>`. With mutual TLS
+//! the common name of the client certificate is the principal.
+
+use crate::http::Request;
+use cloudfront_policy_signer::config;
+use cloudfront_policy_signer::template::parse_ttl;
+use openssl::nid::Nid;
+use openssl::ssl::{SslAcceptor, SslFiletype, SslMethod, SslStream, SslVerifyMode};
+use std::net::TcpStream;
+use std::time::Duration;
+
+/// An authenticated caller of the service
+#[derive(Debug, Clone)]
+pub struct Principal {
+    /// The name of the API key or the common name of the client certificate
+    pub name: String,
+    /// The longest TTL the caller may request
+    pub max_ttl: Option<Duration>,
+    /// The path prefixes the caller may sign for, any path if empty
+    pub prefixes: Vec<String>,
+    /// The hosts the caller may sign for, any host if empty
+    pub distributions: Vec<String>,
+}
+
+impl Principal {
+    /// Constructs a principal without any limits
+    ///
+    /// # Arguments
+    /// * `name` - The name of the principal eg. the common name of a client certificate
+    pub fn unrestricted(name: &str) -> Principal {
+        Principal {
+            name: name.to_string(),
+            max_ttl: None,
+            prefixes: Vec::new(),
+            distributions: Vec::new(),
+        }
+    }
+
+    /// Checks that the principal may sign a resource, returning the reason if not
+    ///
+    /// # Arguments
+    /// * `resource` - The resource as a full URL, after resolving it against the base URL
+    pub fn permits(&self, resource: &str) -> Result<(), String> {
+        if self.prefixes.is_empty() && self.distributions.is_empty() {
+            return Ok(());
+        }
+
+        let (host, path) = resource
+            .split_once("://")
+            .map(|(_, rest)| rest.split_at(rest.find('/').unwrap_or(rest.len())))
+            .ok_or_else(|| format!("{} is not a URL", resource))?;
+        let path = path.split('?').next().unwrap_or(path);
+
+        if !self.distributions.is_empty()
+            && !self
+                .distributions
+                .iter()
+                .any(|distribution| distribution.eq_ignore_ascii_case(host))
+        {
+            return Err(format!("{} may not sign for {}", self.name, host));
+        }
+
+        if !self.prefixes.is_empty() {
+            let traverses = path.split('/').any(|segment| {
+                let segment = segment.to_ascii_lowercase().replace("%2e", ".");
+
+                segment == "." || segment == ".."
+            });
+
+            if traverses || !self.prefixes.iter().any(|prefix| path.starts_with(prefix)) {
+                return Err(format!("{} may not sign for {}", self.name, path));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The API keys accepted by the service
+pub struct ApiKeys {
+    keys: Vec<(String, Principal)>,
+}
+
+impl ApiKeys {
+    /// Reads the API keys from a file
+    ///
+    /// # Arguments
+    /// * `path` - The file with a table per principal
+    pub fn load(path: &str) -> Result<ApiKeys, String> {
+        let tables =
+            config::read_tables(path).map_err(|e| format!("could not read {}: {}", path, e))?;
+        let mut keys = Vec::new();
+
+        for (name, mut values) in tables {
+            let key = values
+                .remove("key")
+                .filter(|key| !key.is_empty())
+                .ok_or_else(|| format!("{}: the principal '{}' has no key", path, name))?;
+            let max_ttl = values
+                .remove("max_ttl")
+                .map(|ttl| parse_ttl(&ttl))
+                .transpose()
+                .map_err(|e| format!("{}: principal '{}': {}", path, name, e))?;
+
+            let list = |value: Option<String>| {
+                value.map_or_else(Vec::new, |value| {
+                    value
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|item| !item.is_empty())
+                        .map(str::to_string)
+                        .collect()
+                })
+            };
+            let prefixes = list(values.remove("prefixes"));
+            let distributions = list(values.remove("distributions"));
+
+            if let Some(prefix) = prefixes.iter().find(|prefix| !prefix.starts_with('/')) {
+                return Err(format!(
+                    "{}: principal '{}': the prefix {} does not start with /",
+                    path, name, prefix
+                ));
+            }
+
+            keys.push((
+                key,
+                Principal {
+                    name,
+                    max_ttl,
+                    prefixes,
+                    distributions,
+                },
+            ));
+        }
+
+        Ok(ApiKeys { keys })
+    }
+
+    /// Returns the principal of the API key sent with the request
+    pub fn authenticate(&self, request: &Request) -> Option<&Principal> {
+        let key = request
+            .header("authorization")
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .or_else(|| request.header("x-api-key"))?
+            .trim();
+
+        self.keys
+            .iter()
+            .find(|(candidate, _)| {
+                candidate.len() == key.len()
+                    && openssl::memcmp::eq(candidate.as_bytes(), key.as_bytes())
+            })
+            .map(|(_, principal)| principal)
+    }
+}
+
+/// Creates the TLS acceptor, requiring client certificates signed by `client_ca` if given
+///
+/// # Arguments
+/// * `certificate` - PEM file with the certificate chain of the service
+/// * `private_key` - PEM file with the private key of the certificate
+/// * `client_ca` - PEM file with the certificate authorities of the clients, enables mutual TLS
+pub fn tls_acceptor(
+    certificate: &str,
+    private_key: &str,
+    client_ca: Option<&str>,
+) -> Result<SslAcceptor, String> {
+    let mut builder =
+        SslAcceptor::mozilla_intermediate_v5(SslMethod::tls_server()).map_err(|e| e.to_string())?;
+
+    builder
+        .set_certificate_chain_file(certificate)
+        .map_err(|e| format!("could not read {}: {}", certificate, e))?;
+    builder
+        .set_private_key_file(private_key, SslFiletype::PEM)
+        .map_err(|e| format!("could not read {}: {}", private_key, e))?;
+
+    if let Some(client_ca) = client_ca {
+        builder
+            .set_ca_file(client_ca)
+            .map_err(|e| format!("could not read {}: {}", client_ca, e))?;
+        builder.set_verify(SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT);
+    }
+
+    Ok(builder.build())
+}
+
+/// Returns the common name of the verified client certificate of a TLS connection
+pub fn peer_name(stream: &SslStream<TcpStream>) -> Option<String> {
+    let certificate = stream.ssl().peer_certificate()?;
+    let common_name = certificate
+        .subject_name()
+        .entries_by_nid(Nid::COMMONNAME)
+        .next()?;
+
+    common_name.data().to_string().ok()
+}
@@ -0,0 +1,101 @@
+/*
+MIT License
+
+Copyright (c) 2020 Martin Karlsen Jensen
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+//! Writing of profiles to the configuration file
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Returns the location of the configuration file, `~/.config/cloudfront-signer/config.toml` by default
+pub fn default_path() -> Result<PathBuf, String> {
+    cloudfront_policy_signer::config::default_path()
+        .ok_or_else(|| "could not determine the configuration directory, use --config".to_string())
+}
+
+/// Writes a profile to the configuration file, replacing an existing profile with the same name
+///
+/// # Arguments
+/// * `path` - The configuration file
+/// * `name` - The name of the profile
+/// * `values` - The keys and values of the profile, values are written as strings
+pub fn write_profile(path: &Path, name: &str, values: &[(&str, &str)]) -> Result<(), String> {
+    let existing = match fs::read_to_string(path) {
+        Ok(existing) => existing,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(format!("could not read {}: {}", path.display(), e)),
+    };
+    let header = format!("[{}]", name);
+    let mut output = String::new();
+    let mut in_profile = false;
+
+    for line in existing.lines() {
+        let trimmed = line.trim();
+
+        if trimmed.starts_with('[') {
+            in_profile = trimmed == header;
+        }
+
+        if !in_profile {
+            output.push_str(line);
+            output.push('\n');
+        }
+    }
+
+    if !output.is_empty() && !output.ends_with("\n\n") {
+        output.push('\n');
+    }
+
+    output.push_str(&header);
+    output.push('\n');
+
+    for (key, value) in values {
+        output.push_str(&format!("{} = {}\n", key, toml_string(value)));
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("could not create {}: {}", parent.display(), e))?;
+    }
+
+    fs::write(path, output).map_err(|e| format!("could not write {}: {}", path.display(), e))
+}
+
+/// Encodes a value as a TOML basic string
+fn toml_string(value: &str) -> String {
+    let mut output = String::from("\"");
+
+    for c in value.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\t' => output.push_str("\\t"),
+            c if c.is_control() => output.push_str(&format!("\\u{:04X}", c as u32)),
+            c => output.push(c),
+        }
+    }
+
+    output.push('"');
+    output
+}
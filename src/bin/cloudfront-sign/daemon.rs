@@ -0,0 +1,93 @@
+/*
+MIT License
+
+Copyright (c) 2020 Martin Karlsen Jensen
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+//! Integration of the service with process managers such as systemd
+//!
+//! SIGTERM and SIGINT request a graceful shutdown, see [`shutdown_requested`], and the state of
+//! the service is reported to systemd through `$NOTIFY_SOCKET` for units with `Type=notify`.
+//! Signals are only handled on Unix, elsewhere the process ends immediately.
+
+use std::env;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static SHUTDOWN: AtomicBool = AtomicBool::new(false);
+
+/// Makes SIGTERM and SIGINT request a graceful shutdown instead of ending the process
+pub fn handle_signals() {
+    #[cfg(unix)]
+    {
+        extern "C" fn request_shutdown(_: libc::c_int) {
+            SHUTDOWN.store(true, Ordering::SeqCst);
+        }
+
+        let handler = request_shutdown as extern "C" fn(libc::c_int) as libc::sighandler_t;
+
+        // SAFETY: the handler only stores to an atomic, which is async-signal-safe
+        unsafe {
+            libc::signal(libc::SIGTERM, handler);
+            libc::signal(libc::SIGINT, handler);
+        }
+    }
+}
+
+/// Whether SIGTERM or SIGINT was received since [`handle_signals`]
+pub fn shutdown_requested() -> bool {
+    SHUTDOWN.load(Ordering::SeqCst)
+}
+
+/// Sends a state such as `READY=1` or `STOPPING=1` to systemd, if started by systemd
+///
+/// # Arguments
+/// * `state` - The newline separated assignments, see sd_notify(3)
+pub fn notify(state: &str) {
+    #[cfg(unix)]
+    if let Some(socket) = env::var_os("NOTIFY_SOCKET") {
+        if let Err(e) = send(&socket, state) {
+            eprintln!("warning: could not notify systemd: {}", e);
+        }
+    }
+
+    #[cfg(not(unix))]
+    let _ = (env::var_os("NOTIFY_SOCKET"), state);
+}
+
+/// Sends a datagram to the notification socket, which starts with `@` in the abstract namespace
+#[cfg(unix)]
+fn send(socket: &std::ffi::OsStr, state: &str) -> std::io::Result<usize> {
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::net::UnixDatagram;
+
+    let datagram = UnixDatagram::unbound()?;
+
+    match socket.as_bytes().strip_prefix(b"@") {
+        #[cfg(target_os = "linux")]
+        Some(name) => {
+            use std::os::linux::net::SocketAddrExt;
+            use std::os::unix::net::SocketAddr;
+
+            datagram.send_to_addr(state.as_bytes(), &SocketAddr::from_abstract_name(name)?)
+        }
+        _ => datagram.send_to(state.as_bytes(), socket),
+    }
+}
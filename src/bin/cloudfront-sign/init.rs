@@ -0,0 +1,178 @@
+/*
+MIT License
+
+Copyright (c) 2020 Martin Karlsen Jensen
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+//! Interactive setup of a key and profile
+
+use crate::args::Args;
+use crate::config;
+use cloudfront_policy_signer::keys::{generate_cloudfront_keypair, CLOUDFRONT_KEY_BITS};
+use cloudfront_policy_signer::template::parse_ttl;
+use openssl::pkey::PKey;
+use openssl::rsa::Rsa;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+/// Walks through selecting or generating a key and writes the resulting profile
+pub fn run(args: &Args) -> Result<(), String> {
+    let config_path = match args.option("config") {
+        Some(path) => PathBuf::from(path),
+        None => config::default_path()?,
+    };
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+
+    println!("This sets up a profile for signing CloudFront URLs.");
+    println!("Press enter to accept the value in brackets.\n");
+
+    let profile = prompt(&mut input, "Profile name", Some("default"))?;
+    let existing_key = prompt(
+        &mut input,
+        "Path to an existing PEM-encoded RSA private key (leave empty to generate one)",
+        Some(""),
+    )?;
+    let (key_path, private_key) = if existing_key.is_empty() {
+        let default_key_path = config_path.with_file_name(format!("{}.pem", profile));
+        let key_path = PathBuf::from(prompt(
+            &mut input,
+            "Where should the new key be written",
+            Some(&default_key_path.to_string_lossy()),
+        )?);
+
+        if key_path.exists() {
+            return Err(format!(
+                "{} already exists, refusing to overwrite it",
+                key_path.display()
+            ));
+        }
+
+        let private_key = generate_cloudfront_keypair(CLOUDFRONT_KEY_BITS)
+            .map_err(|e| format!("could not generate key: {}", e))?
+            .private_key_pem
+            .into_bytes();
+
+        write_private_key(&key_path, &private_key)?;
+        println!("Wrote a new 2048-bit RSA key to {}\n", key_path.display());
+
+        (key_path, private_key)
+    } else {
+        let key_path = PathBuf::from(existing_key);
+        let private_key = fs::read(&key_path)
+            .map_err(|e| format!("could not read {}: {}", key_path.display(), e))?;
+
+        (key_path, private_key)
+    };
+    let public_key = Rsa::private_key_from_pem(&private_key)
+        .and_then(PKey::from_rsa)
+        .and_then(|key| key.public_key_to_pem())
+        .map_err(|e| format!("could not read the private key: {}", e))?;
+
+    println!(
+        "Upload this public key in the CloudFront console under Key management > Public keys,"
+    );
+    println!("then add it to the key group used by your distribution:\n");
+    println!("{}", String::from_utf8_lossy(&public_key));
+
+    let key_pair_id = prompt(
+        &mut input,
+        "Key pair ID shown by CloudFront for the uploaded public key",
+        None,
+    )?;
+    let ttl = loop {
+        let ttl = prompt(&mut input, "Default TTL", Some("1h"))?;
+
+        match parse_ttl(&ttl) {
+            Ok(_) => break ttl,
+            Err(e) => println!("{}", e),
+        }
+    };
+    let cookie_domain = prompt(&mut input, "Cookie domain (optional)", Some(""))?;
+    let key_path = key_path.to_string_lossy();
+    let mut values = vec![
+        ("key", key_path.as_ref()),
+        ("key_pair_id", key_pair_id.as_str()),
+        ("ttl", ttl.as_str()),
+    ];
+
+    if !cookie_domain.is_empty() {
+        values.push(("cookie_domain", cookie_domain.as_str()));
+    }
+
+    config::write_profile(&config_path, &profile, &values)?;
+    println!("\nWrote profile '{}' to {}", profile, config_path.display());
+
+    Ok(())
+}
+
+/// Asks a question and returns the trimmed answer, or the default if the answer is empty
+fn prompt<R: BufRead>(
+    input: &mut R,
+    question: &str,
+    default: Option<&str>,
+) -> Result<String, String> {
+    loop {
+        match default {
+            Some(default) if !default.is_empty() => print!("{} [{}]: ", question, default),
+            _ => print!("{}: ", question),
+        }
+
+        io::stdout().flush().map_err(|e| e.to_string())?;
+
+        let mut answer = String::new();
+
+        if input.read_line(&mut answer).map_err(|e| e.to_string())? == 0 {
+            return Err("unexpected end of input".to_string());
+        }
+
+        match (answer.trim(), default) {
+            ("", Some(default)) => return Ok(default.to_string()),
+            ("", None) => continue,
+            (answer, _) => return Ok(answer.to_string()),
+        }
+    }
+}
+
+/// Writes the private key so that only the current user can read it
+fn write_private_key(path: &Path, key: &[u8]) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("could not create {}: {}", parent.display(), e))?;
+    }
+
+    let mut options = fs::OpenOptions::new();
+
+    options.write(true).create_new(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+
+        options.mode(0o600);
+    }
+
+    options
+        .open(path)
+        .and_then(|mut file| file.write_all(key))
+        .map_err(|e| format!("could not write {}: {}", path.display(), e))
+}
@@ -0,0 +1,406 @@
+/*
+MIT License
+
+Copyright (c) 2020 Martin Karlsen Jensen
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+//! Command line interface for signing CloudFront URLs
+
+mod args;
+mod auth;
+mod config;
+mod daemon;
+mod http;
+mod init;
+mod metrics;
+mod output;
+mod reload;
+mod serve;
+mod trace;
+mod watch;
+
+use args::Args;
+use cloudfront_policy_signer::config::Profile;
+use cloudfront_policy_signer::sandbox;
+use cloudfront_policy_signer::template::parse_ttl;
+use cloudfront_policy_signer::verify::{self, Diagnosis, ParsedSignedUrl};
+use cloudfront_policy_signer::{CloudFrontSigner, PolicyKind};
+use openssl::pkey::{PKey, Public};
+use output::{CookieFormat, Format, SignedRow};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::{env, fs, process};
+
+const USAGE: &str = "Usage: cloudfront-sign <command> [options]
+
+Commands:
+  sign <resource>   Sign a single resource and print the signed URL
+  sign-batch        Sign every resource read from --input (or stdin)
+  sign-cookies <resource>
+                    Create signed cookies granting access to the resource
+  inspect <url>     Decode a signed URL and explain its policy
+  diagnose <url>    Explain why CloudFront would refuse a signed URL
+  init              Interactively set up a key and a configuration profile
+  serve             Run an HTTP service which signs resources on request
+  sign-helper       Sign policy documents read from stdin for a sandboxed signer
+
+Options:
+  --profile <name>        Read defaults for the options below from a configuration profile
+  --config <path>         Configuration file (default ~/.config/cloudfront-signer/config.toml)
+  --key <path>            PEM-encoded RSA private key
+  --key-pair-id <id>      Key pair ID from AWS CloudFront
+  --ttl <duration>        How long the URL is valid for eg. 90s, 30m, 1h (default 1h)
+  --expires <timestamp>   Absolute expiry as a unix timestamp, overrides --ttl
+  --watch                 Keep running and re-sign shortly before expiry or when the
+                          key file changes (sign and sign-cookies only)
+
+Options for sign-batch:
+  --input <path>          File with one resource per line, defaults to stdin
+  --csv                   Read the input as CSV with the columns resource,ttl
+  --format <format>       Output format: csv, json or ndjson (default csv)
+
+Options for sign-cookies:
+  --domain <domain>       Cookie domain, defaults to the host of the resource
+  --path <path>           Cookie path, defaults to the directory of the resource
+  --format <format>       Output format: plain, headers or curl-cookie-jar (default plain)
+
+Options for serve:
+  --listen <addr>         Address to listen on (default 127.0.0.1:8080). Endpoints are
+                          GET /sign?resource=<url>&ttl=<duration>, /metrics, /healthz
+                          and /readyz
+  --key-pair-id-file <path>
+                          Read the key pair ID from a file, reloaded along with the key
+  --reload-interval <duration>
+                          How often the key files are checked for changes (default 10s)
+  --drain-timeout <duration>
+                          How long requests in progress may take to finish after SIGTERM
+                          (default 30s). Readiness is reported to systemd via NOTIFY_SOCKET
+  --spans <path>          Append a span in OTLP JSON for every request, continuing the
+                          trace of a W3C traceparent header
+  --api-keys <path>       Require an API key for /sign, read from a file with a table per
+                          principal holding its key and optionally max_ttl, prefixes and
+                          distributions, comma separated lists of allowed paths and hosts
+  --tls-cert <path>       Serve HTTPS with this PEM certificate chain
+  --tls-key <path>        PEM private key of the certificate
+  --client-ca <path>      Require client certificates signed by these PEM CAs (mutual TLS)
+
+Options for inspect and diagnose:
+  --public-key <path>     PEM-encoded RSA public key used to verify the signature
+
+Without --key the profile from --profile, CLOUDFRONT_SIGNER_PROFILE or 'default' is used.
+";
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let command = args.next();
+    let result = match command.as_deref() {
+        Some("sign") => signing_args(args, &["watch"]).and_then(|args| sign(&args)),
+        Some("sign-batch") => signing_args(args, &["csv"]).and_then(|args| sign_batch(&args)),
+        Some("sign-cookies") => signing_args(args, &["watch"]).and_then(|args| sign_cookies(&args)),
+        Some("inspect") => Args::parse(args, &[]).and_then(|args| inspect(&args)),
+        Some("diagnose") => Args::parse(args, &[]).and_then(|args| diagnose(&args)),
+        Some("init") => Args::parse(args, &[]).and_then(|args| init::run(&args)),
+        Some("serve") => signing_args(args, &[]).and_then(|args| serve::run(&args)),
+        Some("sign-helper") => signing_args(args, &[]).and_then(|args| sign_helper(&args)),
+        Some("help") | Some("--help") | Some("-h") => {
+            print!("{}", USAGE);
+            Ok(())
+        }
+        _ => Err(format!("unknown or missing command\n\n{}", USAGE)),
+    };
+
+    if let Err(e) = result {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
+
+/// Parses the arguments of a signing command and fills in missing options from the profile
+///
+/// The profile is taken from `--profile` or `CLOUDFRONT_SIGNER_PROFILE`. Without either, the
+/// `default` profile is used if `--key` is missing and the profile exists.
+fn signing_args<I: IntoIterator<Item = String>>(args: I, flags: &[&str]) -> Result<Args, String> {
+    let mut args = Args::parse(args, flags)?;
+    let explicit = args
+        .option("profile")
+        .map(str::to_string)
+        .or_else(|| env::var("CLOUDFRONT_SIGNER_PROFILE").ok());
+    let path = match args.option("config") {
+        Some(path) => PathBuf::from(path),
+        None => config::default_path()?,
+    };
+    let profile = match explicit {
+        Some(name) => Profile::load_from(&path, &name).map_err(|e| e.to_string())?,
+        None if args.option("key").is_none() => match Profile::load_from(&path, "default") {
+            Ok(profile) => profile,
+            Err(_) => return Ok(args),
+        },
+        None => return Ok(args),
+    };
+
+    args.set_default("key", profile.key.to_string_lossy().into_owned());
+    args.set_default("key-pair-id", profile.key_pair_id);
+
+    if let Some(ttl) = profile.ttl {
+        args.set_default("ttl", ttl.as_secs().to_string());
+    }
+
+    if let Some(domain) = profile.cookie_domain {
+        args.set_default("domain", domain);
+    }
+
+    Ok(args)
+}
+
+/// Creates the signer from `--key` and `--key-pair-id`
+fn signer(args: &Args) -> Result<CloudFrontSigner, String> {
+    CloudFrontSigner::new(args.required("key")?, args.required("key-pair-id")?)
+        .map_err(|e| e.to_string())
+}
+
+/// Returns the expiry from `--expires`, or from the TTL relative to now
+///
+/// # Arguments
+/// * `args` - The parsed arguments
+/// * `ttl` - A TTL overriding `--ttl`, eg. from a CSV row
+fn expiry(args: &Args, ttl: Option<&str>) -> Result<u64, String> {
+    if let (Some(expires), None) = (args.option("expires"), ttl) {
+        return expires
+            .parse()
+            .map_err(|_| format!("invalid --expires '{}'", expires));
+    }
+
+    let ttl = match ttl.or(args.option("ttl")) {
+        Some(ttl) => parse_ttl(ttl).map_err(|e| e.to_string())?,
+        None => Duration::from_secs(60 * 60),
+    };
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| e.to_string())?;
+
+    Ok((now + ttl).as_secs())
+}
+
+fn sign(args: &Args) -> Result<(), String> {
+    let resource = match args.positional() {
+        [resource] => resource,
+        _ => return Err("sign expects exactly one resource".to_string()),
+    };
+    let sign = |signer: &CloudFrontSigner, expires| {
+        signer
+            .create_canned_policy_signature_url(resource, expires)
+            .map_err(|e| e.to_string())
+    };
+
+    if args.flag("watch") {
+        return watch::run(args, sign);
+    }
+
+    println!("{}", sign(&signer(args)?, expiry(args, None)?)?);
+
+    Ok(())
+}
+
+fn sign_batch(args: &Args) -> Result<(), String> {
+    let signer = signer(args)?;
+    let format = Format::parse(args.option("format").unwrap_or("csv"))?;
+    let input: Box<dyn Read> = match args.option("input") {
+        Some(path) => {
+            Box::new(fs::File::open(path).map_err(|e| format!("could not open {}: {}", path, e))?)
+        }
+        None => Box::new(io::stdin()),
+    };
+    let mut rows = Vec::new();
+
+    for (index, line) in BufReader::new(input).lines().enumerate() {
+        let line = line.map_err(|e| e.to_string())?;
+        let line = line.trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        let (resource, ttl) = if args.flag("csv") {
+            let mut fields = output::split_csv_line(line).into_iter();
+            let resource = fields.next().unwrap_or_default();
+            let ttl = fields.next().filter(|ttl| !ttl.trim().is_empty());
+
+            if index == 0 && resource.eq_ignore_ascii_case("resource") {
+                continue;
+            }
+
+            (resource, ttl)
+        } else {
+            (line.to_string(), None)
+        };
+        let expires = expiry(args, ttl.as_deref().map(str::trim))
+            .map_err(|e| format!("line {}: {}", index + 1, e))?;
+        let url = signer
+            .create_canned_policy_signature_url(&resource, expires)
+            .map_err(|e| format!("line {}: {}", index + 1, e))?;
+
+        rows.push(SignedRow {
+            resource,
+            expires,
+            url,
+        });
+    }
+
+    io::stdout()
+        .write_all(output::render(&rows, format).as_bytes())
+        .map_err(|e| e.to_string())
+}
+
+fn sign_cookies(args: &Args) -> Result<(), String> {
+    let resource = match args.positional() {
+        [resource] => resource,
+        _ => return Err("sign-cookies expects exactly one resource".to_string()),
+    };
+    let format = CookieFormat::parse(args.option("format").unwrap_or("plain"))?;
+    let domain = match args.option("domain") {
+        Some(domain) => domain.to_string(),
+        None => output::host_of(resource)
+            .ok_or_else(|| {
+                format!(
+                    "could not determine the host of '{}', use --domain",
+                    resource
+                )
+            })?
+            .to_string(),
+    };
+    let path = args.option("path");
+    let sign = |signer: &CloudFrontSigner, expires| {
+        let cookies = signer
+            .create_canned_policy_signed_cookies(resource, expires)
+            .map_err(|e| e.to_string())?;
+
+        output::render_cookies(
+            &cookies,
+            format,
+            &domain,
+            path.unwrap_or(cookies.path()),
+            expires,
+        )
+    };
+
+    if args.flag("watch") {
+        return watch::run(args, sign);
+    }
+
+    io::stdout()
+        .write_all(sign(&signer(args)?, expiry(args, None)?)?.as_bytes())
+        .map_err(|e| e.to_string())
+}
+
+/// Answers signing requests of a `SandboxedSigner` over stdin and stdout until stdin is closed
+fn sign_helper(args: &Args) -> Result<(), String> {
+    let signer = signer(args)?;
+
+    sandbox::serve(&signer, io::stdin().lock(), io::stdout().lock()).map_err(|e| e.to_string())
+}
+
+fn inspect(args: &Args) -> Result<(), String> {
+    let url = match args.positional() {
+        [url] => url,
+        _ => return Err("inspect expects exactly one URL".to_string()),
+    };
+    let parsed = ParsedSignedUrl::parse(url).map_err(|e| e.to_string())?;
+    let policy = parsed.policy().map_err(|e| e.to_string())?;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| e.to_string())?
+        .as_secs();
+    let kind = match parsed.kind() {
+        PolicyKind::Canned => "canned",
+        PolicyKind::Custom => "custom",
+    };
+
+    println!("Resource:    {}", parsed.resource());
+    println!("Key-Pair-Id: {}", parsed.key_pair_id());
+    println!("Policy:      {}", kind);
+
+    for (index, statement) in policy.statements.iter().enumerate() {
+        let condition = &statement.condition;
+
+        println!("Statement {}:", index + 1);
+        println!("  Resource:   {}", statement.resource);
+        println!(
+            "  Expires:    {} ({})",
+            output::format_timestamp(condition.date_less_than),
+            output::describe_expiry(condition.date_less_than, now)
+        );
+
+        if let Some(not_before) = condition.date_greater_than {
+            println!(
+                "  Not before: {} ({})",
+                output::format_timestamp(not_before),
+                output::describe_activation(not_before, now)
+            );
+        }
+
+        if let Some(ip_address) = &condition.ip_address {
+            println!("  IP address: {}", ip_address);
+        }
+    }
+
+    let Some(public_key) = public_key(args)? else {
+        println!("Signature:   not verified, pass --public-key to verify it");
+        return Ok(());
+    };
+
+    if parsed.verify(&public_key).map_err(|e| e.to_string())? {
+        println!("Signature:   valid");
+        Ok(())
+    } else {
+        println!("Signature:   INVALID");
+        Err("the signature does not match the policy and public key".to_string())
+    }
+}
+
+/// Explains why CloudFront would refuse the signed URL, failing unless it is valid
+fn diagnose(args: &Args) -> Result<(), String> {
+    let url = match args.positional() {
+        [url] => url,
+        _ => return Err("diagnose expects exactly one URL".to_string()),
+    };
+    let diagnosis = verify::diagnose(url, public_key(args)?.as_ref());
+
+    match diagnosis {
+        Diagnosis::Valid { .. } => {
+            println!("{}", diagnosis);
+            Ok(())
+        }
+        _ => Err(diagnosis.to_string()),
+    }
+}
+
+/// Reads the public key from `--public-key`, if given
+fn public_key(args: &Args) -> Result<Option<PKey<Public>>, String> {
+    let Some(path) = args.option("public-key") else {
+        return Ok(None);
+    };
+    let public_key = fs::read(path).map_err(|e| format!("could not read {}: {}", path, e))?;
+
+    verify::parse_public_key(&public_key)
+        .map(Some)
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,215 @@
+/*
+MIT License
+
+Copyright (c) 2020 Martin Karlsen Jensen
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+//! Prometheus metrics of the signing service, served at `/metrics`
+
+use cloudfront_policy_signer::SignerStatus;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::{Duration, UNIX_EPOCH};
+
+/// The upper bounds of the latency buckets in seconds
+const BUCKETS: [f64; 9] = [0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.5];
+
+/// Request counts and latencies per endpoint
+#[derive(Default)]
+pub struct Metrics {
+    inner: Mutex<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    requests: BTreeMap<(&'static str, u16), u64>,
+    durations: BTreeMap<&'static str, Histogram>,
+}
+
+#[derive(Default)]
+struct Histogram {
+    buckets: [u64; BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Metrics {
+    /// Records a handled request
+    ///
+    /// # Arguments
+    /// * `endpoint` - The endpoint, one of a fixed set to bound the number of series
+    /// * `status` - The status code of the response
+    /// * `duration` - How long handling the request took
+    pub fn record(&self, endpoint: &'static str, status: u16, duration: Duration) {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let seconds = duration.as_secs_f64();
+
+        *inner.requests.entry((endpoint, status)).or_default() += 1;
+
+        let histogram = inner.durations.entry(endpoint).or_default();
+
+        for (bucket, bound) in histogram.buckets.iter_mut().zip(BUCKETS) {
+            if seconds <= bound {
+                *bucket += 1;
+            }
+        }
+
+        histogram.count += 1;
+        histogram.sum += seconds;
+    }
+
+    /// Renders the metrics in the Prometheus text format
+    ///
+    /// # Arguments
+    /// * `status` - The status of the signer, for the key and signing gauges
+    pub fn render(&self, status: &SignerStatus) -> String {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let mut out = String::new();
+
+        header(
+            &mut out,
+            "cloudfront_signer_requests_total",
+            "counter",
+            "Requests handled by the signing service",
+        );
+
+        for ((endpoint, status), count) in &inner.requests {
+            let _ = writeln!(
+                out,
+                "cloudfront_signer_requests_total{{endpoint=\"{}\",status=\"{}\"}} {}",
+                endpoint, status, count
+            );
+        }
+
+        header(
+            &mut out,
+            "cloudfront_signer_request_errors_total",
+            "counter",
+            "Requests answered with a 4xx or 5xx status",
+        );
+
+        let mut errors = BTreeMap::new();
+
+        for ((endpoint, status), count) in &inner.requests {
+            *errors.entry(*endpoint).or_insert(0) += if *status >= 400 { *count } else { 0 };
+        }
+
+        for (endpoint, count) in errors {
+            let _ = writeln!(
+                out,
+                "cloudfront_signer_request_errors_total{{endpoint=\"{}\"}} {}",
+                endpoint, count
+            );
+        }
+
+        header(
+            &mut out,
+            "cloudfront_signer_request_duration_seconds",
+            "histogram",
+            "Time taken to handle requests",
+        );
+
+        for (endpoint, histogram) in &inner.durations {
+            for (bound, count) in BUCKETS.iter().zip(histogram.buckets) {
+                let _ = writeln!(
+                    out,
+                    "cloudfront_signer_request_duration_seconds_bucket{{endpoint=\"{}\",le=\"{}\"}} {}",
+                    endpoint, bound, count
+                );
+            }
+
+            let _ = writeln!(
+                out,
+                "cloudfront_signer_request_duration_seconds_bucket{{endpoint=\"{}\",le=\"+Inf\"}} {}",
+                endpoint, histogram.count
+            );
+            let _ = writeln!(
+                out,
+                "cloudfront_signer_request_duration_seconds_sum{{endpoint=\"{}\"}} {}",
+                endpoint, histogram.sum
+            );
+            let _ = writeln!(
+                out,
+                "cloudfront_signer_request_duration_seconds_count{{endpoint=\"{}\"}} {}",
+                endpoint, histogram.count
+            );
+        }
+
+        gauge(
+            &mut out,
+            "cloudfront_signer_signed_total",
+            "counter",
+            "URLs, cookies and policies signed",
+            status.signed,
+        );
+        gauge(
+            &mut out,
+            "cloudfront_signer_sign_failures_total",
+            "counter",
+            "Attempts to sign that failed",
+            status.failed,
+        );
+        gauge(
+            &mut out,
+            "cloudfront_signer_key_bits",
+            "gauge",
+            "Size of the signing key",
+            status.key_bits,
+        );
+
+        if let Some(key_age) = status.key_age {
+            gauge(
+                &mut out,
+                "cloudfront_signer_key_age_seconds",
+                "gauge",
+                "Time since the key file was last modified",
+                key_age.as_secs(),
+            );
+        }
+
+        gauge(
+            &mut out,
+            "cloudfront_signer_start_time_seconds",
+            "gauge",
+            "Start of the signer as a unix timestamp",
+            status
+                .started_at
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |since_epoch| since_epoch.as_secs()),
+        );
+
+        out
+    }
+}
+
+/// Writes the `HELP` and `TYPE` lines of a metric
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// Writes a metric with a single value
+fn gauge<V: std::fmt::Display>(out: &mut String, name: &str, kind: &str, help: &str, value: V) {
+    header(out, name, kind, help);
+
+    let _ = writeln!(out, "{} {}", name, value);
+}
@@ -0,0 +1,303 @@
+/*
+MIT License
+
+Copyright (c) 2020 Martin Karlsen Jensen
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use cloudfront_policy_signer::SignedCookies;
+
+/// Output formats for lists of signed resources
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    Json,
+    Ndjson,
+}
+
+impl Format {
+    /// Parses the value of `--format`
+    pub fn parse(format: &str) -> Result<Format, String> {
+        match format {
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            "ndjson" => Ok(Format::Ndjson),
+            _ => Err(format!(
+                "unknown format '{}', expected csv, json or ndjson",
+                format
+            )),
+        }
+    }
+}
+
+/// Output formats for signed cookies
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum CookieFormat {
+    /// One `name=value` pair per line
+    Plain,
+    /// One `Set-Cookie` header per line
+    Headers,
+    /// Netscape cookie jar as read by `curl -b`
+    CurlCookieJar,
+}
+
+impl CookieFormat {
+    /// Parses the value of `--format`
+    pub fn parse(format: &str) -> Result<CookieFormat, String> {
+        match format {
+            "plain" => Ok(CookieFormat::Plain),
+            "headers" => Ok(CookieFormat::Headers),
+            "curl-cookie-jar" => Ok(CookieFormat::CurlCookieJar),
+            _ => Err(format!(
+                "unknown format '{}', expected plain, headers or curl-cookie-jar",
+                format
+            )),
+        }
+    }
+}
+
+/// A resource and the signed URL created for it
+pub struct SignedRow {
+    pub resource: String,
+    pub expires: u64,
+    pub url: String,
+}
+
+/// Renders the rows in the requested format
+pub fn render(rows: &[SignedRow], format: Format) -> String {
+    match format {
+        Format::Csv => {
+            let mut output = String::from("resource,expires,url\n");
+
+            for row in rows {
+                output.push_str(&format!(
+                    "{},{},{}\n",
+                    csv_field(&row.resource),
+                    row.expires,
+                    csv_field(&row.url)
+                ));
+            }
+
+            output
+        }
+        Format::Json => {
+            let objects: Vec<String> = rows.iter().map(json_row).collect();
+
+            format!("[{}]\n", objects.join(","))
+        }
+        Format::Ndjson => rows.iter().map(|row| json_row(row) + "\n").collect(),
+    }
+}
+
+/// Renders the cookies in the requested format
+///
+/// # Arguments
+/// * `cookies` - The signed cookies
+/// * `format` - The output format
+/// * `domain` - The domain the cookies are valid for. A leading `.` includes subdomains
+/// * `path` - The path the cookies are valid for
+/// * `expires` - Unix timestamp the cookies expire at, used by the cookie jar
+pub fn render_cookies(
+    cookies: &SignedCookies,
+    format: CookieFormat,
+    domain: &str,
+    path: &str,
+    expires: u64,
+) -> Result<String, String> {
+    cookies.check_size().map_err(|e| e.to_string())?;
+
+    let rendered = match format {
+        CookieFormat::Plain => cookies
+            .iter()
+            .map(|(name, value)| format!("{}={}\n", name, value))
+            .collect(),
+        CookieFormat::Headers => cookies
+            .set_cookie_headers(Some(domain), Some(path))
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .map(|header| format!("Set-Cookie: {}\n", header))
+            .collect(),
+        CookieFormat::CurlCookieJar => {
+            let include_subdomains = if domain.starts_with('.') {
+                "TRUE"
+            } else {
+                "FALSE"
+            };
+            let mut output = String::from("# Netscape HTTP Cookie File\n");
+
+            for (name, value) in cookies.iter() {
+                output.push_str(&format!(
+                    "#HttpOnly_{}\t{}\t{}\tTRUE\t{}\t{}\t{}\n",
+                    domain, include_subdomains, path, expires, name, value
+                ));
+            }
+
+            output
+        }
+    };
+
+    Ok(rendered)
+}
+
+/// Returns the host of an absolute URL
+pub fn host_of(url: &str) -> Option<&str> {
+    let (_, rest) = url.split_once("://")?;
+    let host = rest.split(['/', '?', '#']).next()?;
+    let host = host.rsplit_once('@').map_or(host, |(_, host)| host);
+    let host = host.split(':').next()?;
+
+    (!host.is_empty()).then_some(host)
+}
+
+/// Formats a unix timestamp as a UTC date and time eg. 2020-01-20 14:58:51 UTC
+pub fn format_timestamp(timestamp: u64) -> String {
+    let days = (timestamp / 86_400) as i64;
+    let seconds = timestamp % 86_400;
+
+    // Converts days since the epoch to a civil date, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
+
+/// Formats a number of seconds as eg. 3h12m, keeping the two most significant units
+pub fn format_duration(seconds: u64) -> String {
+    let units = [("d", 86_400), ("h", 3600), ("m", 60), ("s", 1)];
+    let parts: Vec<String> = units
+        .iter()
+        .scan(seconds, |remaining, (unit, size)| {
+            let value = *remaining / size;
+
+            *remaining %= size;
+            Some((value, unit))
+        })
+        .skip_while(|(value, _)| *value == 0)
+        .take(2)
+        .filter(|(value, _)| *value > 0)
+        .map(|(value, unit)| format!("{}{}", value, unit))
+        .collect();
+
+    if parts.is_empty() {
+        "0s".to_string()
+    } else {
+        parts.concat()
+    }
+}
+
+/// Describes an expiry relative to now eg. `expires in 3h12m` or `expired 5m ago`
+pub fn describe_expiry(expires: u64, now: u64) -> String {
+    if expires > now {
+        format!("expires in {}", format_duration(expires - now))
+    } else {
+        format!("EXPIRED {} ago", format_duration(now - expires))
+    }
+}
+
+/// Describes an activation time relative to now eg. `active since 2h` or `NOT YET VALID, starts in 5m`
+pub fn describe_activation(not_before: u64, now: u64) -> String {
+    if not_before > now {
+        format!(
+            "NOT YET VALID, starts in {}",
+            format_duration(not_before - now)
+        )
+    } else {
+        format!("active since {}", format_duration(now - not_before))
+    }
+}
+
+fn json_row(row: &SignedRow) -> String {
+    format!(
+        "{{\"resource\":{},\"expires\":{},\"url\":{}}}",
+        json_string(&row.resource),
+        row.expires,
+        json_string(&row.url)
+    )
+}
+
+/// Quotes a CSV field if it contains a separator, quote or line break
+pub fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Splits a CSV line into its fields, honouring quoted fields
+pub fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+
+    fields.push(field);
+    fields
+}
+
+/// Encodes a string as a JSON string literal
+pub fn json_string(value: &str) -> String {
+    let mut output = String::with_capacity(value.len() + 2);
+
+    output.push('"');
+
+    for c in value.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            c if (c as u32) < 0x20 => output.push_str(&format!("\\u{:04x}", c as u32)),
+            c => output.push(c),
+        }
+    }
+
+    output.push('"');
+    output
+}
@@ -0,0 +1,120 @@
+/*
+MIT License
+
+Copyright (c) 2020 Martin Karlsen Jensen
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+//! Reloading of the signing key of the service when its files change
+//!
+//! Meant for keys mounted from a Kubernetes secret: the kubelet swaps the files atomically on
+//! rotation, so the files are compared by content rather than modification time. When the new key
+//! cannot be loaded the service keeps signing with the previous key and `/readyz` fails.
+
+use crate::args::Args;
+use cloudfront_policy_signer::template::parse_ttl;
+use cloudfront_policy_signer::{CloudFrontSigner, Error, ReloadingSigner};
+use std::fs;
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+/// How often the key files are checked without `--reload-interval`
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Loads the signer from `--key` and `--key-pair-id` or `--key-pair-id-file`
+///
+/// # Arguments
+/// * `args` - The parsed arguments
+pub fn signer(args: &Args) -> Result<ReloadingSigner, String> {
+    let key = PathBuf::from(args.required("key")?);
+    let key_pair_id = match args.option("key-pair-id-file") {
+        Some(path) => KeyPairId::File(PathBuf::from(path)),
+        None => KeyPairId::Fixed(args.required("key-pair-id")?.to_string()),
+    };
+
+    ReloadingSigner::new(move || {
+        let key_pair_id = match &key_pair_id {
+            KeyPairId::Fixed(key_pair_id) => key_pair_id.clone(),
+            KeyPairId::File(path) => fs::read_to_string(path)
+                .map_err(Error::IOError)?
+                .trim()
+                .to_string(),
+        };
+
+        CloudFrontSigner::new(&key, key_pair_id)
+    })
+    .map_err(|e| e.to_string())
+}
+
+/// Where the key pair ID of the signer comes from
+enum KeyPairId {
+    Fixed(String),
+    File(PathBuf),
+}
+
+/// Reloads the signer on a background thread whenever the key files change
+///
+/// # Arguments
+/// * `args` - The parsed arguments, `--reload-interval` sets how often the files are checked
+/// * `signer` - The signer loaded by [`signer`]
+pub fn watch(args: &Args, signer: ReloadingSigner) -> Result<(), String> {
+    let interval = match args.option("reload-interval") {
+        Some(interval) => parse_ttl(interval).map_err(|e| e.to_string())?,
+        None => DEFAULT_INTERVAL,
+    };
+    let paths = [args.option("key"), args.option("key-pair-id-file")]
+        .into_iter()
+        .flatten()
+        .map(PathBuf::from)
+        .collect::<Vec<_>>();
+    let contents = move || {
+        paths
+            .iter()
+            .map(|path| fs::read(path).ok())
+            .collect::<Vec<_>>()
+    };
+    let mut loaded = contents();
+
+    thread::spawn(move || loop {
+        thread::sleep(interval);
+
+        let current = contents();
+
+        if current == loaded {
+            continue;
+        }
+
+        loaded = current;
+
+        match signer.reload() {
+            Ok(()) => eprintln!(
+                "Reloaded the private key for {}",
+                signer.current().key_pair_id()
+            ),
+            Err(e) => eprintln!(
+                "warning: could not reload the private key, still signing with the previous key: {}",
+                e
+            ),
+        }
+    });
+
+    Ok(())
+}
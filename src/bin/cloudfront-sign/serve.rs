@@ -0,0 +1,380 @@
+/*
+MIT License
+
+Copyright (c) 2020 Martin Karlsen Jensen
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+//! The signing service started by `cloudfront-sign serve`
+//!
+//! `GET /sign?resource=<url>&ttl=<duration>` answers with `{"url":...,"expires":...}`,
+//! `GET /metrics` with Prometheus metrics and `GET /healthz` with `ok`. `GET /readyz` fails with
+//! 503 while the key files changed but could not be reloaded, see the [`crate::reload`] module.
+//! Every response carries a `traceparent` header, see the [`crate::trace`] module.
+//!
+//! With `--api-keys` or `--client-ca` only authenticated callers may sign, see the
+//! [`crate::auth`] module, and every signing request is written to stderr as an audit line.
+//!
+//! On SIGTERM the service stops accepting connections and finishes the requests in progress
+//! before exiting, see the [`crate::daemon`] module for the systemd integration.
+
+use crate::args::Args;
+use crate::auth::{self, ApiKeys, Principal};
+use crate::daemon;
+use crate::http::{Request, Response};
+use crate::metrics::Metrics;
+use crate::output::json_string;
+use crate::reload;
+use crate::trace::{Span, SpanLog};
+use cloudfront_policy_signer::template::parse_ttl;
+use cloudfront_policy_signer::ReloadingSigner;
+use openssl::ssl::SslAcceptor;
+use std::io::{self, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The address the service listens on without `--listen`
+const DEFAULT_LISTEN: &str = "127.0.0.1:8080";
+
+/// How long a connection may take to send its request
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often the listener is checked for connections and the process for a shutdown request
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How long requests in progress may take to finish on shutdown without `--drain-timeout`
+const DEFAULT_DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Runs the service until SIGTERM or SIGINT is received and the requests in progress finished
+///
+/// # Arguments
+/// * `args` - The parsed arguments
+pub fn run(args: &Args) -> Result<(), String> {
+    let default_ttl = match args.option("ttl") {
+        Some(ttl) => parse_ttl(ttl).map_err(|e| e.to_string())?,
+        None => Duration::from_secs(60 * 60),
+    };
+    let drain_timeout = match args.option("drain-timeout") {
+        Some(timeout) => parse_ttl(timeout).map_err(|e| e.to_string())?,
+        None => DEFAULT_DRAIN_TIMEOUT,
+    };
+    let signer = reload::signer(args)?;
+
+    reload::watch(args, signer.clone())?;
+
+    let service = Arc::new(Service {
+        signer,
+        metrics: Metrics::default(),
+        default_ttl,
+        spans: args.option("spans").map(SpanLog::open).transpose()?,
+        api_keys: args.option("api-keys").map(ApiKeys::load).transpose()?,
+        tls: tls_acceptor(args)?,
+    });
+    let listen = args.option("listen").unwrap_or(DEFAULT_LISTEN);
+    let listener =
+        TcpListener::bind(listen).map_err(|e| format!("could not listen on {}: {}", listen, e))?;
+
+    let address = listener.local_addr().map_err(|e| e.to_string())?;
+
+    eprintln!("Listening on {}", address);
+
+    if service.api_keys.is_none()
+        && args.option("client-ca").is_none()
+        && !address.ip().is_loopback()
+    {
+        eprintln!(
+            "warning: anyone who can reach {} can sign, use --api-keys or --client-ca",
+            address
+        );
+    }
+
+    listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+    daemon::handle_signals();
+    daemon::notify("READY=1");
+
+    let in_progress = Arc::new(AtomicUsize::new(0));
+
+    while !daemon::shutdown_requested() {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                let service = Arc::clone(&service);
+                let connection = Connection::start(&in_progress);
+                let _ = stream.set_nonblocking(false);
+
+                thread::spawn(move || {
+                    service.accept(stream);
+                    drop(connection);
+                });
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(ACCEPT_INTERVAL),
+            Err(e) => eprintln!("warning: could not accept a connection: {}", e),
+        }
+    }
+
+    daemon::notify("STOPPING=1");
+    drop(listener);
+    drain(&in_progress, drain_timeout);
+
+    Ok(())
+}
+
+/// Waits until no connection is in progress or the timeout elapsed
+fn drain(in_progress: &AtomicUsize, timeout: Duration) {
+    let started = Instant::now();
+    let remaining = in_progress.load(Ordering::SeqCst);
+
+    eprintln!("Shutting down, finishing {} requests", remaining);
+
+    while in_progress.load(Ordering::SeqCst) > 0 {
+        if started.elapsed() >= timeout {
+            eprintln!(
+                "warning: {} requests did not finish within {:?}",
+                in_progress.load(Ordering::SeqCst),
+                timeout
+            );
+            return;
+        }
+
+        thread::sleep(ACCEPT_INTERVAL);
+    }
+}
+
+/// Counts a connection as in progress until dropped, even if answering it panicked
+struct Connection(Arc<AtomicUsize>);
+
+impl Connection {
+    fn start(in_progress: &Arc<AtomicUsize>) -> Connection {
+        in_progress.fetch_add(1, Ordering::SeqCst);
+
+        Connection(Arc::clone(in_progress))
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+struct Service {
+    signer: ReloadingSigner,
+    metrics: Metrics,
+    default_ttl: Duration,
+    spans: Option<SpanLog>,
+    api_keys: Option<ApiKeys>,
+    tls: Option<SslAcceptor>,
+}
+
+/// Creates the TLS acceptor from `--tls-cert`, `--tls-key` and `--client-ca` if given
+fn tls_acceptor(args: &Args) -> Result<Option<SslAcceptor>, String> {
+    match (args.option("tls-cert"), args.option("tls-key")) {
+        (Some(certificate), Some(private_key)) => {
+            auth::tls_acceptor(certificate, private_key, args.option("client-ca")).map(Some)
+        }
+        (None, None) if args.option("client-ca").is_none() => Ok(None),
+        (None, None) => Err("--client-ca requires --tls-cert and --tls-key".to_string()),
+        _ => Err("--tls-cert and --tls-key must be given together".to_string()),
+    }
+}
+
+impl Service {
+    /// Performs the TLS handshake if enabled and answers the request of the connection
+    fn accept(&self, stream: TcpStream) {
+        let _ = stream.set_read_timeout(Some(READ_TIMEOUT));
+
+        match &self.tls {
+            Some(acceptor) => match acceptor.accept(stream) {
+                Ok(stream) => {
+                    let peer = auth::peer_name(&stream);
+
+                    self.handle(stream, peer.as_deref());
+                }
+                Err(e) => eprintln!("warning: TLS handshake failed: {}", e),
+            },
+            None => self.handle(stream, None),
+        }
+    }
+
+    /// Answers the request of a connection and records it in the metrics and the trace
+    ///
+    /// # Arguments
+    /// * `stream` - The connection
+    /// * `peer` - The common name of the verified client certificate
+    fn handle<S: Read + Write>(&self, mut stream: S, peer: Option<&str>) {
+        let started = Instant::now();
+
+        let (span, name, endpoint, response) = match Request::read(BufReader::new(&mut stream)) {
+            Ok(request) => {
+                let span = Span::start(request.header("traceparent"));
+                let (endpoint, response) = self.route(&request, peer);
+
+                (
+                    span,
+                    format!("{} {}", request.method, endpoint),
+                    endpoint,
+                    response,
+                )
+            }
+            Err(e) => (
+                Span::start(None),
+                "invalid request".to_string(),
+                "other",
+                Response::text(400, e),
+            ),
+        };
+        let response = response.header("traceparent", span.traceparent());
+
+        self.metrics
+            .record(endpoint, response.status, started.elapsed());
+
+        if let Some(spans) = &self.spans {
+            if let Some(finished) = span.finish(&name, response.status) {
+                spans.write(&finished);
+            }
+        }
+
+        if let Err(e) = response.write_to(&mut stream) {
+            eprintln!("warning: could not write a response: {}", e);
+        }
+    }
+
+    /// Returns the endpoint for the metrics and the response to the request
+    fn route(&self, request: &Request, peer: Option<&str>) -> (&'static str, Response) {
+        let endpoint = match request.path.as_str() {
+            "/sign" => "/sign",
+            "/metrics" => "/metrics",
+            "/healthz" => "/healthz",
+            "/readyz" => "/readyz",
+            _ => return ("other", Response::text(404, "not found")),
+        };
+
+        if request.method != "GET" {
+            return (endpoint, Response::text(405, "only GET is supported"));
+        }
+
+        let response = match endpoint {
+            "/sign" => match self.authenticate(request, peer) {
+                Ok(principal) => self.sign(request, &principal),
+                Err(response) => response,
+            },
+            "/metrics" => Response::new(
+                200,
+                "text/plain; version=0.0.4",
+                self.metrics.render(&self.signer.current().status()),
+            ),
+            "/readyz" => match self.signer.status().last_error {
+                Some(reason) => {
+                    Response::text(503, format!("could not reload the key: {}", reason))
+                }
+                None => Response::text(200, "ready"),
+            },
+            _ => Response::text(200, "ok"),
+        };
+
+        (endpoint, response)
+    }
+
+    /// Returns the caller of a request, failing with a 401 response if an API key is required
+    ///
+    /// Without API keys the common name of the client certificate names the caller.
+    fn authenticate(&self, request: &Request, peer: Option<&str>) -> Result<Principal, Response> {
+        match &self.api_keys {
+            Some(api_keys) => api_keys.authenticate(request).cloned().ok_or_else(|| {
+                eprintln!("audit: principal=- path={} status=401", request.path);
+
+                error(401, "missing or invalid API key")
+                    .header("www-authenticate", "Bearer".to_string())
+            }),
+            None => Ok(Principal::unrestricted(peer.unwrap_or("anonymous"))),
+        }
+    }
+
+    /// Signs the resource from the query string and writes the audit line of the caller
+    fn sign(&self, request: &Request, principal: &Principal) -> Response {
+        let resource = request.query("resource");
+        let (ttl, response) = match resource {
+            Some(resource) => self.sign_resource(request, resource, principal),
+            None => (None, error(400, "missing the resource parameter")),
+        };
+
+        eprintln!(
+            "audit: principal={} resource={} ttl={} status={}",
+            principal.name,
+            resource.map_or_else(|| "-".to_string(), json_string),
+            ttl.map_or_else(|| "-".to_string(), |ttl| ttl.as_secs().to_string()),
+            response.status
+        );
+
+        response
+    }
+
+    /// Signs a resource within the limits of the caller, returning the TTL and the response
+    ///
+    /// A requested TTL above the limit is refused, the default TTL is capped to it. Resources
+    /// outside the prefixes and distributions of the caller are refused.
+    fn sign_resource(
+        &self,
+        request: &Request,
+        resource: &str,
+        principal: &Principal,
+    ) -> (Option<Duration>, Response) {
+        let ttl = match request.query("ttl").map(parse_ttl).transpose() {
+            Ok(Some(ttl)) => ttl,
+            Ok(None) => principal
+                .max_ttl
+                .map_or(self.default_ttl, |max_ttl| self.default_ttl.min(max_ttl)),
+            Err(e) => return (None, error(400, &e.to_string())),
+        };
+
+        if let Some(max_ttl) = principal.max_ttl.filter(|&max_ttl| ttl > max_ttl) {
+            let message = format!("the TTL may be at most {} seconds", max_ttl.as_secs());
+
+            return (Some(ttl), error(403, &message));
+        }
+
+        let signer = self.signer.current();
+
+        if let Err(message) = principal.permits(&signer.resolve_resource(resource)) {
+            return (Some(ttl), error(403, &message));
+        }
+
+        let response = match signer.sign_with_ttl(resource, ttl) {
+            Ok(signed_url) => Response::json(
+                200,
+                format!(
+                    "{{\"url\":{},\"expires\":{}}}",
+                    json_string(signed_url.as_str()),
+                    signed_url.expires_at()
+                ),
+            ),
+            Err(e) => error(e.http_status(), &e.to_string()),
+        };
+
+        (Some(ttl), response)
+    }
+}
+
+/// Returns a JSON error response
+fn error(status: u16, message: &str) -> Response {
+    Response::json(status, format!("{{\"error\":{}}}", json_string(message)))
+}
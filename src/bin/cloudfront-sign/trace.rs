@@ -0,0 +1,201 @@
+/*
+MIT License
+
+Copyright (c) 2020 Martin Karlsen Jensen
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+//! W3C trace context propagation and spans for the signing service
+//!
+//! A `traceparent` header of the caller makes the span of the request its child, otherwise a new
+//! trace is started. The `traceparent` of the span is returned in the response. With `--spans`
+//! every sampled span is appended to a file as a line of OTLP JSON, which the filelog receiver of
+//! the OpenTelemetry Collector can forward to any tracing backend.
+
+use crate::output::json_string;
+use openssl::rand::rand_bytes;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The span of a request to the service
+pub struct Span {
+    trace_id: String,
+    span_id: String,
+    parent_span_id: Option<String>,
+    sampled: bool,
+    start: SystemTime,
+}
+
+impl Span {
+    /// Starts a span, continuing the trace of a valid `traceparent` header
+    ///
+    /// # Arguments
+    /// * `traceparent` - The `traceparent` header of the request, if any
+    pub fn start(traceparent: Option<&str>) -> Span {
+        let start = SystemTime::now();
+        let span_id = random_hex::<8>();
+
+        match traceparent.and_then(parse_traceparent) {
+            Some((trace_id, parent_span_id, sampled)) => Span {
+                trace_id,
+                span_id,
+                parent_span_id: Some(parent_span_id),
+                sampled,
+                start,
+            },
+            None => Span {
+                trace_id: random_hex::<16>(),
+                span_id,
+                parent_span_id: None,
+                sampled: true,
+                start,
+            },
+        }
+    }
+
+    /// Returns the `traceparent` header naming this span as the parent
+    pub fn traceparent(&self) -> String {
+        format!(
+            "00-{}-{}-{}",
+            self.trace_id,
+            self.span_id,
+            if self.sampled { "01" } else { "00" }
+        )
+    }
+
+    /// Ends the span and returns it as a line of OTLP JSON, `None` if the trace is not sampled
+    ///
+    /// # Arguments
+    /// * `name` - The name of the span eg. GET /sign
+    /// * `status` - The status code of the response
+    pub fn finish(&self, name: &str, status: u16) -> Option<String> {
+        if !self.sampled {
+            return None;
+        }
+
+        let parent = self
+            .parent_span_id
+            .as_ref()
+            .map(|parent| format!(",\"parentSpanId\":\"{}\"", parent))
+            .unwrap_or_default();
+
+        Some(format!(
+            concat!(
+                "{{\"resourceSpans\":[{{\"resource\":{{\"attributes\":[{{\"key\":\"service.name\",",
+                "\"value\":{{\"stringValue\":\"cloudfront-sign\"}}}}]}},\"scopeSpans\":[{{\"spans\":[{{",
+                "\"traceId\":\"{}\",\"spanId\":\"{}\"{},\"name\":{},\"kind\":2,",
+                "\"startTimeUnixNano\":\"{}\",\"endTimeUnixNano\":\"{}\",",
+                "\"attributes\":[{{\"key\":\"http.response.status_code\",\"value\":{{\"intValue\":\"{}\"}}}}],",
+                "\"status\":{{\"code\":{}}}}}]}}]}}]}}"
+            ),
+            self.trace_id,
+            self.span_id,
+            parent,
+            json_string(name),
+            unix_nanos(self.start),
+            unix_nanos(SystemTime::now()),
+            status,
+            if status >= 500 { 2 } else { 0 }
+        ))
+    }
+}
+
+/// A file that finished spans are appended to, one per line
+pub struct SpanLog {
+    file: Mutex<File>,
+}
+
+impl SpanLog {
+    /// Opens the file for appending, creating it if needed
+    pub fn open(path: &str) -> Result<SpanLog, String> {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map(|file| SpanLog {
+                file: Mutex::new(file),
+            })
+            .map_err(|e| format!("could not open {}: {}", path, e))
+    }
+
+    /// Appends a span
+    pub fn write(&self, span: &str) {
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+
+        if let Err(e) = writeln!(file, "{}", span) {
+            eprintln!("warning: could not write a span: {}", e);
+        }
+    }
+}
+
+/// Returns the trace ID, parent span ID and whether the trace is sampled from a `traceparent` header
+///
+/// Unknown versions are read like version 00 as the specification requires, IDs of all zeros are rejected.
+fn parse_traceparent(traceparent: &str) -> Option<(String, String, bool)> {
+    let mut parts = traceparent.trim().split('-');
+    let (version, trace_id, parent_id, flags) =
+        (parts.next()?, parts.next()?, parts.next()?, parts.next()?);
+    let is_hex = |value: &str, len: usize| {
+        value.len() == len
+            && value
+                .bytes()
+                .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+    };
+
+    if !is_hex(version, 2) || version == "ff" || (version == "00" && parts.next().is_some()) {
+        return None;
+    }
+
+    if !is_hex(trace_id, 32) || !is_hex(parent_id, 16) || !is_hex(flags, 2) {
+        return None;
+    }
+
+    if trace_id.bytes().all(|b| b == b'0') || parent_id.bytes().all(|b| b == b'0') {
+        return None;
+    }
+
+    let flags = u8::from_str_radix(flags, 16).ok()?;
+
+    Some((trace_id.to_string(), parent_id.to_string(), flags & 1 == 1))
+}
+
+/// Returns `N` random bytes as lowercase hex
+fn random_hex<const N: usize>() -> String {
+    let mut bytes = [0u8; N];
+
+    // Fall back to the time if the random generator fails, IDs only have to be unique
+    if rand_bytes(&mut bytes).is_err() {
+        let nanos = unix_nanos(SystemTime::now()).to_be_bytes();
+
+        for (byte, nano) in bytes.iter_mut().zip(nanos.iter().cycle()) {
+            *byte = *nano;
+        }
+    }
+
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Returns the time in nanoseconds since the unix epoch
+fn unix_nanos(time: SystemTime) -> u128 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |since_epoch| since_epoch.as_nanos())
+}
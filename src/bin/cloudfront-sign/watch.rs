@@ -0,0 +1,112 @@
+/*
+MIT License
+
+Copyright (c) 2020 Martin Karlsen Jensen
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+//! Re-signing of URLs and cookies before they expire
+
+use crate::args::Args;
+use crate::output::format_timestamp;
+use crate::{expiry, signer};
+use cloudfront_policy_signer::CloudFrontSigner;
+use std::fs;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How often the clock and the key file are checked
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Prints the output of `sign` and prints it again shortly before it expires or when the key file changes
+///
+/// Runs until the process is interrupted.
+///
+/// # Arguments
+/// * `args` - The parsed arguments, `--expires` is not allowed as the output would never change
+/// * `sign` - Signs with the given signer and expiry and returns the text to print
+pub fn run<F>(args: &Args, mut sign: F) -> Result<(), String>
+where
+    F: FnMut(&CloudFrontSigner, u64) -> Result<String, String>,
+{
+    if args.option("expires").is_some() {
+        return Err("--watch cannot be combined with --expires, use --ttl".to_string());
+    }
+
+    let key_path = args.required("key")?;
+    let mut signer = signer(args)?;
+    let mut key_modified = modified(key_path);
+
+    loop {
+        let now = now()?;
+        let expires = expiry(args, None)?;
+        // Re-sign when 10% of the lifetime is left, but at least a second and at most a minute early
+        let margin = ((expires - now) / 10).clamp(1, 60);
+        let resign_at = expires.saturating_sub(margin);
+
+        println!(
+            "# signed at {}, expires at {}, re-signing at {}",
+            format_timestamp(now),
+            format_timestamp(expires),
+            format_timestamp(resign_at)
+        );
+        println!("{}", sign(&signer, expires)?);
+
+        loop {
+            thread::sleep(POLL_INTERVAL);
+
+            let current_modified = modified(key_path);
+
+            if current_modified != key_modified {
+                key_modified = current_modified;
+
+                match crate::signer(args) {
+                    Ok(reloaded) => {
+                        eprintln!("# key file changed, re-signing");
+                        signer = reloaded;
+                        break;
+                    }
+                    Err(e) => eprintln!("# key file changed but could not be loaded: {}", e),
+                }
+            }
+
+            if now_or_zero() >= resign_at {
+                break;
+            }
+        }
+    }
+}
+
+fn modified(path: &str) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+fn now() -> Result<u64, String> {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_secs())
+        .map_err(|e| e.to_string())
+}
+
+fn now_or_zero() -> u64 {
+    now().unwrap_or_default()
+}
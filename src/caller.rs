@@ -0,0 +1,205 @@
+/*
+MIT License
+
+Copyright (c) 2020 Martin Karlsen Jensen
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use crate::{jwt, CloudFrontSigner, Error, Policy, SignedCookies, SignedPolicy, SignedUrl};
+
+/// Signs on behalf of a caller, created by [`CloudFrontSigner::for_caller`]
+///
+/// The caller tag, eg. a tenant ID, is attached to the samples of the signer and selects the
+/// token bucket of [`CloudFrontSigner::rate_limit`].
+///
+/// # Example
+/// ```
+/// use cloudfront_policy_signer::CloudFrontSigner;
+///
+/// let signer = CloudFrontSigner::new("examples/key.pem", "APKAIEXAMPLE").unwrap();
+/// let url = signer
+///     .for_caller("tenant-42")
+///     .metadata("user", "42")
+///     .metadata("purpose", "share")
+///     .metadata_parameter("meta")
+///     .sign_url("https://example.cloudfront.net/flowerpot.png", 1700000000)
+///     .unwrap();
+///
+/// assert!(url
+///     .as_str()
+///     .starts_with("https://example.cloudfront.net/flowerpot.png?meta=user%3D42%26purpose%3Dshare&Expires="));
+/// ```
+pub struct CallerSigner<'a> {
+    signer: &'a CloudFrontSigner,
+    caller: &'a str,
+    metadata: Vec<(String, String)>,
+    metadata_parameter: Option<String>,
+}
+
+/// The caller tag and metadata a grant is issued with
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Attribution<'a> {
+    pub(crate) caller: &'a str,
+    pub(crate) metadata: &'a [(String, String)],
+}
+
+impl<'a> CallerSigner<'a> {
+    pub(crate) fn new(signer: &'a CloudFrontSigner, caller: &'a str) -> CallerSigner<'a> {
+        CallerSigner {
+            signer,
+            caller,
+            metadata: Vec::new(),
+            metadata_parameter: None,
+        }
+    }
+
+    /// Returns the caller tag
+    pub fn caller(&self) -> &str {
+        self.caller
+    }
+
+    /// Attaches opaque metadata to everything signed, which is passed to the sampling sink of the signer
+    ///
+    /// # Arguments
+    /// * `key` - The name of the metadata eg. user
+    /// * `value` - The value of the metadata eg. a user ID
+    pub fn metadata<K: Into<String>, V: Into<String>>(
+        mut self,
+        key: K,
+        value: V,
+    ) -> CallerSigner<'a> {
+        self.metadata.push((key.into(), value.into()));
+        self
+    }
+
+    /// Also puts the metadata in a query parameter of signed URLs, so it appears in the CloudFront access logs
+    ///
+    /// The value is the metadata as `key=value` pairs joined with `&`, percent-encoded. CloudFront
+    /// compares the whole URL with the signed resource, so the parameter is added before signing
+    /// and viewers cannot change it. Cookies and custom policies are not affected.
+    ///
+    /// # Arguments
+    /// * `name` - The name of the query parameter eg. meta
+    pub fn metadata_parameter<T: Into<String>>(mut self, name: T) -> CallerSigner<'a> {
+        self.metadata_parameter = Some(name.into());
+        self
+    }
+
+    fn attribution(&self) -> Option<Attribution<'_>> {
+        Some(Attribution {
+            caller: self.caller,
+            metadata: &self.metadata,
+        })
+    }
+
+    /// Creates a URL with a canned policy, see [`CloudFrontSigner::sign_url`]
+    ///
+    /// # Arguments
+    /// * `resource` - The protected resource eg. https://example.cloudfront.net/flowerpot.png
+    /// * `expiry` - Absolute time that the URL expires, given in the form of a unix timestamp in UTC
+    pub fn sign_url<R: AsRef<str>>(&self, resource: R, expiry: u64) -> Result<SignedUrl, Error> {
+        let resource = resource.as_ref();
+
+        match &self.metadata_parameter {
+            Some(name) if !self.metadata.is_empty() => {
+                let separator = if resource.contains('?') { '&' } else { '?' };
+                let metadata = self
+                    .metadata
+                    .iter()
+                    .map(|(key, value)| format!("{}={}", key, value))
+                    .collect::<Vec<_>>()
+                    .join("&");
+                let resource = format!(
+                    "{}{}{}={}",
+                    resource,
+                    separator,
+                    name,
+                    percent_encode(&metadata)
+                );
+
+                self.signer
+                    .sign_url_for(self.attribution(), &resource, expiry)
+            }
+            _ => self
+                .signer
+                .sign_url_for(self.attribution(), resource, expiry),
+        }
+    }
+
+    /// Creates cookies with a canned policy, see [`CloudFrontSigner::create_canned_policy_signed_cookies`]
+    ///
+    /// # Arguments
+    /// * `resource` - The protected resource eg. https://example.cloudfront.net/flowerpot.png
+    /// * `expiry` - Absolute time that the cookies expire, given in the form of a unix timestamp in UTC
+    pub fn create_canned_policy_signed_cookies<R: AsRef<str>>(
+        &self,
+        resource: R,
+        expiry: u64,
+    ) -> Result<SignedCookies, Error> {
+        self.signer
+            .canned_cookies_for(self.attribution(), resource.as_ref(), expiry)
+    }
+
+    /// Signs a custom policy, see [`CloudFrontSigner::sign_policy`]
+    ///
+    /// # Arguments
+    /// * `policy` - The policy to sign
+    pub fn sign_policy(&self, policy: &Policy) -> Result<SignedPolicy, Error> {
+        self.signer.sign_policy_for(self.attribution(), policy)
+    }
+
+    /// Signs an access token, see [`CloudFrontSigner::sign_jwt`]
+    ///
+    /// # Arguments
+    /// * `claims` - The claims of the token
+    ///
+    /// # Example
+    /// ```
+    /// use cloudfront_policy_signer::{jwt::Claims, CloudFrontSigner, Error};
+    ///
+    /// let signer = CloudFrontSigner::new("examples/key.pem", "APKAIEXAMPLE")
+    ///     .unwrap()
+    ///     .rate_limit(1.0, 1);
+    /// let claims = Claims::new("https://example.cloudfront.net/videos/*", 1700000000);
+    ///
+    /// assert!(signer.for_caller("tenant-42").sign_jwt(&claims).is_ok());
+    /// assert!(matches!(
+    ///     signer.for_caller("tenant-42").sign_jwt(&claims),
+    ///     Err(Error::RateLimited(_))
+    /// ));
+    /// assert_eq!(signer.status().signed, 1);
+    /// ```
+    pub fn sign_jwt(&self, claims: &jwt::Claims) -> Result<String, Error> {
+        self.signer.sign_jwt_for(self.attribution(), claims)
+    }
+}
+
+/// Percent-encodes every byte except unreserved characters
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
@@ -0,0 +1,137 @@
+/*
+MIT License
+
+Copyright (c) 2020 Martin Karlsen Jensen
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use crate::sampling::evenly_selected;
+use crate::{CloudFrontSigner, Error, Policy, SignedCookies, SignedPolicy, SignedUrl};
+use std::sync::atomic::AtomicU64;
+
+/// Signs a part of all requests with a new key and the rest with the current key, to validate a key rotation gradually
+///
+/// Which key signed an output is shown by its `key_pair_id()`, which can be passed to
+/// [`CanarySigner::is_canary`] eg. to tag metrics or logs.
+///
+/// # Example
+/// ```
+/// use cloudfront_policy_signer::{CanarySigner, CloudFrontSigner};
+///
+/// let current = CloudFrontSigner::new("examples/key.pem", "APKAIEXAMPLE").unwrap();
+/// let new = CloudFrontSigner::new("examples/key.pem", "APKAINEWEXAMPLE").unwrap();
+/// let signer = CanarySigner::new(current, new, 0.25).unwrap();
+/// let canaries = (0..8)
+///     .map(|_| {
+///         signer
+///             .sign_url("https://example.cloudfront.net/flowerpot.png", 1700000000)
+///             .unwrap()
+///     })
+///     .filter(|signed_url| signer.is_canary(signed_url.key_pair_id()))
+///     .count();
+///
+/// assert_eq!(canaries, 2);
+/// ```
+#[derive(Debug)]
+pub struct CanarySigner {
+    current: CloudFrontSigner,
+    canary: CloudFrontSigner,
+    rate: f64,
+    issued: AtomicU64,
+}
+
+impl CanarySigner {
+    /// Constructs a signer that sends a part of all requests to the canary
+    ///
+    /// Fails with [`Error::InvalidConfig`] if both signers have the same key pair ID, as the
+    /// outputs could then not be told apart.
+    ///
+    /// # Arguments
+    /// * `current` - The signer with the key that is being replaced
+    /// * `canary` - The signer with the new key
+    /// * `rate` - The part of requests signed by the canary, between 0 and 1 eg. 0.05 for 5%
+    pub fn new(
+        current: CloudFrontSigner,
+        canary: CloudFrontSigner,
+        rate: f64,
+    ) -> Result<CanarySigner, Error> {
+        if current.key_pair_id() == canary.key_pair_id() {
+            return Err(Error::InvalidConfig(format!(
+                "the canary has the same key pair ID {} as the current signer",
+                canary.key_pair_id()
+            )));
+        }
+
+        Ok(CanarySigner {
+            current,
+            canary,
+            rate: rate.clamp(0.0, 1.0),
+            issued: AtomicU64::new(0),
+        })
+    }
+
+    /// Whether an output with this key pair ID was signed by the canary
+    ///
+    /// # Arguments
+    /// * `key_pair_id` - The key pair ID of a signed URL, cookies or policy
+    pub fn is_canary(&self, key_pair_id: &str) -> bool {
+        self.canary.key_pair_id() == key_pair_id
+    }
+
+    /// Returns the signer for the next request
+    fn next(&self) -> &CloudFrontSigner {
+        if evenly_selected(&self.issued, self.rate) {
+            &self.canary
+        } else {
+            &self.current
+        }
+    }
+
+    /// Creates a URL with a canned policy, see [`CloudFrontSigner::sign_url`]
+    ///
+    /// # Arguments
+    /// * `resource` - The protected resource eg. https://example.cloudfront.net/flowerpot.png
+    /// * `expiry` - Absolute time that the URL expires, given in the form of a unix timestamp in UTC
+    pub fn sign_url<R: AsRef<str>>(&self, resource: R, expiry: u64) -> Result<SignedUrl, Error> {
+        self.next().sign_url(resource, expiry)
+    }
+
+    /// Creates cookies with a canned policy, see [`CloudFrontSigner::create_canned_policy_signed_cookies`]
+    ///
+    /// # Arguments
+    /// * `resource` - The protected resource eg. https://example.cloudfront.net/flowerpot.png
+    /// * `expiry` - Absolute time that the cookies expire, given in the form of a unix timestamp in UTC
+    pub fn create_canned_policy_signed_cookies<R: AsRef<str>>(
+        &self,
+        resource: R,
+        expiry: u64,
+    ) -> Result<SignedCookies, Error> {
+        self.next()
+            .create_canned_policy_signed_cookies(resource, expiry)
+    }
+
+    /// Signs a custom policy, see [`CloudFrontSigner::sign_policy`]
+    ///
+    /// # Arguments
+    /// * `policy` - The policy to sign
+    pub fn sign_policy(&self, policy: &Policy) -> Result<SignedPolicy, Error> {
+        self.next().sign_policy(policy)
+    }
+}
@@ -0,0 +1,96 @@
+/*
+MIT License
+
+Copyright (c) 2020 Martin Karlsen Jensen
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use crate::Policy;
+
+/// The end of a canned policy document following the expiry
+pub(crate) const SUFFIX: &str = "}}}]}";
+
+/// A canned policy for a fixed resource whose document is computed at compile time
+///
+/// Created with the `canned_policy!` macro of the `macros` feature, which validates the resource
+/// like `resource!` and embeds the document up to the expiry. Signing only splices in the expiry,
+/// so no policy is built or allocated, see [`crate::CloudFrontSigner::sign_url_embedded`].
+///
+/// # Example
+/// ```
+/// # #[cfg(feature = "macros")]
+/// # {
+/// use cloudfront_policy_signer::{canned_policy, CannedPolicyTemplate, Policy};
+///
+/// static FLOWERPOT: CannedPolicyTemplate =
+///     canned_policy!("https://example.cloudfront.net/flowerpot.png");
+///
+/// assert_eq!(
+///     FLOWERPOT.policy(1700000000),
+///     Policy::canned("https://example.cloudfront.net/flowerpot.png", 1700000000)
+///         .to_json()
+///         .into_bytes()
+/// );
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CannedPolicyTemplate {
+    resource: &'static str,
+    prefix: &'static str,
+}
+
+impl CannedPolicyTemplate {
+    /// Wraps the parts computed by the `canned_policy!` macro, which are not checked again
+    ///
+    /// # Arguments
+    /// * `resource` - The resource of the policy
+    /// * `prefix` - The policy document up to the expiry
+    #[doc(hidden)]
+    pub const fn from_parts(resource: &'static str, prefix: &'static str) -> CannedPolicyTemplate {
+        CannedPolicyTemplate { resource, prefix }
+    }
+
+    /// Returns the resource of the policy
+    pub fn resource(&self) -> &'static str {
+        self.resource
+    }
+
+    /// Returns the policy document up to the expiry
+    #[cfg(feature = "sign")]
+    pub(crate) fn prefix(&self) -> &'static str {
+        self.prefix
+    }
+
+    /// Returns the policy document for the expiry, the same bytes as the canned [`Policy`]
+    ///
+    /// # Arguments
+    /// * `expiry` - Absolute time that the policy expires, given in the form of a unix timestamp in UTC
+    pub fn policy(&self, expiry: u64) -> Vec<u8> {
+        format!("{}{}{}", self.prefix, expiry, SUFFIX).into_bytes()
+    }
+
+    /// Parses the policy document for the expiry
+    ///
+    /// # Arguments
+    /// * `expiry` - Absolute time that the policy expires, given in the form of a unix timestamp in UTC
+    pub fn to_policy(&self, expiry: u64) -> Policy {
+        Policy::canned(self.resource, expiry)
+    }
+}
@@ -0,0 +1,249 @@
+/*
+MIT License
+
+Copyright (c) 2020 Martin Karlsen Jensen
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+//! Named profiles read from `~/.config/cloudfront-signer/config.toml`
+//!
+//! Each profile is a table in the file:
+//!
+//! ```toml
+//! [prod]
+//! key = "/etc/cloudfront/prod.pem"
+//! key_pair_id = "K2JCJMDEHXQW5F"
+//! ttl = "1h"
+//! cookie_domain = "example.com"
+//! base_url = "https://example.cloudfront.net"
+//! ```
+//!
+//! Like the AWS CLI, every value can be overridden with an environment variable:
+//! `CLOUDFRONT_SIGNER_KEY`, `CLOUDFRONT_SIGNER_KEY_PAIR_ID`, `CLOUDFRONT_SIGNER_TTL`,
+//! `CLOUDFRONT_SIGNER_COOKIE_DOMAIN` and `CLOUDFRONT_SIGNER_BASE_URL`. `CLOUDFRONT_SIGNER_CONFIG_FILE` changes the location of the file.
+
+use crate::template::parse_ttl;
+use crate::Error;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use std::{env, fs, io};
+
+/// A named set of signing settings
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Profile {
+    /// The name of the profile
+    pub name: String,
+    /// Path where the private key file can be found
+    pub key: PathBuf,
+    /// The key pair ID from AWS CloudFront
+    pub key_pair_id: String,
+    /// How long signed URLs and cookies are valid for by default
+    pub ttl: Option<Duration>,
+    /// The domain signed cookies are set for
+    pub cookie_domain: Option<String>,
+    /// The URL of the distribution that paths are signed against
+    pub base_url: Option<String>,
+}
+
+impl Profile {
+    /// Loads a profile from the default configuration file, applying environment variable overrides
+    ///
+    /// # Arguments
+    /// * `name` - The name of the profile eg. prod
+    pub fn load(name: &str) -> Result<Profile, Error> {
+        let path = default_path().ok_or_else(|| {
+            Error::InvalidConfig("could not determine the configuration directory".to_string())
+        })?;
+
+        Profile::load_from(path, name)
+    }
+
+    /// Loads a profile from the given configuration file, applying environment variable overrides
+    ///
+    /// A missing file is treated as empty so a profile can be given entirely through the environment.
+    ///
+    /// # Arguments
+    /// * `path` - The configuration file
+    /// * `name` - The name of the profile eg. prod
+    pub fn load_from<P: AsRef<Path>>(path: P, name: &str) -> Result<Profile, Error> {
+        let mut profiles = match fs::read_to_string(path.as_ref()) {
+            Ok(contents) => parse(&contents)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(Error::IOError(e)),
+        };
+        let found = profiles.contains_key(name);
+        let mut values = profiles.remove(name).unwrap_or_default();
+        let mut overridden = false;
+
+        for (key, variable) in [
+            ("key", "CLOUDFRONT_SIGNER_KEY"),
+            ("key_pair_id", "CLOUDFRONT_SIGNER_KEY_PAIR_ID"),
+            ("ttl", "CLOUDFRONT_SIGNER_TTL"),
+            ("cookie_domain", "CLOUDFRONT_SIGNER_COOKIE_DOMAIN"),
+            ("base_url", "CLOUDFRONT_SIGNER_BASE_URL"),
+        ] {
+            if let Ok(value) = env::var(variable) {
+                values.insert(key.to_string(), value);
+                overridden = true;
+            }
+        }
+
+        if !found && !overridden {
+            return Err(Error::ProfileNotFound(name.to_string()));
+        }
+
+        let mut required = |key: &str| {
+            values
+                .remove(key)
+                .ok_or_else(|| Error::InvalidConfig(format!("profile '{}' has no {}", name, key)))
+        };
+
+        Ok(Profile {
+            name: name.to_string(),
+            key: PathBuf::from(required("key")?),
+            key_pair_id: required("key_pair_id")?,
+            ttl: values
+                .remove("ttl")
+                .map(|ttl| parse_ttl(&ttl))
+                .transpose()?,
+            cookie_domain: values.remove("cookie_domain"),
+            base_url: values.remove("base_url"),
+        })
+    }
+}
+
+/// Returns the location of the configuration file
+///
+/// This is `CLOUDFRONT_SIGNER_CONFIG_FILE` if set, otherwise `cloudfront-signer/config.toml` in
+/// `XDG_CONFIG_HOME` or `~/.config`.
+pub fn default_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("CLOUDFRONT_SIGNER_CONFIG_FILE") {
+        return Some(PathBuf::from(path));
+    }
+
+    env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|config| config.join("cloudfront-signer").join("config.toml"))
+}
+
+/// Reads every table of a file in the format of the configuration file, eg. for files of the CLI
+///
+/// # Arguments
+/// * `path` - The file
+pub fn read_tables<P: AsRef<Path>>(
+    path: P,
+) -> Result<HashMap<String, HashMap<String, String>>, Error> {
+    parse(&fs::read_to_string(path).map_err(Error::IOError)?)
+}
+
+/// Parses the subset of TOML used by the configuration file: tables of string and integer values
+///
+/// # Arguments
+/// * `contents` - The contents of the configuration file
+fn parse(contents: &str) -> Result<HashMap<String, HashMap<String, String>>, Error> {
+    let mut profiles: HashMap<String, HashMap<String, String>> = HashMap::new();
+    let mut current = None;
+
+    for (index, line) in contents.lines().enumerate() {
+        let invalid =
+            |message: &str| Error::InvalidConfig(format!("line {}: {}", index + 1, message));
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix('[') {
+            let name = name
+                .split_once(']')
+                .filter(|(_, rest)| rest.trim().is_empty() || rest.trim().starts_with('#'))
+                .map(|(name, _)| name.trim().trim_matches('"'))
+                .filter(|name| !name.is_empty())
+                .ok_or_else(|| invalid("invalid table header"))?;
+
+            profiles.entry(name.to_string()).or_default();
+            current = Some(name.to_string());
+            continue;
+        }
+
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| invalid("expected key = value"))?;
+        let profile = current
+            .as_ref()
+            .ok_or_else(|| invalid("values must be inside a [profile] table"))?;
+        let value = parse_value(value.trim()).ok_or_else(|| invalid("invalid value"))?;
+
+        profiles
+            .entry(profile.clone())
+            .or_default()
+            .insert(key.trim().to_string(), value);
+    }
+
+    Ok(profiles)
+}
+
+/// Parses a basic string, literal string or integer, ignoring a trailing comment
+fn parse_value(value: &str) -> Option<String> {
+    if let Some(literal) = value.strip_prefix('\'') {
+        let (literal, rest) = literal.split_once('\'')?;
+
+        return is_comment_or_empty(rest).then(|| literal.to_string());
+    }
+
+    let Some(basic) = value.strip_prefix('"') else {
+        let number = value.split('#').next()?.trim();
+
+        return (!number.is_empty() && number.bytes().all(|b| b.is_ascii_digit()))
+            .then(|| number.to_string());
+    };
+    let mut output = String::new();
+    let mut chars = basic.chars();
+
+    loop {
+        match chars.next()? {
+            '"' => break,
+            '\\' => match chars.next()? {
+                '"' => output.push('"'),
+                '\\' => output.push('\\'),
+                'n' => output.push('\n'),
+                't' => output.push('\t'),
+                'r' => output.push('\r'),
+                'u' => {
+                    let hex: String = chars.by_ref().take(4).collect();
+
+                    output.push(char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?);
+                }
+                _ => return None,
+            },
+            c => output.push(c),
+        }
+    }
+
+    is_comment_or_empty(chars.as_str()).then_some(output)
+}
+
+fn is_comment_or_empty(rest: &str) -> bool {
+    let rest = rest.trim();
+
+    rest.is_empty() || rest.starts_with('#')
+}
@@ -0,0 +1,100 @@
+/*
+MIT License
+
+Copyright (c) 2020 Martin Karlsen Jensen
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+//! A signer for the current thread, for code that cannot be handed a signer
+//!
+//! Template filters, serializers and other code deep in a call stack often have no way to receive
+//! a handle from the application. [`with_signer`] makes a signer available to everything it calls
+//! on the same thread, where [`sign_url`] or [`current`] pick it up. Nothing is set unless the
+//! application opts in.
+//!
+//! The signer is bound to the thread, so async tasks which move between threads must call
+//! [`with_signer`] around the code that signs rather than around the whole task.
+//!
+//! ```
+//! use cloudfront_policy_signer::{context, CloudFrontSigner};
+//! use std::sync::Arc;
+//!
+//! fn avatar_filter(user: u64) -> String {
+//!     let resource = format!("https://example.cloudfront.net/avatars/{}.png", user);
+//!
+//!     context::sign_url(&resource, 1700000000).unwrap().into_string()
+//! }
+//!
+//! let signer = CloudFrontSigner::new("examples/key.pem", "APKAIEXAMPLE").unwrap();
+//! let url = context::with_signer(Arc::new(signer), || avatar_filter(42));
+//!
+//! assert!(url.starts_with("https://example.cloudfront.net/avatars/42.png?"));
+//! assert!(context::current().is_none());
+//! ```
+
+use crate::{Error, SignedUrl, UrlSigner};
+use std::cell::RefCell;
+use std::sync::Arc;
+
+thread_local! {
+    static SIGNERS: RefCell<Vec<Arc<dyn UrlSigner>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Removes the signer set by [`with_signer`], also when the closure panics
+struct Restore;
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        SIGNERS.with(|signers| signers.borrow_mut().pop());
+    }
+}
+
+/// Runs the closure with the signer set for the current thread
+///
+/// Calls can be nested, the innermost signer is the current one until its closure returns.
+///
+/// # Arguments
+/// * `signer` - The signer used by [`current`] and [`sign_url`] within the closure
+/// * `f` - The code that signs
+pub fn with_signer<F: FnOnce() -> R, R>(signer: Arc<dyn UrlSigner>, f: F) -> R {
+    SIGNERS.with(|signers| signers.borrow_mut().push(signer));
+
+    let _restore = Restore;
+
+    f()
+}
+
+/// Returns the signer set for the current thread by [`with_signer`], if any
+pub fn current() -> Option<Arc<dyn UrlSigner>> {
+    SIGNERS.with(|signers| signers.borrow().last().cloned())
+}
+
+/// Creates a URL with a canned policy using the signer of the current thread
+///
+/// Fails with [`Error::InvalidConfig`] outside of [`with_signer`].
+///
+/// # Arguments
+/// * `resource` - The protected resource eg. https://example.cloudfront.net/flowerpot.png
+/// * `expiry` - Absolute time that the URL expires, given in the form of a unix timestamp in UTC
+pub fn sign_url(resource: &str, expiry: u64) -> Result<SignedUrl, Error> {
+    current()
+        .ok_or_else(|| Error::InvalidConfig("no signer is set for this thread".to_string()))?
+        .sign_url(resource, expiry)
+}
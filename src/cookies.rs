@@ -0,0 +1,320 @@
+/*
+MIT License
+
+Copyright (c) 2020 Martin Karlsen Jensen
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use crate::signed_url::{Grant, REDACTED};
+use crate::{Error, PolicyKind};
+use std::fmt;
+use std::time::{Duration, SystemTime};
+
+/// The largest cookie, counting its name and value, that browsers reliably accept
+pub const MAX_COOKIE_SIZE: usize = 4096;
+
+/// Cookies granting access to resources on CloudFront
+///
+/// See [CloudFront Documentation](https://docs.aws.amazon.com/AmazonCloudFront/latest/DeveloperGuide/private-content-setting-signed-cookie-canned-policy.html) for more details
+///
+/// The `Debug` output redacts the signature.
+#[derive(Clone, PartialEq, Eq)]
+pub struct SignedCookies {
+    cookies: Vec<(&'static str, String)>,
+    grant: Grant,
+    path: String,
+}
+
+impl SignedCookies {
+    /// Constructs the cookies for a canned policy
+    ///
+    /// # Arguments
+    /// * `expiry` - Absolute time that the cookies expire, given in the form of a unix timestamp in UTC
+    /// * `signature` - The URL safe signature of the policy
+    /// * `key_pair_id` - The key pair ID from AWS CloudFront
+    /// * `resource` - The resource of the policy
+    pub(crate) fn canned(
+        expiry: u64,
+        signature: String,
+        key_pair_id: &str,
+        resource: &str,
+    ) -> SignedCookies {
+        SignedCookies {
+            cookies: vec![
+                ("CloudFront-Expires", expiry.to_string()),
+                ("CloudFront-Signature", signature),
+                ("CloudFront-Key-Pair-Id", key_pair_id.to_string()),
+            ],
+            grant: Grant {
+                expires: expiry,
+                not_before: None,
+                key_pair_id: key_pair_id.to_string(),
+                kind: PolicyKind::Canned,
+            },
+            path: cookie_path([Some(resource)]),
+        }
+    }
+
+    /// Constructs the cookies for a custom policy
+    ///
+    /// # Arguments
+    /// * `policy` - The URL safe encoded policy
+    /// * `signature` - The URL safe signature of the policy
+    /// * `grant` - What the policy grants access to
+    /// * `path` - The cookie path derived from the resources of the policy
+    pub(crate) fn custom(
+        policy: String,
+        signature: String,
+        grant: Grant,
+        path: String,
+    ) -> SignedCookies {
+        SignedCookies {
+            cookies: vec![
+                ("CloudFront-Policy", policy),
+                ("CloudFront-Signature", signature),
+                ("CloudFront-Key-Pair-Id", grant.key_pair_id.clone()),
+            ],
+            grant,
+            path,
+        }
+    }
+
+    /// Returns the name and value of every cookie
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.cookies
+            .iter()
+            .map(|(name, value)| (*name, value.as_str()))
+    }
+
+    /// Returns the value of the cookie with the given name
+    ///
+    /// # Arguments
+    /// * `name` - The name of the cookie eg. CloudFront-Signature
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.iter()
+            .find(|(cookie_name, _)| *cookie_name == name)
+            .map(|(_, value)| value)
+    }
+
+    /// Returns the absolute time access expires, given in the form of a unix timestamp in UTC
+    ///
+    /// For custom policies with several statements this is the earliest expiry of any statement.
+    pub fn expires_at(&self) -> u64 {
+        self.grant.expires
+    }
+
+    /// Returns the absolute time access starts, given in the form of a unix timestamp in UTC
+    pub fn not_before(&self) -> Option<u64> {
+        self.grant.not_before
+    }
+
+    /// Returns the key pair ID the cookies were signed with
+    pub fn key_pair_id(&self) -> &str {
+        &self.grant.key_pair_id
+    }
+
+    /// Returns whether the cookies were signed with a canned or a custom policy
+    pub fn kind(&self) -> PolicyKind {
+        self.grant.kind
+    }
+
+    /// Whether the cookies have expired at the given time
+    ///
+    /// See [`crate::SignedUrl::is_expired`] for tolerating clock drift.
+    ///
+    /// # Arguments
+    /// * `now` - The current time, usually `SystemTime::now()`
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        self.grant.is_expired(now)
+    }
+
+    /// Returns how long the cookies are still valid for at the given time, zero if they have expired
+    ///
+    /// # Arguments
+    /// * `now` - The current time, usually `SystemTime::now()`
+    pub fn remaining(&self, now: SystemTime) -> Duration {
+        self.grant.remaining(now)
+    }
+
+    /// Returns the path the cookies are scoped to by default, derived from the resources of the policy
+    ///
+    /// This is the deepest directory covering every resource, eg. /videos for
+    /// https://example.cloudfront.net/videos/*, so the cookies are not sent with every request to
+    /// the domain. Resources without a path give /.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns a `Set-Cookie` header value for every cookie
+    ///
+    /// The cookies are marked `Secure` and `HttpOnly`. Fails with [`Error::CookieTooLarge`] if a
+    /// cookie exceeds [`MAX_COOKIE_SIZE`], which browsers would silently drop, and with
+    /// [`Error::InvalidCookieAttribute`] if the domain or path contains `;` or a control character
+    /// such as CR or LF, which would add attributes or headers to the response.
+    ///
+    /// # Arguments
+    /// * `domain` - The domain the cookies are sent to eg. example.cloudfront.net. Without a domain the browser only sends them to the host that set them
+    /// * `path` - The path the cookies are sent for eg. /, defaults to [`SignedCookies::path`]
+    ///
+    /// # Example
+    /// ```
+    /// # #[cfg(feature = "sign")]
+    /// # {
+    /// use cloudfront_policy_signer::{CloudFrontSigner, Error};
+    ///
+    /// let signer = CloudFrontSigner::new("examples/key.pem", "APKAIEXAMPLE").unwrap();
+    /// let cookies = signer
+    ///     .create_canned_policy_signed_cookies("https://example.cloudfront.net/videos/*", 1700000000)
+    ///     .unwrap();
+    ///
+    /// assert!(cookies.set_cookie_headers(None, Some("/videos")).is_ok());
+    /// assert!(matches!(
+    ///     cookies.set_cookie_headers(Some("example.com; SameSite=None"), None),
+    ///     Err(Error::InvalidCookieAttribute(_))
+    /// ));
+    /// assert!(matches!(
+    ///     cookies.set_cookie_headers(None, Some("/\r\nLocation: https://evil.example")),
+    ///     Err(Error::InvalidCookieAttribute(_))
+    /// ));
+    /// # }
+    /// ```
+    pub fn set_cookie_headers(
+        &self,
+        domain: Option<&str>,
+        path: Option<&str>,
+    ) -> Result<Vec<String>, Error> {
+        self.check_size()?;
+
+        let path = path.unwrap_or(&self.path);
+
+        check_attribute("path", path)?;
+
+        if let Some(domain) = domain {
+            check_attribute("domain", domain)?;
+        }
+
+        Ok(self
+            .iter()
+            .map(|(name, value)| match domain {
+                Some(domain) => format!(
+                    "{}={}; Domain={}; Path={}; Secure; HttpOnly",
+                    name, value, domain, path
+                ),
+                None => format!("{}={}; Path={}; Secure; HttpOnly", name, value, path),
+            })
+            .collect())
+    }
+
+    /// Checks that no cookie exceeds [`MAX_COOKIE_SIZE`], failing with [`Error::CookieTooLarge`] otherwise
+    ///
+    /// Only the `CloudFront-Policy` cookie of large custom policies can get this big.
+    pub fn check_size(&self) -> Result<(), Error> {
+        for (name, value) in self.iter() {
+            let len = name.len() + 1 + value.len();
+
+            if len > MAX_COOKIE_SIZE {
+                error!("The cookie {} is {} bytes long", name, len);
+
+                return Err(Error::CookieTooLarge {
+                    name: name.to_string(),
+                    len,
+                    limit: MAX_COOKIE_SIZE,
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the deepest directory covering every resource, used as the default cookie path
+///
+/// # Arguments
+/// * `resources` - The resources of the policy, `None` for a statement without a resource
+pub(crate) fn cookie_path<'a, I: IntoIterator<Item = Option<&'a str>>>(resources: I) -> String {
+    let mut common: Option<Vec<&str>> = None;
+
+    for resource in resources {
+        let directory = resource.map_or("", |resource| {
+            let path = resource
+                .split_once("://")
+                .and_then(|(_, rest)| rest.find('/').map(|start| &rest[start..]))
+                .unwrap_or("");
+            let path = &path[..path.find(['*', '?']).unwrap_or(path.len())];
+
+            path.rfind('/').map_or("", |end| &path[..end])
+        });
+        let segments = directory
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .collect::<Vec<_>>();
+
+        common = Some(match common {
+            None => segments,
+            Some(common) => common
+                .into_iter()
+                .zip(segments)
+                .take_while(|(a, b)| a == b)
+                .map(|(a, _)| a)
+                .collect(),
+        });
+    }
+
+    format!("/{}", common.unwrap_or_default().join("/"))
+}
+
+impl fmt::Debug for SignedCookies {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let cookies = self
+            .cookies
+            .iter()
+            .map(|(name, value)| match *name {
+                "CloudFront-Signature" => (*name, REDACTED),
+                _ => (*name, value.as_str()),
+            })
+            .collect::<Vec<_>>();
+
+        f.debug_struct("SignedCookies")
+            .field("cookies", &cookies)
+            .field("grant", &self.grant)
+            .finish()
+    }
+}
+
+/// Fails with [`Error::InvalidCookieAttribute`] if the value of a cookie attribute contains `;` or a control character
+///
+/// # Arguments
+/// * `name` - The name of the attribute eg. domain
+/// * `value` - The value of the attribute
+fn check_attribute(name: &str, value: &str) -> Result<(), Error> {
+    if value.contains(|c: char| c == ';' || c.is_control()) {
+        error!(
+            "The cookie {} {:?} contains ';' or a control character",
+            name, value
+        );
+
+        return Err(Error::InvalidCookieAttribute(format!(
+            "the {} {:?} contains ';' or a control character",
+            name, value
+        )));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,302 @@
+/*
+MIT License
+
+Copyright (c) 2020 Martin Karlsen Jensen
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+//! Verifying signed URLs and cookies the way CloudFront does, for edge validators
+//!
+//! [`EdgeVerifier`] decodes the policy of a signed URL or of signed cookies, verifies its RSA
+//! signature with the trusted public keys and checks the resource, time window and IP address,
+//! so an edge function double-checking access shares the code that issued the grant.
+//! [`EdgeVerifier::viewer_request`] takes a Lambda@Edge viewer request event and returns what the
+//! handler should return: the request when access is granted, a 403 response otherwise.
+//!
+//! Lambda@Edge itself only runs Node.js and Python, so the verifier runs in Rust where the same
+//! event format is used, eg. a Lambda function behind CloudFront or tests of the edge function.
+//! Like the [`crate::verify`] module it is available without the default `sign` feature, which
+//! leaves out everything handling private keys or reading files.
+//!
+//! ```
+//! # #[cfg(feature = "sign")]
+//! # {
+//! use cloudfront_policy_signer::edge::EdgeVerifier;
+//! use cloudfront_policy_signer::verify::parse_public_key;
+//! use cloudfront_policy_signer::CloudFrontSigner;
+//! use std::time::{Duration, UNIX_EPOCH};
+//!
+//! let signer = CloudFrontSigner::new("examples/key.pem", "APKAIEXAMPLE").unwrap();
+//! let public_key = parse_public_key(signer.public_key_pem().unwrap().as_bytes()).unwrap();
+//! let verifier = EdgeVerifier::new().key("APKAIEXAMPLE", public_key);
+//! let now = UNIX_EPOCH + Duration::from_secs(1600000000);
+//!
+//! let url = signer
+//!     .sign_url("https://example.cloudfront.net/flowerpot.png", 1700000000)
+//!     .unwrap();
+//! assert!(verifier.verify_url(url.as_str(), None, now).is_ok());
+//!
+//! let cookies = signer
+//!     .create_canned_policy_signed_cookies("https://example.cloudfront.net/flowerpot.png", 1700000000)
+//!     .unwrap();
+//! let header = cookies
+//!     .iter()
+//!     .map(|(name, value)| format!("{}={}", name, value))
+//!     .collect::<Vec<_>>()
+//!     .join("; ");
+//! assert!(verifier
+//!     .verify_cookies("https://example.cloudfront.net/flowerpot.png", &header, None, now)
+//!     .is_ok());
+//! assert!(verifier
+//!     .verify_cookies("https://example.cloudfront.net/other.png", &header, None, now)
+//!     .is_err());
+//! # }
+//! ```
+
+use crate::json::{self, Value};
+use crate::resource::wildcard_matches;
+use crate::verify::{verify_signature, ParsedSignedUrl};
+use crate::{base64, generate_canned_policy, Error, Policy};
+use openssl::pkey::{PKey, Public};
+use std::net::Ipv4Addr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Verifies signed URLs and cookies with a set of trusted public keys
+#[derive(Default)]
+pub struct EdgeVerifier {
+    keys: Vec<(String, PKey<Public>)>,
+}
+
+impl EdgeVerifier {
+    /// Constructs a new `EdgeVerifier` without any trusted keys
+    pub fn new() -> EdgeVerifier {
+        EdgeVerifier::default()
+    }
+
+    /// Trusts a public key, eg. one of the key group of the distribution
+    ///
+    /// # Arguments
+    /// * `key_pair_id` - The ID of the public key in CloudFront eg. K2JCJMDEHXQW5F
+    /// * `public_key` - The public key, see [`crate::verify::parse_public_key`]
+    pub fn key<T: Into<String>>(
+        mut self,
+        key_pair_id: T,
+        public_key: PKey<Public>,
+    ) -> EdgeVerifier {
+        self.keys.push((key_pair_id.into(), public_key));
+        self
+    }
+
+    /// Verifies a signed URL and returns its policy
+    ///
+    /// # Arguments
+    /// * `url` - The requested URL including the signing parameters
+    /// * `client_ip` - The IP address of the viewer, checked against the `IpAddress` conditions
+    /// * `now` - The current time, usually `SystemTime::now()`
+    pub fn verify_url(
+        &self,
+        url: &str,
+        client_ip: Option<&str>,
+        now: SystemTime,
+    ) -> Result<Policy, Error> {
+        let parsed = ParsedSignedUrl::parse(url)?;
+
+        self.verify(
+            parsed.resource(),
+            parsed.policy_json(),
+            parsed.signature(),
+            parsed.key_pair_id(),
+            client_ip,
+            now,
+        )
+    }
+
+    /// Verifies the signed cookies sent with a request and returns their policy
+    ///
+    /// # Arguments
+    /// * `url` - The requested URL eg. https://example.cloudfront.net/flowerpot.png
+    /// * `cookie_header` - The value of the Cookie header, or several joined with `; `
+    /// * `client_ip` - The IP address of the viewer, checked against the `IpAddress` conditions
+    /// * `now` - The current time, usually `SystemTime::now()`
+    pub fn verify_cookies(
+        &self,
+        url: &str,
+        cookie_header: &str,
+        client_ip: Option<&str>,
+        now: SystemTime,
+    ) -> Result<Policy, Error> {
+        let cookie = |name: &str| {
+            cookie_header.split(';').find_map(|cookie| {
+                cookie
+                    .trim()
+                    .split_once('=')
+                    .filter(|(cookie_name, _)| *cookie_name == name)
+                    .map(|(_, value)| value)
+            })
+        };
+        let missing =
+            |name: &str| Error::InvalidSignedUrl(format!("the {} cookie is missing", name));
+        let policy = match (cookie("CloudFront-Policy"), cookie("CloudFront-Expires")) {
+            (Some(policy), _) => base64::decode(policy)?,
+            (None, Some(expires)) => {
+                let expires = expires.parse().map_err(|_| {
+                    Error::InvalidSignedUrl(
+                        "the CloudFront-Expires cookie is not a unix timestamp".to_string(),
+                    )
+                })?;
+
+                generate_canned_policy(url, expires)
+            }
+            (None, None) => return Err(missing("CloudFront-Policy")),
+        };
+        let signature = base64::decode(
+            cookie("CloudFront-Signature").ok_or_else(|| missing("CloudFront-Signature"))?,
+        )?;
+        let key_pair_id =
+            cookie("CloudFront-Key-Pair-Id").ok_or_else(|| missing("CloudFront-Key-Pair-Id"))?;
+
+        self.verify(url, &policy, &signature, key_pair_id, client_ip, now)
+    }
+
+    /// Handles a Lambda@Edge viewer request event and returns the JSON the handler should return
+    ///
+    /// Requests with a `Signature` query parameter are verified as signed URLs and all others as
+    /// signed cookies. Granted requests are returned unchanged, denied ones get a 403 response.
+    /// Fails with [`Error::InvalidConfig`] if the event is not a viewer request event.
+    ///
+    /// # Arguments
+    /// * `event` - The event passed to the function
+    /// * `now` - The current time, usually `SystemTime::now()`
+    pub fn viewer_request(&self, event: &str, now: SystemTime) -> Result<String, Error> {
+        let invalid =
+            || Error::InvalidConfig("the event is not a CloudFront viewer request".to_string());
+        let event = json::parse(event.as_bytes()).map_err(|e| {
+            error!("Could not parse the viewer request event due to {}", e);
+            invalid()
+        })?;
+        let request = event
+            .get("Records")
+            .and_then(Value::as_array)
+            .and_then(|records| records.first())
+            .and_then(|record| record.get("cf"))
+            .and_then(|cf| cf.get("request"))
+            .ok_or_else(invalid)?;
+        let field = |name: &str| request.get(name).and_then(Value::as_str);
+        let header = |name: &str| {
+            request
+                .get("headers")
+                .and_then(|headers| headers.get(name))
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(|header| header.get("value").and_then(Value::as_str))
+                .collect::<Vec<_>>()
+        };
+        let host = header("host").first().copied().ok_or_else(invalid)?;
+        let uri = field("uri").ok_or_else(invalid)?;
+        let querystring = field("querystring").unwrap_or("");
+        let client_ip = field("clientIp");
+        let url = format!("https://{}{}", host, uri);
+        let verified = if querystring
+            .split('&')
+            .any(|parameter| parameter.starts_with("Signature="))
+        {
+            self.verify_url(&format!("{}?{}", url, querystring), client_ip, now)
+        } else {
+            self.verify_cookies(&url, &header("cookie").join("; "), client_ip, now)
+        };
+
+        match verified {
+            Ok(_) => Ok(request.to_json()),
+            Err(e) => {
+                warn!("Denied access to {} due to {}", url, e);
+                Ok(r#"{"status":"403","statusDescription":"Forbidden"}"#.to_string())
+            }
+        }
+    }
+
+    /// Verifies a policy document and its signature for a requested URL
+    fn verify(
+        &self,
+        url: &str,
+        policy: &[u8],
+        signature: &[u8],
+        key_pair_id: &str,
+        client_ip: Option<&str>,
+        now: SystemTime,
+    ) -> Result<Policy, Error> {
+        let invalid = |message: String| Error::InvalidSignedUrl(message);
+        let public_key = self
+            .keys
+            .iter()
+            .find(|(id, _)| id == key_pair_id)
+            .map(|(_, public_key)| public_key)
+            .ok_or_else(|| invalid(format!("the key pair ID {} is not trusted", key_pair_id)))?;
+
+        if !verify_signature(policy, signature, public_key)? {
+            return Err(invalid("the signature does not match".to_string()));
+        }
+
+        let policy = Policy::from_json(policy)?;
+        let now = now
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since_epoch| since_epoch.as_secs());
+        let client_ip = client_ip.and_then(|client_ip| client_ip.parse::<Ipv4Addr>().ok());
+        let mut denied = None;
+
+        for statement in &policy.statements {
+            let condition = &statement.condition;
+            let reason =
+                if !wildcard_matches(&statement.resource, url) {
+                    "the policy does not cover the requested URL"
+                } else if now >= condition.date_less_than {
+                    "the policy has expired"
+                } else if condition.date_greater_than.is_some_and(|start| now < start) {
+                    "the policy is not valid yet"
+                } else if condition.ip_address.as_deref().is_some_and(|range| {
+                    !client_ip.is_some_and(|client_ip| in_range(client_ip, range))
+                }) {
+                    "the viewer is outside the allowed IP addresses"
+                } else {
+                    return Ok(policy);
+                };
+
+            denied.get_or_insert(reason);
+        }
+
+        Err(invalid(
+            denied.unwrap_or("the policy has no statements").to_string(),
+        ))
+    }
+}
+
+/// Whether the address lies within an IPv4 address or CIDR range
+fn in_range(address: Ipv4Addr, range: &str) -> bool {
+    let (network, prefix) = range.split_once('/').unwrap_or((range, "32"));
+
+    match (network.parse::<Ipv4Addr>(), prefix.parse::<u32>()) {
+        (Ok(network), Ok(prefix)) if prefix <= 32 => {
+            let mask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+
+            u32::from(address) & mask == u32::from(network) & mask
+        }
+        _ => false,
+    }
+}
@@ -0,0 +1,117 @@
+/*
+MIT License
+
+Copyright (c) 2020 Martin Karlsen Jensen
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+//! A process-wide signer that can be replaced at runtime
+//!
+//! Long-running services call [`init`] once at startup and [`reconfigure`] whenever the settings
+//! change, eg. from an admin endpoint with a new default TTL or key, without restarting.
+//! Requests that are being signed during a reconfiguration finish with the previous signer.
+//!
+//! ```
+//! use cloudfront_policy_signer::{global, CloudFrontSigner};
+//! use std::time::Duration;
+//!
+//! let signer = CloudFrontSigner::new("examples/key.pem", "APKAIEXAMPLE").unwrap();
+//!
+//! global::init(signer.default_ttl(Duration::from_secs(3600))).unwrap();
+//! global::sign("https://example.cloudfront.net/flowerpot.png").unwrap();
+//!
+//! let signer = CloudFrontSigner::new("examples/key.pem", "APKAIEXAMPLE").unwrap();
+//!
+//! global::reconfigure(signer.default_ttl(Duration::from_secs(60))).unwrap();
+//! ```
+
+use crate::{CloudFrontSigner, Error, SignedUrl};
+use std::sync::{Arc, RwLock};
+
+static SIGNER: RwLock<Option<Arc<CloudFrontSigner>>> = RwLock::new(None);
+
+/// Sets the process-wide signer
+///
+/// Fails with [`Error::InvalidConfig`] if it is already set, use [`reconfigure`] to replace it.
+///
+/// # Arguments
+/// * `signer` - The configured signer
+pub fn init(signer: CloudFrontSigner) -> Result<(), Error> {
+    let mut current = SIGNER.write().unwrap_or_else(|e| e.into_inner());
+
+    if current.is_some() {
+        return Err(Error::InvalidConfig(
+            "the global signer is already initialized".to_string(),
+        ));
+    }
+
+    *current = Some(Arc::new(signer));
+
+    Ok(())
+}
+
+/// Sets the process-wide signer from a profile in the configuration file, see [`CloudFrontSigner::from_profile`]
+///
+/// # Arguments
+/// * `name` - The name of the profile eg. prod
+pub fn init_from_profile(name: &str) -> Result<(), Error> {
+    init(CloudFrontSigner::from_profile(name)?)
+}
+
+/// Replaces the process-wide signer and returns the previous one
+///
+/// Fails with [`Error::InvalidConfig`] if [`init`] was not called, so a misconfigured service
+/// does not silently start signing.
+///
+/// # Arguments
+/// * `signer` - The newly configured signer
+pub fn reconfigure(signer: CloudFrontSigner) -> Result<Arc<CloudFrontSigner>, Error> {
+    let mut current = SIGNER.write().unwrap_or_else(|e| e.into_inner());
+
+    let previous = current.take().ok_or_else(not_initialized)?;
+
+    *current = Some(Arc::new(signer));
+
+    Ok(previous)
+}
+
+/// Returns the process-wide signer
+///
+/// Fails with [`Error::InvalidConfig`] if [`init`] was not called.
+pub fn signer() -> Result<Arc<CloudFrontSigner>, Error> {
+    SIGNER
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .ok_or_else(not_initialized)
+}
+
+/// Creates a URL with a canned policy and the default TTL of the process-wide signer, see [`CloudFrontSigner::sign`]
+///
+/// # Arguments
+/// * `resource` - The protected resource eg. https://example.cloudfront.net/flowerpot.png
+pub fn sign<R: AsRef<str>>(resource: R) -> Result<SignedUrl, Error> {
+    signer()?.sign(resource)
+}
+
+/// The error for using the process-wide signer before [`init`]
+fn not_initialized() -> Error {
+    Error::InvalidConfig("the global signer is not initialized".to_string())
+}
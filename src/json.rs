@@ -0,0 +1,458 @@
+/*
+MIT License
+
+Copyright (c) 2020 Martin Karlsen Jensen
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+//! Minimal JSON parser for reading policy documents
+
+/// Maximum nesting of arrays and objects accepted by the parser
+const MAX_DEPTH: usize = 32;
+
+/// A parsed JSON value. Objects keep the order of their members
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Value {
+    Null,
+    Bool(bool),
+    /// The number exactly as it appeared in the document
+    Number(String),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// Returns the member with the given key if this is an object
+    pub(crate) fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(members) => members
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_u64(&self) -> Option<u64> {
+        match self {
+            Value::Number(number) => number.parse().ok(),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Value::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    /// Returns the first key that appears twice in the same object, looking through every nested value
+    ///
+    /// [`Value::get`] only sees the first of duplicate members, while others may use the last.
+    #[cfg(feature = "sign")]
+    pub(crate) fn duplicate_key(&self) -> Option<&str> {
+        match self {
+            Value::Object(members) => members
+                .iter()
+                .enumerate()
+                .find(|(index, (key, _))| members[..*index].iter().any(|(other, _)| other == key))
+                .map(|(_, (key, _))| key.as_str())
+                .or_else(|| members.iter().find_map(|(_, value)| value.duplicate_key())),
+            Value::Array(values) => values.iter().find_map(Value::duplicate_key),
+            _ => None,
+        }
+    }
+
+    /// Returns the value as compact JSON without any insignificant whitespace
+    pub(crate) fn to_json(&self) -> String {
+        let mut out = String::new();
+
+        self.write(&mut out);
+        out
+    }
+
+    fn write(&self, out: &mut String) {
+        match self {
+            Value::Null => out.push_str("null"),
+            Value::Bool(value) => out.push_str(if *value { "true" } else { "false" }),
+            Value::Number(number) => out.push_str(number),
+            Value::String(value) => write_string(out, value),
+            Value::Array(values) => {
+                out.push('[');
+
+                for (index, value) in values.iter().enumerate() {
+                    if index > 0 {
+                        out.push(',');
+                    }
+
+                    value.write(out);
+                }
+
+                out.push(']');
+            }
+            Value::Object(members) => {
+                out.push('{');
+
+                for (index, (name, value)) in members.iter().enumerate() {
+                    if index > 0 {
+                        out.push(',');
+                    }
+
+                    write_string(out, name);
+                    out.push(':');
+                    value.write(out);
+                }
+
+                out.push('}');
+            }
+        }
+    }
+}
+
+/// Parses a complete JSON document, returning a description of the problem if it is malformed
+///
+/// # Arguments
+/// * `input` - The JSON document
+pub(crate) fn parse(input: &[u8]) -> Result<Value, String> {
+    let mut parser = Parser { input, position: 0 };
+    let value = parser.value(0)?;
+
+    parser.skip_whitespace();
+
+    if parser.position != input.len() {
+        return Err(parser.error("unexpected trailing characters"));
+    }
+
+    Ok(value)
+}
+
+/// Appends the string to `out` as a JSON string literal
+///
+/// # Arguments
+/// * `out` - The string the literal is appended to
+/// * `value` - The string to encode
+pub(crate) fn write_string(out: &mut String, value: &str) {
+    write_string_with(value, |part| out.push_str(part));
+}
+
+/// Passes the JSON string literal of the value to a closure in parts, without allocating
+///
+/// # Arguments
+/// * `value` - The string to encode
+/// * `push` - Called with every part of the literal in order
+pub(crate) fn write_string_with<F: FnMut(&str)>(value: &str, mut push: F) {
+    const HEX: &[u8; 16] = b"0123456789abcdef";
+
+    push("\"");
+
+    let mut start = 0;
+
+    for (index, c) in value.char_indices() {
+        let escape = match c {
+            '"' => "\\\"",
+            '\\' => "\\\\",
+            '\n' => "\\n",
+            '\r' => "\\r",
+            '\t' => "\\t",
+            c if (c as u32) < 0x20 => "",
+            _ => continue,
+        };
+
+        push(&value[start..index]);
+        start = index + 1;
+
+        if escape.is_empty() {
+            let code = [
+                b'\\',
+                b'u',
+                b'0',
+                b'0',
+                HEX[c as usize >> 4],
+                HEX[c as usize & 0xf],
+            ];
+
+            push(std::str::from_utf8(&code).unwrap_or_default());
+        } else {
+            push(escape);
+        }
+    }
+
+    push(&value[start..]);
+    push("\"");
+}
+
+struct Parser<'a> {
+    input: &'a [u8],
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> String {
+        format!("{} at byte {}", message, self.position)
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.position).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.position += 1;
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), String> {
+        if self.peek() == Some(byte) {
+            self.position += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", byte as char)))
+        }
+    }
+
+    fn literal(&mut self, literal: &str, value: Value) -> Result<Value, String> {
+        if self.input[self.position..].starts_with(literal.as_bytes()) {
+            self.position += literal.len();
+            Ok(value)
+        } else {
+            Err(self.error("invalid literal"))
+        }
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Value, String> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("document is nested too deeply"));
+        }
+
+        self.skip_whitespace();
+
+        match self.peek() {
+            Some(b'{') => self.object(depth),
+            Some(b'[') => self.array(depth),
+            Some(b'"') => self.string().map(Value::String),
+            Some(b't') => self.literal("true", Value::Bool(true)),
+            Some(b'f') => self.literal("false", Value::Bool(false)),
+            Some(b'n') => self.literal("null", Value::Null),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(_) => Err(self.error("unexpected character")),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn object(&mut self, depth: usize) -> Result<Value, String> {
+        let mut members = Vec::new();
+
+        self.expect(b'{')?;
+        self.skip_whitespace();
+
+        if self.peek() == Some(b'}') {
+            self.position += 1;
+            return Ok(Value::Object(members));
+        }
+
+        loop {
+            self.skip_whitespace();
+
+            let key = self.string()?;
+
+            self.skip_whitespace();
+            self.expect(b':')?;
+            members.push((key, self.value(depth + 1)?));
+            self.skip_whitespace();
+
+            match self.peek() {
+                Some(b',') => self.position += 1,
+                Some(b'}') => {
+                    self.position += 1;
+                    return Ok(Value::Object(members));
+                }
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+    }
+
+    fn array(&mut self, depth: usize) -> Result<Value, String> {
+        let mut values = Vec::new();
+
+        self.expect(b'[')?;
+        self.skip_whitespace();
+
+        if self.peek() == Some(b']') {
+            self.position += 1;
+            return Ok(Value::Array(values));
+        }
+
+        loop {
+            values.push(self.value(depth + 1)?);
+            self.skip_whitespace();
+
+            match self.peek() {
+                Some(b',') => self.position += 1,
+                Some(b']') => {
+                    self.position += 1;
+                    return Ok(Value::Array(values));
+                }
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Value, String> {
+        let start = self.position;
+
+        if self.peek() == Some(b'-') {
+            self.position += 1;
+        }
+
+        let digits = |parser: &mut Self| {
+            let start = parser.position;
+
+            while matches!(parser.peek(), Some(b'0'..=b'9')) {
+                parser.position += 1;
+            }
+
+            parser.position > start
+        };
+
+        if !digits(self) {
+            return Err(self.error("expected digits"));
+        }
+
+        if self.peek() == Some(b'.') {
+            self.position += 1;
+
+            if !digits(self) {
+                return Err(self.error("expected digits after the decimal point"));
+            }
+        }
+
+        if matches!(self.peek(), Some(b'e' | b'E')) {
+            self.position += 1;
+
+            if matches!(self.peek(), Some(b'+' | b'-')) {
+                self.position += 1;
+            }
+
+            if !digits(self) {
+                return Err(self.error("expected digits in the exponent"));
+            }
+        }
+
+        // Only ASCII digits and signs were consumed so this is valid UTF-8
+        let number = String::from_utf8_lossy(&self.input[start..self.position]).into_owned();
+
+        Ok(Value::Number(number))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let mut bytes = Vec::new();
+
+        self.expect(b'"')?;
+
+        loop {
+            match self.peek() {
+                Some(b'"') => {
+                    self.position += 1;
+                    break;
+                }
+                Some(b'\\') => {
+                    self.position += 1;
+                    self.escape(&mut bytes)?;
+                }
+                Some(byte) if byte < 0x20 => {
+                    return Err(self.error("control character in string"));
+                }
+                Some(byte) => {
+                    bytes.push(byte);
+                    self.position += 1;
+                }
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+
+        String::from_utf8(bytes).map_err(|_| self.error("string is not valid UTF-8"))
+    }
+
+    fn escape(&mut self, bytes: &mut Vec<u8>) -> Result<(), String> {
+        let escaped = match self.peek() {
+            Some(b'"') => '"',
+            Some(b'\\') => '\\',
+            Some(b'/') => '/',
+            Some(b'b') => '\u{8}',
+            Some(b'f') => '\u{c}',
+            Some(b'n') => '\n',
+            Some(b'r') => '\r',
+            Some(b't') => '\t',
+            Some(b'u') => {
+                self.position += 1;
+
+                let high = self.hex4()?;
+                let code_point = if (0xD800..0xDC00).contains(&high) {
+                    self.expect(b'\\')?;
+                    self.expect(b'u')?;
+
+                    let low = self.hex4()?;
+
+                    if !(0xDC00..0xE000).contains(&low) {
+                        return Err(self.error("invalid surrogate pair"));
+                    }
+
+                    0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+                } else {
+                    high
+                };
+                let c = char::from_u32(code_point).ok_or_else(|| self.error("invalid escape"))?;
+
+                bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+
+                return Ok(());
+            }
+            _ => return Err(self.error("invalid escape")),
+        };
+
+        self.position += 1;
+        bytes.extend_from_slice(escaped.encode_utf8(&mut [0; 4]).as_bytes());
+
+        Ok(())
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        let hex = self
+            .input
+            .get(self.position..self.position + 4)
+            .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+            .ok_or_else(|| self.error("invalid unicode escape"))?;
+
+        self.position += 4;
+
+        Ok(hex)
+    }
+}
@@ -0,0 +1,247 @@
+/*
+MIT License
+
+Copyright (c) 2020 Martin Karlsen Jensen
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+//! RS256 JSON Web Tokens, for setups where Lambda@Edge checks a token instead of a signed URL
+//!
+//! Tokens are signed with the same private key as URLs by [`CloudFrontSigner::sign_jwt`] and
+//! carry the key pair ID as `kid`. The protected resource is the `resource` claim, which may
+//! contain wildcards like the resource of a policy.
+//!
+//! ```
+//! # #[cfg(feature = "sign")]
+//! # {
+//! use cloudfront_policy_signer::jwt::{self, Claims};
+//! use cloudfront_policy_signer::verify::parse_public_key;
+//! use cloudfront_policy_signer::CloudFrontSigner;
+//! use std::time::{Duration, UNIX_EPOCH};
+//!
+//! let signer = CloudFrontSigner::new("examples/key.pem", "APKAIEXAMPLE").unwrap();
+//! let claims = Claims::new("https://example.cloudfront.net/videos/*", 1700000000).subject("user-42");
+//! let token = signer.sign_jwt(&claims).unwrap();
+//!
+//! let public_key = parse_public_key(signer.public_key_pem().unwrap().as_bytes()).unwrap();
+//! let verified = jwt::verify(&token, &public_key, UNIX_EPOCH + Duration::from_secs(1690000000)).unwrap();
+//!
+//! assert_eq!(verified.subject.as_deref(), Some("user-42"));
+//! # }
+//! ```
+//!
+//! [`CloudFrontSigner::sign_jwt`]: crate::CloudFrontSigner::sign_jwt
+
+use crate::json::{self, Value};
+use crate::{base64, Error};
+use openssl::hash::MessageDigest;
+use openssl::pkey::{PKey, Public};
+use openssl::sign::Verifier;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The claims of an access token
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Claims {
+    /// The protected resource eg. https://example.cloudfront.net/videos/* (`resource`)
+    pub resource: String,
+    /// Absolute time the token expires, given in the form of a unix timestamp in UTC (`exp`)
+    pub expires: u64,
+    /// Absolute time the token becomes valid, given in the form of a unix timestamp in UTC (`nbf`)
+    pub not_before: Option<u64>,
+    /// Absolute time the token was issued, given in the form of a unix timestamp in UTC (`iat`). Set when signing
+    pub issued_at: Option<u64>,
+    /// Who the token was issued to eg. a user ID (`sub`)
+    pub subject: Option<String>,
+}
+
+impl Claims {
+    /// Constructs the claims granting access to a resource
+    ///
+    /// # Arguments
+    /// * `resource` - The protected resource eg. https://example.cloudfront.net/videos/*
+    /// * `expires` - Absolute time the token expires, given in the form of a unix timestamp in UTC
+    pub fn new<R: Into<String>>(resource: R, expires: u64) -> Claims {
+        Claims {
+            resource: resource.into(),
+            expires,
+            not_before: None,
+            issued_at: None,
+            subject: None,
+        }
+    }
+
+    /// Sets the time the token becomes valid
+    ///
+    /// # Arguments
+    /// * `not_before` - Absolute time the token becomes valid, given in the form of a unix timestamp in UTC
+    pub fn not_before(mut self, not_before: u64) -> Claims {
+        self.not_before = Some(not_before);
+        self
+    }
+
+    /// Sets who the token is issued to
+    ///
+    /// # Arguments
+    /// * `subject` - eg. a user ID
+    pub fn subject<T: Into<String>>(mut self, subject: T) -> Claims {
+        self.subject = Some(subject.into());
+        self
+    }
+
+    #[cfg(feature = "sign")]
+    fn to_json(&self, issued_at: u64) -> String {
+        let mut json = String::from("{\"resource\":");
+
+        json::write_string(&mut json, &self.resource);
+        json.push_str(&format!(",\"exp\":{},\"iat\":{}", self.expires, issued_at));
+
+        if let Some(not_before) = self.not_before {
+            json.push_str(&format!(",\"nbf\":{}", not_before));
+        }
+
+        if let Some(subject) = &self.subject {
+            json.push_str(",\"sub\":");
+            json::write_string(&mut json, subject);
+        }
+
+        json.push('}');
+        json
+    }
+}
+
+/// Returns the encoded header and claims, which are signed to create the token
+///
+/// # Arguments
+/// * `claims` - The claims of the token
+/// * `key_pair_id` - The key pair ID, sent as `kid`
+/// * `issued_at` - The unix timestamp the token is issued at
+#[cfg(feature = "sign")]
+pub(crate) fn signing_input(claims: &Claims, key_pair_id: &str, issued_at: u64) -> String {
+    let mut header = String::from("{\"alg\":\"RS256\",\"typ\":\"JWT\",\"kid\":");
+
+    json::write_string(&mut header, key_pair_id);
+    header.push('}');
+
+    format!(
+        "{}.{}",
+        encode(header.as_bytes()),
+        encode(claims.to_json(issued_at).as_bytes())
+    )
+}
+
+/// Appends the signature to the signing input
+#[cfg(feature = "sign")]
+pub(crate) fn finish(signing_input: String, signature: &[u8]) -> String {
+    format!("{}.{}", signing_input, encode(signature))
+}
+
+/// Verifies the signature and validity of a token and returns its claims
+///
+/// Fails with [`Error::InvalidToken`] if the token is malformed, not signed with RS256 by the
+/// private key matching `public_key`, expired or not yet valid. Whether the `resource` claim
+/// covers the requested URL is up to the caller.
+///
+/// # Arguments
+/// * `token` - The token
+/// * `public_key` - The public key uploaded to CloudFront
+/// * `now` - The current time, usually `SystemTime::now()`
+pub fn verify(token: &str, public_key: &PKey<Public>, now: SystemTime) -> Result<Claims, Error> {
+    let invalid = |message: &str| Error::InvalidToken(message.to_string());
+    let (signing_input, signature) = token
+        .rsplit_once('.')
+        .ok_or_else(|| invalid("the token has no signature"))?;
+    let (header, claims) = signing_input
+        .split_once('.')
+        .ok_or_else(|| invalid("the token has no claims"))?;
+    let parse = |part: &str| {
+        json::parse(&decode(part)?)
+            .map_err(|e| Error::InvalidToken(format!("malformed JSON: {}", e)))
+    };
+
+    if parse(header)?.get("alg").and_then(Value::as_str) != Some("RS256") {
+        return Err(invalid("the token is not signed with RS256"));
+    }
+
+    let mut verifier = Verifier::new(MessageDigest::sha256(), public_key).map_err(|e| {
+        error!("Could not create verifier due to {}", e);
+        Error::Unknown
+    })?;
+
+    verifier.update(signing_input.as_bytes()).map_err(|e| {
+        error!("Could not update verifier due to {}", e);
+        Error::Unknown
+    })?;
+
+    if !verifier.verify(&decode(signature)?).unwrap_or(false) {
+        return Err(invalid("the signature is invalid"));
+    }
+
+    let claims = parse(claims)?;
+    let claims = Claims {
+        resource: claims
+            .get("resource")
+            .and_then(Value::as_str)
+            .ok_or_else(|| invalid("the token has no resource claim"))?
+            .to_string(),
+        expires: claims
+            .get("exp")
+            .and_then(Value::as_u64)
+            .ok_or_else(|| invalid("the token has no exp claim"))?,
+        not_before: claims.get("nbf").and_then(Value::as_u64),
+        issued_at: claims.get("iat").and_then(Value::as_u64),
+        subject: claims
+            .get("sub")
+            .and_then(Value::as_str)
+            .map(str::to_string),
+    };
+    let now = now
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_secs())
+        .unwrap_or_default();
+
+    if now >= claims.expires {
+        return Err(invalid("the token has expired"));
+    }
+
+    if claims.not_before.is_some_and(|not_before| now < not_before) {
+        return Err(invalid("the token is not valid yet"));
+    }
+
+    Ok(claims)
+}
+
+/// Encodes bytes as base64url without padding, as JWTs use
+#[cfg(feature = "sign")]
+fn encode(bytes: &[u8]) -> String {
+    base64::encode(bytes)
+        .trim_end_matches('_')
+        .replace('~', "_")
+}
+
+fn decode(encoded: &str) -> Result<Vec<u8>, Error> {
+    let mut translated = encoded.replace('_', "~");
+
+    while !translated.len().is_multiple_of(4) {
+        translated.push('_');
+    }
+
+    base64::decode(&translated)
+        .map_err(|_| Error::InvalidToken("a part is not valid base64url".to_string()))
+}
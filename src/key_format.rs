@@ -0,0 +1,69 @@
+/*
+MIT License
+
+Copyright (c) 2020 Martin Karlsen Jensen
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+//! Sniffing of key files that could not be parsed, to explain what they contain instead
+
+/// Describes a key file that is not an RSA private key, along with how to convert it
+///
+/// Returns `None` when the contents are not recognized, eg. a malformed RSA private key.
+///
+/// # Arguments
+/// * `key` - The contents of the key file
+pub(crate) fn diagnose(key: &[u8]) -> Option<String> {
+    let text = String::from_utf8_lossy(key);
+    let label = text
+        .lines()
+        .map(str::trim)
+        .find_map(|line| line.strip_prefix("-----BEGIN ")?.strip_suffix("-----"));
+    let diagnosis = match label {
+        Some("RSA PRIVATE KEY") if text.contains("ENCRYPTED") => {
+            "an encrypted RSA private key. Remove the passphrase with: openssl rsa -in key.pem -out key-decrypted.pem"
+        }
+        Some("ENCRYPTED PRIVATE KEY") => {
+            "an encrypted PKCS#8 private key. Remove the passphrase with: openssl pkcs8 -in key.pem -traditional -out key-decrypted.pem"
+        }
+        Some("PRIVATE KEY") => {
+            "a PKCS#8 private key that is not an RSA key. CloudFront only accepts RSA keys, create one with: openssl genrsa -out private_key.pem 2048"
+        }
+        Some("EC PRIVATE KEY") => {
+            "an EC private key. CloudFront only accepts RSA keys, create one with: openssl genrsa -out private_key.pem 2048"
+        }
+        Some("OPENSSH PRIVATE KEY") => {
+            "an OpenSSH private key. Convert it to PEM with: ssh-keygen -p -m PEM -f key"
+        }
+        Some("CERTIFICATE") => {
+            "a certificate instead of a private key. Use the private key the certificate was created for"
+        }
+        Some("PUBLIC KEY") | Some("RSA PUBLIC KEY") => {
+            "a public key. Sign with the private key and upload the public key to CloudFront"
+        }
+        Some(_) => return None,
+        None if key.first() == Some(&0x30) => {
+            "a DER encoded key. Convert it to PEM with: openssl rsa -inform DER -in key.der -out key.pem"
+        }
+        None => "no PEM data. Make sure the path points to the private key downloaded from AWS or generated by OpenSSL",
+    };
+
+    Some(diagnosis.to_string())
+}
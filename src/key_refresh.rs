@@ -0,0 +1,326 @@
+/*
+MIT License
+
+Copyright (c) 2020 Martin Karlsen Jensen
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use crate::{CloudFrontSigner, Error, Policy, SignedCookies, SignedPolicy, SignedUrl};
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::{Arc, Condvar, Mutex, RwLock};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime};
+
+/// A signer whose key can be reloaded while it is in use, eg. after the key file was rotated
+///
+/// Requests that are being signed while the key is reloaded finish with the previous signer.
+/// Cloning is cheap and every clone sees the reloaded key.
+///
+/// When reloading fails, eg. because a remote key store is unreachable, signing continues with
+/// the last key that loaded successfully and a warning is logged. Set a
+/// [grace period](ReloadingSigner::grace_period) to fail with [`Error::KeyRefreshFailed`] once the
+/// key could not be reloaded for too long, eg. when keys are revoked shortly after rotation.
+///
+/// # Example
+/// ```
+/// use cloudfront_policy_signer::ReloadingSigner;
+/// use std::time::Duration;
+///
+/// let signer = ReloadingSigner::from_file("examples/key.pem", "APKAIEXAMPLE").unwrap();
+/// let refresh = signer.spawn_refresh(Duration::from_secs(300));
+///
+/// signer
+///     .current()
+///     .sign_url("https://example.cloudfront.net/flowerpot.png", 1700000000)
+///     .unwrap();
+///
+/// assert!(refresh.status().last_error.is_none());
+/// ```
+#[derive(Clone)]
+pub struct ReloadingSigner {
+    shared: Arc<Shared>,
+    grace_period: Option<Duration>,
+}
+
+struct Shared {
+    load: Box<dyn Fn() -> Result<CloudFrontSigner, Error> + Send + Sync>,
+    current: RwLock<Arc<CloudFrontSigner>>,
+    status: Mutex<RefreshStatus>,
+}
+
+/// The outcome of the latest reloads of a [`ReloadingSigner`], eg. for a health endpoint
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RefreshStatus {
+    /// When the key was last loaded successfully
+    pub last_success: SystemTime,
+    /// When the key was last attempted to be loaded
+    pub last_attempt: SystemTime,
+    /// Why the latest attempt failed, `None` if it succeeded
+    pub last_error: Option<String>,
+    /// How many attempts failed since the key was last loaded successfully
+    pub consecutive_failures: u32,
+}
+
+impl fmt::Debug for ReloadingSigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReloadingSigner")
+            .field("current", &self.current())
+            .field("status", &self.status())
+            .field("grace_period", &self.grace_period)
+            .finish()
+    }
+}
+
+impl ReloadingSigner {
+    /// Constructs the signer, loading the key once before returning
+    ///
+    /// # Arguments
+    /// * `load` - Creates a signer from the current key, called again on every reload
+    pub fn new<F>(load: F) -> Result<ReloadingSigner, Error>
+    where
+        F: Fn() -> Result<CloudFrontSigner, Error> + Send + Sync + 'static,
+    {
+        let signer = load()?;
+        let now = SystemTime::now();
+
+        Ok(ReloadingSigner {
+            shared: Arc::new(Shared {
+                load: Box::new(load),
+                current: RwLock::new(Arc::new(signer)),
+                status: Mutex::new(RefreshStatus {
+                    last_success: now,
+                    last_attempt: now,
+                    last_error: None,
+                    consecutive_failures: 0,
+                }),
+            }),
+            grace_period: None,
+        })
+    }
+
+    /// Constructs a signer that reloads the key from a file
+    ///
+    /// # Arguments
+    /// * `private_key_location` - Path where the private key file can be found
+    /// * `key_pair_id` - The key pair ID from AWS CloudFront
+    pub fn from_file<P: Into<PathBuf>, T: ToString>(
+        private_key_location: P,
+        key_pair_id: T,
+    ) -> Result<ReloadingSigner, Error> {
+        let path = private_key_location.into();
+        let key_pair_id = key_pair_id.to_string();
+
+        ReloadingSigner::new(move || CloudFrontSigner::new(&path, &key_pair_id))
+    }
+
+    /// Sets how long signing may continue with the previous key after reloading started failing
+    ///
+    /// Without a grace period the previous key is used until a reload succeeds.
+    ///
+    /// # Arguments
+    /// * `grace_period` - How long after the last successful reload signing continues
+    pub fn grace_period(mut self, grace_period: Duration) -> ReloadingSigner {
+        self.grace_period = Some(grace_period);
+        self
+    }
+
+    /// Returns the signer with the most recently loaded key
+    ///
+    /// Unlike the signing methods this ignores the grace period.
+    pub fn current(&self) -> Arc<CloudFrontSigner> {
+        self.shared
+            .current
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Returns the outcome of the latest reloads
+    pub fn status(&self) -> RefreshStatus {
+        self.shared
+            .status
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Loads the key again and swaps it in, keeping the previous key if loading fails
+    pub fn reload(&self) -> Result<(), Error> {
+        let result = (self.shared.load)();
+        let mut status = self.shared.status.lock().unwrap_or_else(|e| e.into_inner());
+
+        status.last_attempt = SystemTime::now();
+
+        match result {
+            Ok(signer) => {
+                *self
+                    .shared
+                    .current
+                    .write()
+                    .unwrap_or_else(|e| e.into_inner()) = Arc::new(signer);
+                status.last_success = status.last_attempt;
+                status.last_error = None;
+                status.consecutive_failures = 0;
+
+                Ok(())
+            }
+            Err(e) => {
+                warn!(
+                    "Could not reload the private key due to {}, signing with the key loaded {:?} ago",
+                    e,
+                    status
+                        .last_attempt
+                        .duration_since(status.last_success)
+                        .unwrap_or_default()
+                );
+                status.last_error = Some(e.to_string());
+                status.consecutive_failures += 1;
+
+                Err(e)
+            }
+        }
+    }
+
+    /// Reloads the key on a background thread until the returned handle is dropped
+    ///
+    /// # Arguments
+    /// * `interval` - How long to wait between reloads
+    pub fn spawn_refresh(&self, interval: Duration) -> RefreshHandle {
+        let stop = Arc::new((Mutex::new(false), Condvar::new()));
+        let signer = self.clone();
+        let thread_stop = stop.clone();
+        let thread = thread::spawn(move || {
+            let (stopped, condvar) = &*thread_stop;
+            let mut stopped = stopped.lock().unwrap_or_else(|e| e.into_inner());
+
+            loop {
+                stopped = condvar
+                    .wait_timeout_while(stopped, interval, |stopped| !*stopped)
+                    .unwrap_or_else(|e| e.into_inner())
+                    .0;
+
+                if *stopped {
+                    return;
+                }
+
+                let _ = signer.reload();
+            }
+        });
+
+        RefreshHandle {
+            signer: self.clone(),
+            stop,
+            thread: Some(thread),
+        }
+    }
+
+    /// Creates a URL with a canned policy, see [`CloudFrontSigner::sign_url`]
+    ///
+    /// # Arguments
+    /// * `resource` - The protected resource eg. https://example.cloudfront.net/flowerpot.png
+    /// * `expiry` - Absolute time that the URL expires, given in the form of a unix timestamp in UTC
+    pub fn sign_url<R: AsRef<str>>(&self, resource: R, expiry: u64) -> Result<SignedUrl, Error> {
+        self.usable()?.sign_url(resource, expiry)
+    }
+
+    /// Creates cookies with a canned policy, see [`CloudFrontSigner::create_canned_policy_signed_cookies`]
+    ///
+    /// # Arguments
+    /// * `resource` - The protected resource eg. https://example.cloudfront.net/flowerpot.png
+    /// * `expiry` - Absolute time that the cookies expire, given in the form of a unix timestamp in UTC
+    pub fn create_canned_policy_signed_cookies<R: AsRef<str>>(
+        &self,
+        resource: R,
+        expiry: u64,
+    ) -> Result<SignedCookies, Error> {
+        self.usable()?
+            .create_canned_policy_signed_cookies(resource, expiry)
+    }
+
+    /// Signs a custom policy, see [`CloudFrontSigner::sign_policy`]
+    ///
+    /// # Arguments
+    /// * `policy` - The policy to sign
+    pub fn sign_policy(&self, policy: &Policy) -> Result<SignedPolicy, Error> {
+        self.usable()?.sign_policy(policy)
+    }
+
+    /// Returns the current signer unless reloading has been failing for longer than the grace period
+    fn usable(&self) -> Result<Arc<CloudFrontSigner>, Error> {
+        if let Some(grace_period) = self.grace_period {
+            let status = self.status();
+
+            if let Some(reason) = status.last_error {
+                let failing_for = SystemTime::now()
+                    .duration_since(status.last_success)
+                    .unwrap_or_default();
+
+                if failing_for > grace_period {
+                    error!(
+                        "Refused to sign with a key that could not be reloaded for {:?}",
+                        failing_for
+                    );
+                    return Err(Error::KeyRefreshFailed(reason));
+                }
+            }
+        }
+
+        Ok(self.current())
+    }
+}
+
+/// Handle of the background thread started by [`ReloadingSigner::spawn_refresh`], which stops the thread when dropped
+pub struct RefreshHandle {
+    signer: ReloadingSigner,
+    stop: Arc<(Mutex<bool>, Condvar)>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl fmt::Debug for RefreshHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RefreshHandle")
+            .field("status", &self.status())
+            .finish()
+    }
+}
+
+impl RefreshHandle {
+    /// Returns the outcome of the latest reloads, see [`ReloadingSigner::status`]
+    pub fn status(&self) -> RefreshStatus {
+        self.signer.status()
+    }
+
+    /// Stops the background thread and waits for it to finish
+    pub fn stop(self) {}
+}
+
+impl Drop for RefreshHandle {
+    fn drop(&mut self) {
+        let (stopped, condvar) = &*self.stop;
+
+        *stopped.lock().unwrap_or_else(|e| e.into_inner()) = true;
+        condvar.notify_all();
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
@@ -0,0 +1,513 @@
+/*
+MIT License
+
+Copyright (c) 2020 Martin Karlsen Jensen
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+//! Inspecting key material used for signing
+
+#[cfg(feature = "sign")]
+use crate::schedule::TimeZone;
+#[cfg(feature = "sign")]
+use crate::signed_url::REDACTED;
+use crate::{json, Error};
+#[cfg(feature = "sign")]
+use openssl::asn1::{Asn1Time, Asn1TimeRef};
+#[cfg(feature = "sign")]
+use openssl::error::ErrorStack;
+use openssl::hash::{hash, MessageDigest};
+#[cfg(feature = "sign")]
+use openssl::pkey::Private;
+use openssl::pkey::{HasPublic, PKey};
+#[cfg(feature = "sign")]
+use openssl::rsa::Rsa;
+#[cfg(feature = "sign")]
+use openssl::x509::X509;
+#[cfg(feature = "sign")]
+use std::fmt;
+#[cfg(feature = "sign")]
+use std::fs;
+#[cfg(feature = "sign")]
+use std::io::ErrorKind;
+#[cfg(feature = "sign")]
+use std::path::{Path, PathBuf};
+#[cfg(feature = "sign")]
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(feature = "sign")]
+use std::sync::Arc;
+#[cfg(feature = "sign")]
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The only RSA key size CloudFront accepts for public keys
+pub const CLOUDFRONT_KEY_BITS: u32 = 2048;
+
+/// A newly generated key pair, created by [`generate_cloudfront_keypair`]
+///
+/// The `Debug` output redacts the private key.
+#[cfg(feature = "sign")]
+pub struct GeneratedKeyPair {
+    /// The private key as PEM-encoded PKCS#8, to be kept secret and used for signing
+    pub private_key_pem: String,
+    /// The public key as PEM-encoded SubjectPublicKeyInfo, as accepted by the CloudFront console
+    pub public_key_pem: String,
+}
+
+#[cfg(feature = "sign")]
+impl fmt::Debug for GeneratedKeyPair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GeneratedKeyPair")
+            .field("private_key_pem", &REDACTED)
+            .field("public_key_pem", &self.public_key_pem)
+            .finish()
+    }
+}
+
+/// Describes a private key for `Debug` output without revealing it, eg. `<redacted> 2048-bit RSA, fp=ab:cd:..`
+///
+/// # Arguments
+/// * `key` - The private key
+#[cfg(feature = "sign")]
+pub(crate) fn describe_redacted(key: &PKey<Private>) -> String {
+    match fingerprint_sha256(key) {
+        Ok(fingerprint) => format!("{} {}-bit RSA, fp={}", REDACTED, key.bits(), fingerprint),
+        Err(_) => format!("{} {}-bit RSA", REDACTED, key.bits()),
+    }
+}
+
+/// Generates an RSA key pair that can be uploaded to CloudFront
+///
+/// # Arguments
+/// * `bits` - The size of the key, which must be [`CLOUDFRONT_KEY_BITS`] as CloudFront rejects other sizes
+///
+/// # Example
+/// ```
+/// use cloudfront_policy_signer::keys::{generate_cloudfront_keypair, CLOUDFRONT_KEY_BITS};
+/// use cloudfront_policy_signer::CloudFrontSigner;
+///
+/// let key_pair = generate_cloudfront_keypair(CLOUDFRONT_KEY_BITS).unwrap();
+///
+/// assert!(key_pair.public_key_pem.starts_with("-----BEGIN PUBLIC KEY-----"));
+/// CloudFrontSigner::new_in_memory(&key_pair.private_key_pem, "APKAIEXAMPLE").unwrap();
+/// ```
+#[cfg(feature = "sign")]
+pub fn generate_cloudfront_keypair(bits: u32) -> Result<GeneratedKeyPair, Error> {
+    if bits != CLOUDFRONT_KEY_BITS {
+        error!("Refused to generate a {}-bit key", bits);
+        return Err(Error::UnsupportedKeySize(bits));
+    }
+
+    let key = Rsa::generate(bits).and_then(PKey::from_rsa).map_err(|e| {
+        error!("Could not generate RSA key due to {}", e);
+        Error::Unknown
+    })?;
+    let pem = |pem: Result<Vec<u8>, ErrorStack>| {
+        pem.map_err(|e| {
+            error!("Could not encode key due to {}", e);
+            Error::Unknown
+        })
+        .map(|pem| String::from_utf8_lossy(&pem).into_owned())
+    };
+
+    Ok(GeneratedKeyPair {
+        private_key_pem: pem(key.private_key_to_pem_pkcs8())?,
+        public_key_pem: pem(key.public_key_to_pem())?,
+    })
+}
+
+/// Returns the SHA-256 fingerprint of the public part of a key as colon separated hex
+///
+/// The fingerprint is taken over the DER encoded SubjectPublicKeyInfo, so a private key and its
+/// public key have the same fingerprint. Compare it with
+/// `openssl pkey -pubin -in public_key.pem -outform DER | openssl dgst -sha256 -c`.
+///
+/// # Arguments
+/// * `key` - A private or public key
+pub fn fingerprint_sha256<T: HasPublic>(key: &PKey<T>) -> Result<String, Error> {
+    fingerprint(key, MessageDigest::sha256())
+}
+
+/// Returns the MD5 fingerprint of the public part of a key as colon separated hex
+///
+/// Only meant for comparing with tools that still show MD5 fingerprints, prefer [`fingerprint_sha256`].
+///
+/// # Arguments
+/// * `key` - A private or public key
+pub fn fingerprint_md5<T: HasPublic>(key: &PKey<T>) -> Result<String, Error> {
+    fingerprint(key, MessageDigest::md5())
+}
+
+fn fingerprint<T: HasPublic>(key: &PKey<T>, digest: MessageDigest) -> Result<String, Error> {
+    let der = key.public_key_to_der().map_err(|e| {
+        error!("Could not encode public key due to {}", e);
+        Error::Unknown
+    })?;
+    let digest = hash(digest, &der).map_err(|e| {
+        error!("Could not hash public key due to {}", e);
+        Error::Unknown
+    })?;
+
+    Ok(digest
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<Vec<_>>()
+        .join(":"))
+}
+
+#[cfg(feature = "sign")]
+/// The default period before the key expires in which [`crate::CloudFrontSigner::expiry_warnings`] warns
+pub const DEFAULT_EXPIRY_WARNING_PERIOD: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Where the expiry of a signing key is known from
+#[cfg(feature = "sign")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExpirySource {
+    /// The `notAfter` of a certificate in the same PEM file as the private key
+    Certificate,
+    /// A file next to the private key whose name ends in `.expiry`, eg. key.pem.expiry
+    MetadataFile(PathBuf),
+    /// Set with [`crate::CloudFrontSigner::key_expires_at`]
+    Configured,
+}
+
+/// A signing key that expires within the warning period or has expired, see [`crate::CloudFrontSigner::expiry_warnings`]
+#[cfg(feature = "sign")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpiryWarning {
+    /// Where the expiry is known from
+    pub source: ExpirySource,
+    /// When the key expires
+    pub expires_at: SystemTime,
+    /// How long the key is still valid for, zero if it has expired
+    pub remaining: Duration,
+}
+
+#[cfg(feature = "sign")]
+impl fmt::Display for ExpiryWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let source = match &self.source {
+            ExpirySource::Certificate => "certificate".to_string(),
+            ExpirySource::MetadataFile(path) => path.display().to_string(),
+            ExpirySource::Configured => "configuration".to_string(),
+        };
+
+        if self.remaining.is_zero() {
+            write!(f, "The signing key has expired according to its {}", source)
+        } else {
+            write!(
+                f,
+                "The signing key expires in {} days according to its {}",
+                self.remaining.as_secs() / (24 * 60 * 60),
+                source
+            )
+        }
+    }
+}
+
+/// Receives the warnings of a signer whose key is about to expire
+#[cfg(feature = "sign")]
+pub type ExpiryCallback = Arc<dyn Fn(&ExpiryWarning) + Send + Sync>;
+
+/// The known expiries of a signing key and how to warn about them
+#[cfg(feature = "sign")]
+pub(crate) struct KeyExpiry {
+    pub(crate) expiries: Vec<(ExpirySource, SystemTime)>,
+    pub(crate) warning_period: Duration,
+    pub(crate) callback: Option<ExpiryCallback>,
+    /// The unix timestamp the callback was last called at, so it is called at most once a day
+    last_notified: AtomicU64,
+}
+
+#[cfg(feature = "sign")]
+impl KeyExpiry {
+    /// Reads the expiries from the certificates in the PEM file of the key and from its metadata file
+    ///
+    /// # Arguments
+    /// * `pem` - The contents of the PEM file of the private key
+    /// * `location` - Where the private key was read from, if it was read from a file
+    pub(crate) fn read(pem: &[u8], location: Option<&Path>) -> KeyExpiry {
+        let mut expiries = Vec::new();
+
+        if let Ok(certificates) = X509::stack_from_pem(pem) {
+            expiries.extend(
+                certificates
+                    .iter()
+                    .filter_map(|certificate| asn1_to_system_time(certificate.not_after()))
+                    .map(|expires_at| (ExpirySource::Certificate, expires_at)),
+            );
+        }
+
+        if let Some(location) = location {
+            let mut path = location.as_os_str().to_owned();
+
+            path.push(".expiry");
+
+            let path = PathBuf::from(path);
+
+            match fs::read_to_string(&path) {
+                Ok(contents) => match parse_expiry(contents.trim()) {
+                    Some(expires_at) => expiries.push((ExpirySource::MetadataFile(path), expires_at)),
+                    None => warn!(
+                        "Ignored {} as it is neither a unix timestamp nor a date such as 2030-06-01",
+                        path.display()
+                    ),
+                },
+                Err(e) if e.kind() == ErrorKind::NotFound => {}
+                Err(e) => warn!("Could not read {} due to {}", path.display(), e),
+            }
+        }
+
+        KeyExpiry {
+            expiries,
+            warning_period: DEFAULT_EXPIRY_WARNING_PERIOD,
+            callback: None,
+            last_notified: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns a warning for every expiry within the warning period of the given time
+    pub(crate) fn warnings(&self, now: SystemTime) -> Vec<ExpiryWarning> {
+        self.expiries
+            .iter()
+            .filter(|(_, expires_at)| *expires_at <= now + self.warning_period)
+            .map(|(source, expires_at)| ExpiryWarning {
+                source: source.clone(),
+                expires_at: *expires_at,
+                remaining: expires_at.duration_since(now).unwrap_or_default(),
+            })
+            .collect()
+    }
+
+    /// Passes the warnings to the callback, unless it was called less than a day ago
+    pub(crate) fn notify(&self, now: SystemTime) {
+        let Some(callback) = &self.callback else {
+            return;
+        };
+        let now_secs = now
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since_epoch| since_epoch.as_secs());
+        let last = self.last_notified.load(Ordering::Relaxed);
+
+        if last != 0 && now_secs < last + 24 * 60 * 60 {
+            return;
+        }
+
+        if self
+            .last_notified
+            .compare_exchange(last, now_secs.max(1), Ordering::Relaxed, Ordering::Relaxed)
+            .is_ok()
+        {
+            for warning in self.warnings(now) {
+                callback(&warning);
+            }
+        }
+    }
+}
+
+/// Parses the contents of an expiry metadata file, a unix timestamp or a date in UTC
+#[cfg(feature = "sign")]
+fn parse_expiry(contents: &str) -> Option<SystemTime> {
+    let secs = match contents.parse::<u64>() {
+        Ok(secs) => secs,
+        Err(_) => TimeZone::utc()
+            .to_unix(&format!("{}T00:00", contents))
+            .ok()?,
+    };
+
+    Some(UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+#[cfg(feature = "sign")]
+fn asn1_to_system_time(time: &Asn1TimeRef) -> Option<SystemTime> {
+    let diff = Asn1Time::from_unix(0).ok()?.diff(time).ok()?;
+    let secs = i64::from(diff.days) * 24 * 60 * 60 + i64::from(diff.secs);
+
+    u64::try_from(secs)
+        .ok()
+        .map(|secs| UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+/// The format of a key pair ID, see [`key_pair_id_format`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyPairIdFormat {
+    /// The ID of a public key in a key group, eg. K2JCJMDEHXQW5F
+    PublicKey,
+    /// The ID of a legacy CloudFront key pair of the root account, eg. APKA9ONS7QCOWEXAMPLE
+    Legacy,
+    /// Neither format, eg. because the ID was truncated when it was copied
+    Unrecognized,
+}
+
+/// Returns the format of a key pair ID
+///
+/// # Arguments
+/// * `key_pair_id` - The key pair ID eg. K2JCJMDEHXQW5F
+pub fn key_pair_id_format(key_pair_id: &str) -> KeyPairIdFormat {
+    let alphanumeric = key_pair_id
+        .bytes()
+        .all(|b| b.is_ascii_uppercase() || b.is_ascii_digit());
+
+    match key_pair_id.len() {
+        13..=15 if alphanumeric && key_pair_id.starts_with('K') => KeyPairIdFormat::PublicKey,
+        20 if alphanumeric && key_pair_id.starts_with("APKA") => KeyPairIdFormat::Legacy,
+        _ => KeyPairIdFormat::Unrecognized,
+    }
+}
+
+/// Trims the whitespace around a key pair ID and checks that it can be valid
+///
+/// Fails with [`Error::InvalidKeyPairId`] if the ID is empty or contains lowercase letters or
+/// other characters that never appear in key pair IDs, which CloudFront would only reject with a
+/// 403. IDs in neither known format are accepted with a warning, as they may be truncated.
+///
+/// # Arguments
+/// * `key_pair_id` - The key pair ID as configured eg. K2JCJMDEHXQW5F
+///
+/// # Example
+/// ```
+/// use cloudfront_policy_signer::keys::normalize_key_pair_id;
+///
+/// assert_eq!(normalize_key_pair_id(" K2JCJMDEHXQW5F\n").unwrap(), "K2JCJMDEHXQW5F");
+/// assert!(normalize_key_pair_id("k2jcjmdehxqw5f").is_err());
+/// ```
+pub fn normalize_key_pair_id(key_pair_id: &str) -> Result<String, Error> {
+    let key_pair_id = key_pair_id.trim();
+    let invalid = |reason: String| {
+        error!("Refused the key pair ID '{}'", key_pair_id);
+        Err(Error::InvalidKeyPairId(reason))
+    };
+
+    if key_pair_id.is_empty() {
+        return invalid("the key pair ID is empty".to_string());
+    }
+
+    if key_pair_id.bytes().any(|b| b.is_ascii_lowercase()) {
+        return invalid(format!(
+            "'{}' contains lowercase letters, but key pair IDs are upper case",
+            key_pair_id
+        ));
+    }
+
+    if let Some(c) = key_pair_id.chars().find(|c| !c.is_ascii_alphanumeric()) {
+        return invalid(format!("'{}' contains the character {:?}", key_pair_id, c));
+    }
+
+    if key_pair_id_format(key_pair_id) == KeyPairIdFormat::Unrecognized {
+        warn!(
+            "The key pair ID {} is neither a public key ID such as K2JCJMDEHXQW5F nor a legacy ID such as APKA9ONS7QCOWEXAMPLE, it may be truncated",
+            key_pair_id
+        );
+    }
+
+    Ok(key_pair_id.to_string())
+}
+
+/// The `PublicKeyConfig` of the CloudFront API for uploading a public key
+///
+/// The JSON can be passed to `aws cloudfront create-public-key --public-key-config file://config.json`
+/// or used as input by Terraform and CDK pipelines provisioning key groups.
+///
+/// # Example
+/// ```
+/// # #[cfg(feature = "sign")]
+/// # {
+/// use cloudfront_policy_signer::CloudFrontSigner;
+///
+/// let signer = CloudFrontSigner::new("examples/key.pem", "APKAIEXAMPLE").unwrap();
+/// let config = signer
+///     .public_key_config("media-signing-2024")
+///     .unwrap()
+///     .comment("Signs URLs for the media distribution");
+///
+/// println!("{}", config.to_json());
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublicKeyConfig {
+    /// A unique reference for the upload, defaults to the SHA-256 fingerprint so uploading the same key twice is idempotent
+    pub caller_reference: String,
+    /// The name of the public key in CloudFront
+    pub name: String,
+    /// The PEM-encoded public key
+    pub encoded_key: String,
+    /// An optional comment shown in the CloudFront console
+    pub comment: Option<String>,
+}
+
+impl PublicKeyConfig {
+    /// Constructs the configuration for the public part of a key
+    ///
+    /// # Arguments
+    /// * `name` - The name of the public key in CloudFront eg. media-signing-2024
+    /// * `key` - A private or public key
+    pub fn for_key<N: Into<String>, T: HasPublic>(
+        name: N,
+        key: &PKey<T>,
+    ) -> Result<PublicKeyConfig, Error> {
+        let encoded_key = key.public_key_to_pem().map_err(|e| {
+            error!("Could not encode public key due to {}", e);
+            Error::Unknown
+        })?;
+
+        Ok(PublicKeyConfig {
+            caller_reference: fingerprint_sha256(key)?.replace(':', ""),
+            name: name.into(),
+            encoded_key: String::from_utf8_lossy(&encoded_key).into_owned(),
+            comment: None,
+        })
+    }
+
+    /// Sets the caller reference
+    ///
+    /// # Arguments
+    /// * `caller_reference` - A unique reference for the upload
+    pub fn caller_reference<T: Into<String>>(mut self, caller_reference: T) -> PublicKeyConfig {
+        self.caller_reference = caller_reference.into();
+        self
+    }
+
+    /// Sets the comment
+    ///
+    /// # Arguments
+    /// * `comment` - The comment shown in the CloudFront console
+    pub fn comment<T: Into<String>>(mut self, comment: T) -> PublicKeyConfig {
+        self.comment = Some(comment.into());
+        self
+    }
+
+    /// Returns the configuration as JSON in the format of the CloudFront API
+    pub fn to_json(&self) -> String {
+        let mut json = String::from("{\"CallerReference\":");
+
+        json::write_string(&mut json, &self.caller_reference);
+        json.push_str(",\"Name\":");
+        json::write_string(&mut json, &self.name);
+        json.push_str(",\"EncodedKey\":");
+        json::write_string(&mut json, &self.encoded_key);
+
+        if let Some(comment) = &self.comment {
+            json.push_str(",\"Comment\":");
+            json::write_string(&mut json, comment);
+        }
+
+        json.push('}');
+        json
+    }
+}
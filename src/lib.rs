@@ -22,14 +22,205 @@ OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 SOFTWARE.
 */
 
-use log::error;
-use openssl::base64::encode_block;
+/// Logs an error through `log` when the `log` feature is enabled
+macro_rules! error {
+    ($($arg:tt)*) => {{
+        #[cfg(feature = "log")]
+        log::error!($($arg)*);
+        #[cfg(not(feature = "log"))]
+        let _ = format_args!($($arg)*);
+    }};
+}
+
+/// Logs a warning through `log` when the `log` feature is enabled
+macro_rules! warn {
+    ($($arg:tt)*) => {{
+        #[cfg(feature = "log")]
+        log::warn!($($arg)*);
+        #[cfg(not(feature = "log"))]
+        let _ = format_args!($($arg)*);
+    }};
+}
+
+pub mod base64;
+#[cfg(feature = "sign")]
+mod caller;
+#[cfg(feature = "sign")]
+mod canary;
+mod canned_template;
+#[cfg(feature = "sign")]
+pub mod config;
+#[cfg(feature = "sign")]
+pub mod context;
+#[cfg(feature = "sign")]
+mod cookies;
+pub mod edge;
+#[cfg(feature = "sign")]
+pub mod global;
+mod json;
+pub mod jwt;
+#[cfg(feature = "sign")]
+mod key_format;
+#[cfg(feature = "sign")]
+mod key_refresh;
+pub mod keys;
+#[cfg(feature = "sign")]
+pub mod origin;
+mod policy;
+mod policy_template;
+#[cfg(feature = "sign")]
+pub mod post_process;
+#[cfg(feature = "sign")]
+mod rate_limit;
+#[cfg(feature = "sign")]
+mod refresh;
+#[cfg(feature = "sign")]
+mod regional;
+mod resource;
+#[cfg(feature = "sign")]
+pub mod rewrite;
+#[cfg(feature = "sign")]
+pub mod sampling;
+#[cfg(feature = "sign")]
+pub mod sandbox;
+#[cfg(feature = "sign")]
+pub mod schedule;
+#[cfg(feature = "sign")]
+mod shared_policy;
+#[cfg(feature = "sign")]
+mod signed_policy;
+#[cfg(feature = "sign")]
+mod signed_url;
+pub mod single_use;
+#[cfg(feature = "sign")]
+mod status;
+#[cfg(feature = "sign")]
+mod stream;
+#[cfg(feature = "sign")]
+pub mod template;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(feature = "sign")]
+mod ttl_policy;
+#[cfg(feature = "sign")]
+mod url_signer;
+pub mod verify;
+
+#[cfg(feature = "sign")]
+use caller::Attribution;
+#[cfg(feature = "sign")]
+pub use caller::CallerSigner;
+#[cfg(feature = "sign")]
+pub use canary::CanarySigner;
+pub use canned_template::CannedPolicyTemplate;
+#[cfg(feature = "sign")]
+pub use cookies::{SignedCookies, MAX_COOKIE_SIZE};
+#[cfg(feature = "sign")]
+pub use key_refresh::{RefreshHandle, RefreshStatus, ReloadingSigner};
+pub use policy::{Condition, Policy, PolicyBuilder, PolicyDifference, PolicyKind, Statement};
+pub use policy_template::PolicyTemplate;
+#[cfg(feature = "sign")]
+pub use refresh::RefreshingSignedUrl;
+#[cfg(feature = "sign")]
+pub use regional::{RegionSelection, RegionalSigner};
+pub use resource::{EncodingProfile, Resource};
+#[cfg(feature = "sign")]
+pub use shared_policy::SharedPolicy;
+#[cfg(feature = "sign")]
+pub use signed_policy::{PolicyArtifacts, SignedPolicy};
+#[cfg(feature = "sign")]
+pub use signed_url::SignedUrl;
+#[cfg(feature = "sign")]
+pub use status::{KeyProvider, SignerStatus};
+#[cfg(feature = "sign")]
+pub use stream::PolicyStream;
+#[cfg(feature = "sign")]
+pub use ttl_policy::TtlPolicy;
+#[cfg(feature = "sign")]
+pub use url_signer::UrlSigner;
+
+#[cfg(feature = "macros")]
+pub use cloudfront_policy_signer_macros::{canned_policy, resource};
+
+/// Checks that the macros refuse invalid resources at compile time, only built for doctests
+///
+/// A valid resource compiles:
+/// ```
+/// use cloudfront_policy_signer::{resource, Resource};
+///
+/// let resource: Resource = resource!("https://example.cloudfront.net/videos/*.mp4");
+///
+/// assert_eq!(resource.as_str(), "https://example.cloudfront.net/videos/*.mp4");
+/// ```
+///
+/// Whitespace has to be percent-encoded:
+/// ```compile_fail
+/// cloudfront_policy_signer::resource!("https://example.cloudfront.net/flower pot.png");
+/// ```
+///
+/// A wildcard in the host is only allowed as the first label:
+/// ```compile_fail
+/// cloudfront_policy_signer::resource!("https://example.*.net/flowerpot.png");
+/// ```
+///
+/// Empty path segments never match the requested URL:
+/// ```compile_fail
+/// cloudfront_policy_signer::resource!("https://example.cloudfront.net//flowerpot.png");
+/// ```
+///
+/// The scheme is required:
+/// ```compile_fail
+/// cloudfront_policy_signer::resource!("example.cloudfront.net/flowerpot.png");
+/// ```
+///
+/// Canned policies accept a query string but no wildcards:
+/// ```
+/// use cloudfront_policy_signer::{canned_policy, CannedPolicyTemplate};
+///
+/// static PREVIEW: CannedPolicyTemplate =
+///     canned_policy!("https://example.cloudfront.net/flowerpot.png?size=small");
+/// ```
+/// ```compile_fail
+/// use cloudfront_policy_signer::{canned_policy, CannedPolicyTemplate};
+///
+/// static VIDEOS: CannedPolicyTemplate = canned_policy!("https://example.cloudfront.net/videos/*");
+/// ```
+/// ```compile_fail
+/// use cloudfront_policy_signer::{canned_policy, CannedPolicyTemplate};
+///
+/// static ALL: CannedPolicyTemplate = canned_policy!("https://*.cloudfront.net/flowerpot.png");
+/// ```
+/// ```compile_fail
+/// use cloudfront_policy_signer::{canned_policy, CannedPolicyTemplate};
+///
+/// static PNG: CannedPolicyTemplate = canned_policy!("https://example.cloudfront.net/flowerp?t/a.png");
+/// ```
+#[cfg(all(doctest, feature = "macros"))]
+pub struct MacroValidation;
+
+use std::fmt;
+use std::io::Error as SysIOError;
+
+#[cfg(feature = "sign")]
 use openssl::hash::MessageDigest;
+#[cfg(feature = "sign")]
 use openssl::pkey::{PKey, Private};
+#[cfg(feature = "sign")]
 use openssl::rsa;
+#[cfg(feature = "sign")]
 use openssl::sign::Signer;
-use std::io::Error as SysIOError;
-use std::{fmt, fs};
+#[cfg(feature = "sign")]
+use std::borrow::Cow;
+#[cfg(feature = "sign")]
+use std::fs;
+#[cfg(feature = "sign")]
+use std::io::Write;
+#[cfg(feature = "sign")]
+use std::path::Path;
+#[cfg(feature = "sign")]
+use std::sync::Arc;
+#[cfg(feature = "sign")]
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 /// Enumeration of all possible errors returned by the crate
 #[derive(Debug)]
@@ -38,14 +229,122 @@ pub enum Error {
     IOError(SysIOError),
     /// The private key was in an unsupported format or somehow malformed. It only accepts keys in PEM-encoded PKCS#1
     PrivateKeyParseError,
+    /// The private key file contains something other than an RSA private key. The message describes what it contains and how to convert it
+    UnsupportedPrivateKeyFormat(String),
     /// The key could not be converted from a `openssl::rsa::Rsa<openssl::pkey::Private>` to a `PKey<Private>`
     PrivateKeyConvertError,
     /// The policy could not be signed. Refer to the error printed out in the logs
     CouldNotSign,
+    /// The public key was in an unsupported format or somehow malformed. It only accepts PEM-encoded RSA public keys
+    PublicKeyParseError,
+    /// The URL is not a valid signed URL. The message describes what is wrong with it
+    InvalidSignedUrl(String),
+    /// The policy is not a valid policy document. The message describes what is wrong with it
+    InvalidPolicy(String),
+    /// The configuration file could not be parsed or a profile is incomplete. The message describes the problem
+    InvalidConfig(String),
+    /// The profile does not exist in the configuration file
+    ProfileNotFound(String),
+    /// CloudFront does not support IPv6 addresses in the IpAddress condition of a policy and would block every viewer
+    Ipv6NotSupportedByCloudfront(String),
+    /// The signed URL is longer than the configured limit
+    UrlTooLong {
+        /// The length of the signed URL in bytes
+        len: usize,
+        /// The configured limit in bytes
+        limit: usize,
+    },
+    /// CloudFront only accepts 2048-bit RSA keys, see [`keys::CLOUDFRONT_KEY_BITS`]
+    UnsupportedKeySize(u32),
+    /// A signed cookie is larger than browsers accept
+    CookieTooLarge {
+        /// The name of the cookie
+        name: String,
+        /// The length of the name and value of the cookie in bytes
+        len: usize,
+        /// The limit in bytes
+        limit: usize,
+    },
+    /// The domain or path of a cookie contains a character that would end the attribute in the `Set-Cookie` header, eg. `;`
+    InvalidCookieAttribute(String),
+    /// A placeholder of a policy template was not given a value
+    UnfilledPlaceholder(String),
+    /// A value was given for a placeholder that does not appear in the policy template
+    UnknownPlaceholder(String),
+    /// The TTL could not be parsed. It must be a number of seconds or a duration such as `1h30m`
+    InvalidTtl(String),
+    /// A URL was signed from a template before a signer was registered
+    NoSignerRegistered,
+    /// A signer was registered for templates after one had already been registered
+    SignerAlreadyRegistered,
+    /// The resource is outside the allowed prefixes or inside a denied prefix of the signer
+    ResourceNotAllowed(String),
+    /// The caller was issued more grants than its rate limit allows
+    RateLimited(String),
+    /// The access token is malformed, has an invalid signature or is not valid at this time. The message describes what is wrong with it
+    InvalidToken(String),
+    /// The key could not be reloaded for longer than the grace period of a [`ReloadingSigner`]. The message is the latest failure
+    KeyRefreshFailed(String),
+    /// A [`post_process::UrlPostProcessor`] could not transform a signed URL. The message describes why
+    PostProcessFailed(String),
+    /// The nonce of a single use URL was already used or never issued, see [`single_use`]
+    UrlAlreadyUsed(String),
+    /// The key pair ID can not be valid, eg. because it contains lowercase letters. The message describes the problem
+    InvalidKeyPairId(String),
     /// Blanket error for all errors from OpenSSL that should not occur, but can due to it being written in unsafe C.
     Unknown,
 }
 
+impl Error {
+    /// Returns the HTTP status code a web service should respond with when signing fails with this error
+    ///
+    /// Errors caused by the input of a request, such as an invalid policy or TTL, map to 400 and
+    /// resources the signer refuses to sign map to 403. A key that could not be reloaded within
+    /// its grace period maps to 503, a failing post-processor to 502 and a reused single use URL
+    /// to 410. Everything else is a problem with the configuration or environment of the service
+    /// and maps to 500.
+    ///
+    /// # Example
+    /// ```
+    /// use cloudfront_policy_signer::Error;
+    ///
+    /// assert_eq!(Error::InvalidTtl("soon".to_string()).http_status(), 400);
+    /// assert_eq!(Error::CouldNotSign.http_status(), 500);
+    /// ```
+    pub fn http_status(&self) -> u16 {
+        match self {
+            Error::InvalidSignedUrl(_)
+            | Error::InvalidPolicy(_)
+            | Error::Ipv6NotSupportedByCloudfront(_)
+            | Error::UrlTooLong { .. }
+            | Error::CookieTooLarge { .. }
+            | Error::InvalidCookieAttribute(_)
+            | Error::UnfilledPlaceholder(_)
+            | Error::UnknownPlaceholder(_)
+            | Error::InvalidTtl(_)
+            | Error::InvalidToken(_) => 400,
+            Error::ResourceNotAllowed(_) => 403,
+            Error::RateLimited(_) => 429,
+            Error::UrlAlreadyUsed(_) => 410,
+            Error::PostProcessFailed(_) => 502,
+            Error::KeyRefreshFailed(_) => 503,
+            Error::IOError(_)
+            | Error::PrivateKeyParseError
+            | Error::UnsupportedPrivateKeyFormat(_)
+            | Error::PrivateKeyConvertError
+            | Error::CouldNotSign
+            | Error::PublicKeyParseError
+            | Error::InvalidConfig(_)
+            | Error::ProfileNotFound(_)
+            | Error::NoSignerRegistered
+            | Error::SignerAlreadyRegistered
+            | Error::UnsupportedKeySize(_)
+            | Error::InvalidKeyPairId(_)
+            | Error::Unknown => 500,
+        }
+    }
+}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -55,12 +354,124 @@ impl fmt::Display for Error {
             Error::PrivateKeyParseError => {
                 write!(f, "The private key was in an unsupported format or somehow malformed. It only accepts keys in PEM-encoded PKCS#1")
             }
+            Error::UnsupportedPrivateKeyFormat(diagnosis) => {
+                write!(f, "The private key file contains {}", diagnosis)
+            }
             Error::PrivateKeyConvertError => {
                 write!(f, "The key could not be converted from a openssl::rsa::Rsa<openssl::pkey::Private> to a PKey<Private>. Refer to log output")
             }
             Error::CouldNotSign => {
                 write!(f, "The policy could not be signed. Refer log output")
             }
+            Error::PublicKeyParseError => {
+                write!(f, "The public key was in an unsupported format or somehow malformed. It only accepts PEM-encoded RSA public keys")
+            }
+            Error::InvalidSignedUrl(message) => {
+                write!(f, "Invalid signed URL: {}", message)
+            }
+            Error::InvalidPolicy(message) => {
+                write!(f, "Invalid policy: {}", message)
+            }
+            Error::InvalidConfig(message) => {
+                write!(f, "Invalid configuration: {}", message)
+            }
+            Error::ProfileNotFound(name) => {
+                write!(
+                    f,
+                    "The profile '{}' does not exist in the configuration file",
+                    name
+                )
+            }
+            Error::Ipv6NotSupportedByCloudfront(ip_address) => {
+                write!(f, "CloudFront does not support the IPv6 address {} in the IpAddress condition of a policy. Omit the condition for IPv6 viewers instead", ip_address)
+            }
+            Error::UrlTooLong { len, limit } => {
+                write!(f, "The signed URL is {} bytes long which exceeds the limit of {} bytes. Use a narrower policy or signed cookies instead", len, limit)
+            }
+            Error::CookieTooLarge { name, len, limit } => {
+                write!(f, "The cookie {} is {} bytes long which exceeds the limit of {} bytes browsers accept. Use a policy with fewer statements or conditions", name, len, limit)
+            }
+            Error::UnsupportedKeySize(bits) => {
+                write!(
+                    f,
+                    "CloudFront does not accept {}-bit keys, only {}-bit RSA keys",
+                    bits,
+                    keys::CLOUDFRONT_KEY_BITS
+                )
+            }
+            Error::InvalidCookieAttribute(reason) => {
+                write!(f, "Invalid cookie attribute: {}", reason)
+            }
+            Error::UnfilledPlaceholder(name) => {
+                write!(
+                    f,
+                    "The placeholder {{{}}} of the policy template was not given a value",
+                    name
+                )
+            }
+            Error::UnknownPlaceholder(name) => {
+                write!(
+                    f,
+                    "The placeholder {{{}}} does not appear in the policy template",
+                    name
+                )
+            }
+            Error::InvalidTtl(ttl) => {
+                write!(
+                    f,
+                    "The TTL '{}' is invalid. Use a number of seconds or a duration such as 1h30m",
+                    ttl
+                )
+            }
+            Error::NoSignerRegistered => {
+                write!(
+                    f,
+                    "No signer has been registered for signing URLs from templates"
+                )
+            }
+            Error::SignerAlreadyRegistered => {
+                write!(
+                    f,
+                    "A signer has already been registered for signing URLs from templates"
+                )
+            }
+            Error::ResourceNotAllowed(resource) => {
+                write!(
+                    f,
+                    "The signer is not allowed to sign the resource '{}'",
+                    resource
+                )
+            }
+            Error::RateLimited(caller) => {
+                write!(
+                    f,
+                    "The caller '{}' exceeded its rate limit for signing",
+                    caller
+                )
+            }
+            Error::InvalidToken(reason) => {
+                write!(f, "The access token is invalid: {}", reason)
+            }
+            Error::KeyRefreshFailed(reason) => {
+                write!(
+                    f,
+                    "The private key could not be reloaded within the grace period: {}",
+                    reason
+                )
+            }
+            Error::PostProcessFailed(reason) => {
+                write!(f, "The signed URL could not be post-processed: {}", reason)
+            }
+            Error::UrlAlreadyUsed(nonce) => {
+                write!(
+                    f,
+                    "The single use URL with nonce {} was already used",
+                    nonce
+                )
+            }
+            Error::InvalidKeyPairId(reason) => {
+                write!(f, "Invalid key pair ID: {}", reason)
+            }
             Error::Unknown => {
                 write!(f, "Unkown error occurred")
             }
@@ -75,8 +486,8 @@ impl fmt::Display for Error {
 /// * `expiry` - The time the resource link should expire at
 ///
 ///
-fn generate_canned_policy(resource: &str, expiry: u64) -> Vec<u8> {
-    format!("{{\"Statement\":[{{\"Resource\":\"{}\",\"Condition\":{{\"DateLessThan\":{{\"AWS:EpochTime\":{}}}}}}}]}}", resource, expiry).into_bytes()
+pub(crate) fn generate_canned_policy(resource: &str, expiry: u64) -> Vec<u8> {
+    Policy::canned(resource, expiry).to_json().into_bytes()
 }
 
 /// Reads the contents of a file into memory and returns it as a vector of bytes
@@ -89,8 +500,9 @@ fn generate_canned_policy(resource: &str, expiry: u64) -> Vec<u8> {
 /// See the [CloudFront Documentation](https://docs.aws.amazon.com/AmazonCloudFront/latest/DeveloperGuide/private-content-trusted-signers.html#private-content-creating-cloudfront-key-pairs) about creating these keypairs
 ///
 ///
-fn read_rsa_private_key(file: &str) -> Result<Vec<u8>, Error> {
-    fs::read(&file).map_err(|e| {
+#[cfg(feature = "sign")]
+fn read_rsa_private_key(file: &Path) -> Result<Vec<u8>, Error> {
+    fs::read(file).map_err(|e| {
         error!("Could not read private key from file due to {}", e);
         Error::IOError(e)
     })
@@ -101,11 +513,16 @@ fn read_rsa_private_key(file: &str) -> Result<Vec<u8>, Error> {
 /// # Arguments
 /// * `key` - An array of bytes containing a RSA private key part
 ///
+#[cfg(feature = "sign")]
 fn parse_rsa_private_key(key: &[u8]) -> Result<PKey<Private>, Error> {
-    rsa::Rsa::private_key_from_pem(&key)
+    rsa::Rsa::private_key_from_pem(key)
         .map_err(|e| {
             error!("Could not parse RSA private key due to {}", e);
-            Error::PrivateKeyParseError
+
+            match key_format::diagnose(key) {
+                Some(diagnosis) => Error::UnsupportedPrivateKeyFormat(diagnosis),
+                None => Error::PrivateKeyParseError,
+            }
         })
         .and_then(|private_key| {
             PKey::from_rsa(private_key).map_err(|e| {
@@ -115,22 +532,92 @@ fn parse_rsa_private_key(key: &[u8]) -> Result<PKey<Private>, Error> {
         })
 }
 
-/// Signs the canned policy and returns it as a vector of bytes
+/// Signs the policy and returns the signature as a vector of bytes
 ///
 /// # Arguments
 /// * `policy` - An array of bytes containing the properly formatted policy
 /// * `private_key` - The representation of the RSA private key part
 ///
 ///
-fn sign_canned_policy(policy: &[u8], private_key: &PKey<Private>) -> Result<Vec<u8>, Error> {
-    Signer::new(MessageDigest::sha1(), &private_key)
+#[cfg(feature = "sign")]
+fn sign_policy_document(policy: &[u8], private_key: &PKey<Private>) -> Result<Vec<u8>, Error> {
+    sign_with_digest(policy, private_key, MessageDigest::sha1())
+}
+
+/// The longest signature that is created without allocating, matching RSA keys of 4096 bits
+#[cfg(feature = "sign")]
+const MAX_STACK_SIGNATURE_LEN: usize = 512;
+
+/// Signs the canned policy for the resource into `out` and returns the length of the signature
+///
+/// The policy document is fed to the signer in parts, so neither the document nor the signature
+/// is allocated. `out` must hold at least `private_key.size()` bytes.
+///
+/// # Arguments
+/// * `resource` - The protected resource eg. https://example.cloudfront.net/flowerpot.png
+/// * `expiry` - Absolute time that the policy expires, given in the form of a unix timestamp in UTC
+/// * `private_key` - The representation of the RSA private key part
+/// * `out` - The buffer the signature is written to
+#[cfg(feature = "sign")]
+fn sign_canned_policy_into(
+    resource: &str,
+    expiry: u64,
+    private_key: &PKey<Private>,
+    out: &mut [u8],
+) -> Result<usize, Error> {
+    let mut signer = Signer::new(MessageDigest::sha1(), private_key).map_err(|e| {
+        error!("Could not create signer due to {}", e);
+        Error::Unknown
+    })?;
+    let mut result = Ok(());
+    let mut update = |part: &[u8]| {
+        if result.is_ok() {
+            result = signer.update(part);
+        }
+    };
+    let mut digits = [0u8; 20];
+    let mut remaining = &mut digits[..];
+    // Formatting a u64 into 20 bytes cannot fail
+    let _ = write!(remaining, "{}", expiry);
+    let digits_len = 20 - remaining.len();
+
+    update(b"{\"Statement\":[{\"Resource\":");
+    json::write_string_with(resource, |part| update(part.as_bytes()));
+    update(b",\"Condition\":{\"DateLessThan\":{\"AWS:EpochTime\":");
+    update(&digits[..digits_len]);
+    update(canned_template::SUFFIX.as_bytes());
+
+    result.map_err(|e| {
+        error!("Could not update signer due to {}", e);
+        Error::Unknown
+    })?;
+
+    signer.sign(out).map_err(|e| {
+        error!("Could not sign due to {}", e);
+        Error::CouldNotSign
+    })
+}
+
+/// Signs the data with RSA PKCS#1 v1.5 and the digest and returns the signature as a vector of bytes
+///
+/// # Arguments
+/// * `data` - The data to sign
+/// * `private_key` - The representation of the RSA private key part
+/// * `digest` - The digest, SHA-1 for CloudFront policies
+#[cfg(feature = "sign")]
+fn sign_with_digest(
+    data: &[u8],
+    private_key: &PKey<Private>,
+    digest: MessageDigest,
+) -> Result<Vec<u8>, Error> {
+    Signer::new(digest, private_key)
         .map_err(|e| {
             error!("Could not create signer due to {}", e);
             Error::Unknown
         })
         .and_then(|mut signer| {
             signer
-                .update(&policy)
+                .update(data)
                 .map_err(|e| {
                     error!("Could not update signer due to {}", e);
                     Error::Unknown
@@ -144,19 +631,6 @@ fn sign_canned_policy(policy: &[u8], private_key: &PKey<Private>) -> Result<Vec<
         })
 }
 
-/// Base64 encode an array of data and use that to create an URL safe string
-///
-/// # Arguments
-/// * `bytes` - An array of bytes to be encoded
-///
-///
-fn encode_signature_url_safe(bytes: &[u8]) -> String {
-    encode_block(&bytes)
-        .replace("+", "-")
-        .replace("=", "_")
-        .replace("/", "~")
-}
-
 /// Signs a canned policy with the specified path and expiration date and returns it in an URL safe format appropriate for AWS.
 ///
 ///
@@ -170,83 +644,1740 @@ fn encode_signature_url_safe(bytes: &[u8]) -> String {
 ///
 /// # Example
 /// ```
-/// use cloudfront_url_signer;
-///
 ///let resource = "https://example.cloudfront.net/flowerpot.png";
 ///let expiry = 1579532331;
 ///let certificate_location = "examples/key.pem";
 ///let key_pair_id = "APKAIEXAMPLE";
-///let signature = cloudfront_url_signer::create_canned_policy_signature(resource, expiry, certificate_location).unwrap();
+///let signature = cloudfront_policy_signer::create_canned_policy_signature(resource, expiry, certificate_location).unwrap();
 ///
 ///println!("Signed URL is {}", format!("{}?Expires={}&Signature={}&Key-Pair-Id={}", resource, expiry, signature, key_pair_id));
 /// ```
 ///
-pub fn create_canned_policy_signature(
-    resource: &str,
+#[cfg(feature = "sign")]
+pub fn create_canned_policy_signature<R: AsRef<str>, P: AsRef<Path>>(
+    resource: R,
     expiry: u64,
-    private_key_location: &str,
+    private_key_location: P,
 ) -> Result<String, Error> {
-    let key = read_file_to_private_key(private_key_location)?;
-    let signed_policy = sign_canned_policy(&generate_canned_policy(resource, expiry), &key)?;
+    let key = read_file_to_private_key(private_key_location.as_ref())?;
+    let signed_policy =
+        sign_policy_document(&generate_canned_policy(resource.as_ref(), expiry), &key)?;
+
+    Ok(base64::encode(&signed_policy))
+}
+
+/// Returns the URL if it is not longer than the limit
+///
+/// # Arguments
+/// * `url` - The signed URL
+/// * `limit` - The maximum length in bytes
+#[cfg(feature = "sign")]
+pub(crate) fn check_url_length(url: String, limit: usize) -> Result<String, Error> {
+    if url.len() > limit {
+        return Err(Error::UrlTooLong {
+            len: url.len(),
+            limit,
+        });
+    }
+
+    Ok(url)
+}
+
+/// Appends the parameters of a canned policy to the resource and checks the length of the URL
+///
+/// # Arguments
+/// * `resource` - The protected resource eg. https://example.cloudfront.net/flowerpot.png
+/// * `expiry` - Absolute time that the URL expires, given in the form of a unix timestamp in UTC
+/// * `signature` - The raw signature of the canned policy
+/// * `key_pair_id` - The key pair ID from AWS CloudFront
+/// * `limit` - The maximum length of the URL in bytes
+#[cfg(feature = "sign")]
+pub(crate) fn canned_url(
+    resource: &str,
+    expiry: u64,
+    signature: &[u8],
+    key_pair_id: &str,
+    limit: usize,
+) -> Result<SignedUrl, Error> {
+    let separator = if resource.contains('?') { '&' } else { '?' };
+    let url = format!(
+        "{}{}Expires={}&Signature={}&Key-Pair-Id={}",
+        resource,
+        separator,
+        expiry,
+        base64::encode(signature),
+        key_pair_id
+    );
+
+    Ok(SignedUrl::new(
+        check_url_length(url, limit)?,
+        signed_url::Grant {
+            expires: expiry,
+            not_before: None,
+            key_pair_id: key_pair_id.to_string(),
+            kind: PolicyKind::Canned,
+        },
+    ))
+}
 
-    Ok(encode_signature_url_safe(&signed_policy))
+/// Whether a resource is on the same scheme and host as a base URL, ignoring case
+///
+/// # Arguments
+/// * `base_url` - The URL of the distribution eg. https://example.cloudfront.net
+/// * `resource` - The protected resource eg. https://example.cloudfront.net/flowerpot.png
+#[cfg(feature = "sign")]
+fn same_host(base_url: &str, resource: &str) -> bool {
+    match (split_origin(base_url), split_origin(resource)) {
+        (Some((scheme, host, _)), Some((other_scheme, other_host, _))) => {
+            scheme.eq_ignore_ascii_case(other_scheme) && host.eq_ignore_ascii_case(other_host)
+        }
+        _ => false,
+    }
+}
+
+/// Whether everything a resource matches starts with the prefix, comparing scheme and host ignoring case
+///
+/// A resource with wildcards is covered if the part before its first `*` or `?` starts with the prefix.
+///
+/// # Arguments
+/// * `prefix` - An allowed prefix eg. https://example.cloudfront.net/public/
+/// * `resource` - The resource of a policy statement, possibly with wildcards
+#[cfg(feature = "sign")]
+fn prefix_covers(prefix: &str, resource: &str) -> bool {
+    let (prefix, resource) = (lowercase_origin(prefix), lowercase_origin(resource));
+    let literal = &resource[..resource.find(['*', '?']).unwrap_or(resource.len())];
+
+    literal.starts_with(prefix.as_ref())
+}
+
+/// Whether anything a resource matches starts with the prefix, comparing scheme and host ignoring case
+///
+/// # Arguments
+/// * `prefix` - A denied prefix eg. https://example.cloudfront.net/internal/
+/// * `resource` - The resource of a policy statement, possibly with wildcards
+#[cfg(feature = "sign")]
+fn prefix_overlaps(prefix: &str, resource: &str) -> bool {
+    let (prefix, resource) = (lowercase_origin(prefix), lowercase_origin(resource));
+
+    // A match starting with the prefix consumes the prefix with the start of the pattern, possibly
+    // ending inside a `*` which then also matches the end of the prefix
+    (0..=resource.len())
+        .filter(|&end| resource.is_char_boundary(end))
+        .any(|end| resource::wildcard_matches(&resource[..end], &prefix))
+}
+
+/// Whether the path of a resource has a `.` or `..` segment, also percent-encoded, which could leave a prefix
+///
+/// # Arguments
+/// * `resource` - The resource of a policy statement eg. https://example.cloudfront.net/a/../b
+#[cfg(feature = "sign")]
+fn has_dot_segment(resource: &str) -> bool {
+    let path = split_origin(resource).map_or(resource, |(_, _, rest)| rest);
+
+    path.split(['/', '?']).any(|segment| {
+        let segment = segment.to_ascii_lowercase().replace("%2e", ".");
+
+        segment == "." || segment == ".."
+    })
+}
+
+/// Returns the URL with its scheme and host in lowercase, as they are case insensitive
+#[cfg(feature = "sign")]
+fn lowercase_origin(url: &str) -> Cow<'_, str> {
+    match split_origin(url) {
+        Some((scheme, host, rest))
+            if scheme
+                .bytes()
+                .chain(host.bytes())
+                .any(|b| b.is_ascii_uppercase()) =>
+        {
+            Cow::Owned(format!(
+                "{}://{}{}",
+                scheme.to_ascii_lowercase(),
+                host.to_ascii_lowercase(),
+                rest
+            ))
+        }
+        _ => Cow::Borrowed(url),
+    }
+}
+
+/// Splits a URL into its scheme, its host and the rest, `None` if it has no scheme
+///
+/// # Arguments
+/// * `url` - The URL eg. https://example.cloudfront.net/flowerpot.png
+#[cfg(feature = "sign")]
+fn split_origin(url: &str) -> Option<(&str, &str, &str)> {
+    let (scheme, rest) = url.split_once("://")?;
+    let end = rest.find(['/', '?']).unwrap_or(rest.len());
+
+    Some((scheme, &rest[..end], &rest[end..]))
+}
+
+/// Parses a policy document produced elsewhere, refusing duplicate keys
+///
+/// A duplicate `Resource` would otherwise be checked against the prefixes by its first
+/// occurrence while CloudFront may use the other.
+///
+/// # Arguments
+/// * `document` - The policy document as JSON
+#[cfg(feature = "sign")]
+fn parse_policy_document(document: &[u8]) -> Result<json::Value, Error> {
+    let document = json::parse(document).map_err(Error::InvalidPolicy)?;
+
+    if let Some(key) = document.duplicate_key() {
+        error!("The policy document has the key {} twice", key);
+
+        return Err(Error::InvalidPolicy(format!(
+            "the key {} appears twice in an object",
+            key
+        )));
+    }
+
+    Ok(document)
+}
+
+/// Returns the `Resource` of every statement of a policy document, `None` for statements without one
+///
+/// # Arguments
+/// * `document` - The parsed policy document
+#[cfg(feature = "sign")]
+fn raw_policy_resources(document: &json::Value) -> Vec<Option<&str>> {
+    document
+        .get("Statement")
+        .and_then(json::Value::as_array)
+        .unwrap_or_default()
+        .iter()
+        .map(|statement| statement.get("Resource").and_then(json::Value::as_str))
+        .collect()
+}
+
+/// Returns the earliest `DateLessThan` and latest `DateGreaterThan` of a policy document
+///
+/// # Arguments
+/// * `document` - The parsed policy document
+#[cfg(feature = "sign")]
+fn raw_policy_validity(document: &json::Value) -> Result<(u64, Option<u64>), Error> {
+    let mut expires: Option<u64> = None;
+    let mut not_before: Option<u64> = None;
+    let epoch_time = |condition: &json::Value, name: &str| {
+        condition
+            .get(name)
+            .and_then(|date| date.get("AWS:EpochTime"))
+            .and_then(json::Value::as_u64)
+    };
+
+    for statement in document
+        .get("Statement")
+        .and_then(json::Value::as_array)
+        .unwrap_or_default()
+    {
+        let condition = statement.get("Condition");
+        let date_less_than = condition
+            .and_then(|condition| epoch_time(condition, "DateLessThan"))
+            .ok_or_else(|| {
+                Error::InvalidPolicy("a statement has no valid DateLessThan".to_string())
+            })?;
+
+        expires = Some(expires.map_or(date_less_than, |e| e.min(date_less_than)));
+
+        if let Some(date_greater_than) =
+            condition.and_then(|condition| epoch_time(condition, "DateGreaterThan"))
+        {
+            not_before = Some(not_before.map_or(date_greater_than, |n| n.max(date_greater_than)));
+        }
+    }
+
+    let expires =
+        expires.ok_or_else(|| Error::InvalidPolicy("the policy has no statements".to_string()))?;
+
+    Ok((expires, not_before))
 }
 
 /// Reads a .pem file and tries to transform it into a PKey<Private>
 ///
 /// # Arguments
 /// * `private_key_location` - Path where the private key file can be found
-fn read_file_to_private_key(private_key_location: &str) -> Result<PKey<Private>, Error> {
+#[cfg(feature = "sign")]
+fn read_file_to_private_key(private_key_location: &Path) -> Result<PKey<Private>, Error> {
     let key = read_rsa_private_key(private_key_location)?;
 
     parse_rsa_private_key(&key)
 }
 
-/// Struct to create a URL from CloudFront with a cached private key
-/// Unliked method `create_canned_policy_signature`, calling `create_canned_policy_signature_url`
-/// in an instance of this struct will not read the private key file every time it is invoked.
-pub struct CloudFrontCannedPolicySigner {
+/// Signs URLs, cookies and custom policies for CloudFront with a cached private key
+///
+/// The key is read once when the signer is created, unlike [`create_canned_policy_signature`]
+/// which reads the key file on every call. Use [`CloudFrontSigner::sign_url`] for URLs with a
+/// canned policy, [`CloudFrontSigner::create_canned_policy_signed_cookies`] for cookies and
+/// [`CloudFrontSigner::sign_policy`] for custom policies, which can be delivered either way.
+///
+/// # Example
+/// ```
+/// use cloudfront_policy_signer::{CloudFrontSigner, Policy};
+///
+/// let signer = CloudFrontSigner::new("examples/key.pem", "APKAIEXAMPLE").unwrap();
+/// let url = signer
+///     .sign_url("https://example.cloudfront.net/flowerpot.png", 1700000000)
+///     .unwrap();
+/// let policy = signer
+///     .sign_policy(&Policy::canned("https://example.cloudfront.net/videos/*", 1700000000))
+///     .unwrap();
+///
+/// assert!(url.as_str().contains("&Key-Pair-Id=APKAIEXAMPLE"));
+/// assert_eq!(policy.key_pair_id(), "APKAIEXAMPLE");
+/// ```
+///
+/// The `Debug` output redacts the private key and only shows its size and fingerprint.
+#[cfg(feature = "sign")]
+pub struct CloudFrontSigner {
     private_key: PKey<Private>,
-    key_pair_id: String
+    key_pair_id: String,
+    max_url_length: usize,
+    allowed_prefixes: Vec<String>,
+    denied_prefixes: Vec<String>,
+    sampler: Option<sampling::Sampler>,
+    rate_limiter: Option<rate_limit::RateLimiter>,
+    post_processors: Vec<Arc<dyn post_process::UrlPostProcessor>>,
+    ttl_policy: Option<TtlPolicy>,
+    default_ttl: Option<Duration>,
+    base_url: Option<String>,
+    aliases: Vec<(String, String)>,
+    encoding_profile: EncodingProfile,
+    key_expiry: keys::KeyExpiry,
+    key_provider: KeyProvider,
+    key_modified_at: Option<SystemTime>,
+    counters: status::Counters,
+    fixed_time: Option<SystemTime>,
 }
 
-impl CloudFrontCannedPolicySigner {
-    /// Constructs a new instance of `CloudFrontCannedPolicySigner`
+#[cfg(feature = "sign")]
+impl fmt::Debug for CloudFrontSigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CloudFrontSigner")
+            .field("private_key", &keys::describe_redacted(&self.private_key))
+            .field("key_pair_id", &self.key_pair_id)
+            .field("max_url_length", &self.max_url_length)
+            .field("allowed_prefixes", &self.allowed_prefixes)
+            .field("denied_prefixes", &self.denied_prefixes)
+            .field("sampling", &self.sampler.is_some())
+            .field("rate_limit", &self.rate_limiter.is_some())
+            .field("post_processors", &self.post_processors.len())
+            .field("ttl_policy", &self.ttl_policy)
+            .field("default_ttl", &self.default_ttl)
+            .field("base_url", &self.base_url)
+            .field("aliases", &self.aliases)
+            .field("encoding_profile", &self.encoding_profile)
+            .field("key_expiries", &self.key_expiry.expiries)
+            .field("key_provider", &self.key_provider)
+            .field("counters", &self.counters.snapshot())
+            .field("fixed_time", &self.fixed_time)
+            .finish()
+    }
+}
+
+/// The default limit for the length of signed URLs, matching the maximum request line CloudFront accepts
+pub const DEFAULT_MAX_URL_LENGTH: usize = 8192;
+
+/// Previous name of [`CloudFrontSigner`], kept for compatibility
+#[cfg(feature = "sign")]
+pub type CloudFrontCannedPolicySigner = CloudFrontSigner;
+
+#[cfg(feature = "sign")]
+impl CloudFrontSigner {
+    /// Constructs a new instance of `CloudFrontSigner`
     /// # Arguments
     /// * `private_key_location` - Path where the private key file can be found
-    /// * `key_pair_id` - The key pair ID from AWS CloudFront
-    pub fn new<T: ToString>(private_key_location: &str, key_pair_id: T) -> Result<CloudFrontCannedPolicySigner, Error> {
+    /// * `key_pair_id` - The key pair ID from AWS CloudFront, see [`keys::normalize_key_pair_id`]
+    pub fn new<P: AsRef<Path>, T: ToString>(
+        private_key_location: P,
+        key_pair_id: T,
+    ) -> Result<CloudFrontSigner, Error> {
+        let private_key_location = private_key_location.as_ref();
+        let key = read_rsa_private_key(private_key_location)?;
+
         Ok(Self {
-            private_key: read_file_to_private_key(private_key_location)?,
-            key_pair_id: key_pair_id.to_string()
+            private_key: parse_rsa_private_key(&key)?,
+            key_pair_id: keys::normalize_key_pair_id(&key_pair_id.to_string())?,
+            max_url_length: DEFAULT_MAX_URL_LENGTH,
+            allowed_prefixes: Vec::new(),
+            denied_prefixes: Vec::new(),
+            sampler: None,
+            rate_limiter: None,
+            post_processors: Vec::new(),
+            ttl_policy: None,
+            default_ttl: None,
+            base_url: None,
+            aliases: Vec::new(),
+            encoding_profile: EncodingProfile::CUSTOM_ORIGIN,
+            key_expiry: keys::KeyExpiry::read(&key, Some(private_key_location)),
+            key_provider: KeyProvider::File(private_key_location.to_path_buf()),
+            key_modified_at: fs::metadata(private_key_location)
+                .and_then(|metadata| metadata.modified())
+                .ok(),
+            counters: status::Counters::new(),
+            fixed_time: None,
         })
     }
 
-    /// Constructs a new instance of `CloudFrontCannedPolicySigner`
+    /// Constructs a new instance of `CloudFrontSigner`
     /// # Arguments
     /// * `private_key` - In memory RSA private key
-    /// * `key_pair_id` - The key pair ID from AWS CloudFront
-    pub fn new_in_memory<T: ToString>(private_key: &[u8], key_pair_id: T) -> Result<CloudFrontCannedPolicySigner, Error> {
+    /// * `key_pair_id` - The key pair ID from AWS CloudFront, see [`keys::normalize_key_pair_id`]
+    pub fn new_in_memory<K: AsRef<[u8]>, T: ToString>(
+        private_key: K,
+        key_pair_id: T,
+    ) -> Result<CloudFrontSigner, Error> {
         Ok(Self {
-            private_key: parse_rsa_private_key(private_key)?,
-            key_pair_id: key_pair_id.to_string()
+            private_key: parse_rsa_private_key(private_key.as_ref())?,
+            key_pair_id: keys::normalize_key_pair_id(&key_pair_id.to_string())?,
+            max_url_length: DEFAULT_MAX_URL_LENGTH,
+            allowed_prefixes: Vec::new(),
+            denied_prefixes: Vec::new(),
+            sampler: None,
+            rate_limiter: None,
+            post_processors: Vec::new(),
+            ttl_policy: None,
+            default_ttl: None,
+            base_url: None,
+            aliases: Vec::new(),
+            encoding_profile: EncodingProfile::CUSTOM_ORIGIN,
+            key_expiry: keys::KeyExpiry::read(private_key.as_ref(), None),
+            key_provider: KeyProvider::Memory,
+            key_modified_at: None,
+            counters: status::Counters::new(),
+            fixed_time: None,
         })
     }
 
+    /// Constructs a new instance of `CloudFrontSigner` from a profile in the configuration file
+    ///
+    /// See the [`config`] module for the format of the file and the environment variable overrides.
+    /// The TTL and base URL of the profile become the default TTL and base URL of the signer.
+    /// # Arguments
+    /// * `name` - The name of the profile eg. prod
+    pub fn from_profile(name: &str) -> Result<CloudFrontSigner, Error> {
+        let profile = config::Profile::load(name)?;
+        let mut signer = CloudFrontSigner::new(profile.key, profile.key_pair_id)?;
+
+        if let Some(ttl) = profile.ttl {
+            signer = signer.default_ttl(ttl);
+        }
+
+        if let Some(base_url) = profile.base_url {
+            signer = signer.base_url(base_url);
+        }
+
+        Ok(signer)
+    }
+
+    /// Returns the key pair ID the signer signs with
+    pub fn key_pair_id(&self) -> &str {
+        &self.key_pair_id
+    }
+
+    /// Returns the SHA-256 fingerprint of the public key belonging to the private key, see [`keys::fingerprint_sha256`]
+    pub fn fingerprint_sha256(&self) -> Result<String, Error> {
+        keys::fingerprint_sha256(&self.private_key)
+    }
+
+    /// Returns the MD5 fingerprint of the public key belonging to the private key, see [`keys::fingerprint_md5`]
+    pub fn fingerprint_md5(&self) -> Result<String, Error> {
+        keys::fingerprint_md5(&self.private_key)
+    }
+
+    /// Returns the PEM-encoded public key belonging to the private key, as accepted by the CloudFront console
+    pub fn public_key_pem(&self) -> Result<String, Error> {
+        self.private_key
+            .public_key_to_pem()
+            .map(|pem| String::from_utf8_lossy(&pem).into_owned())
+            .map_err(|e| {
+                error!("Could not encode public key due to {}", e);
+                Error::Unknown
+            })
+    }
+
+    /// Returns the configuration for uploading the public key to CloudFront, see [`keys::PublicKeyConfig`]
+    ///
+    /// # Arguments
+    /// * `name` - The name of the public key in CloudFront eg. media-signing-2024
+    pub fn public_key_config<N: Into<String>>(
+        &self,
+        name: N,
+    ) -> Result<keys::PublicKeyConfig, Error> {
+        keys::PublicKeyConfig::for_key(name, &self.private_key)
+    }
+
+    /// Sets the maximum length of signed URLs, longer URLs fail with [`Error::UrlTooLong`]
+    ///
+    /// Defaults to [`DEFAULT_MAX_URL_LENGTH`]. Browsers and proxies in front of the viewer may impose lower limits.
+    /// # Arguments
+    /// * `limit` - The maximum length in bytes
+    pub fn max_url_length(mut self, limit: usize) -> CloudFrontSigner {
+        self.max_url_length = limit;
+        self
+    }
+
+    /// Restricts signing to resources starting with one of the prefixes, failing with [`Error::ResourceNotAllowed`] otherwise
+    ///
+    /// Without allowed prefixes every resource may be signed. Prefixes should end with a `/`,
+    /// otherwise eg. https://example.cloudfront.net also allows https://example.cloudfront.net.evil.com.
+    /// The scheme and host are compared ignoring case, and a resource with wildcards is only
+    /// allowed if everything it matches starts with the prefix. Resources with `.` or `..` path
+    /// segments, also percent-encoded, are refused while prefixes are set, as they could leave it.
+    /// # Arguments
+    /// * `prefixes` - The allowed prefixes eg. https://example.cloudfront.net/public/
+    ///
+    /// # Example
+    /// ```
+    /// use cloudfront_policy_signer::{CloudFrontSigner, Policy};
+    ///
+    /// let signer = CloudFrontSigner::new("examples/key.pem", "APKAIEXAMPLE")
+    ///     .unwrap()
+    ///     .allowed_prefixes(["https://example.cloudfront.net/public/"]);
+    /// let policy = |resource| Policy::builder(resource).expires_at(1700000000).build().unwrap();
+    ///
+    /// assert!(signer.sign_policy(&policy("https://example.cloudfront.net/public/*")).is_ok());
+    /// assert!(signer.sign_url("https://EXAMPLE.cloudfront.net/public/a.png", 1700000000).is_ok());
+    /// assert!(signer.sign_policy(&policy("https://example.cloudfront.net/pub*")).is_err());
+    /// assert!(signer
+    ///     .sign_url("https://example.cloudfront.net/public/../internal/x", 1700000000)
+    ///     .is_err());
+    /// assert!(signer
+    ///     .sign_url("https://example.cloudfront.net/public/%2e%2E/internal/x", 1700000000)
+    ///     .is_err());
+    /// ```
+    pub fn allowed_prefixes<I, T>(mut self, prefixes: I) -> CloudFrontSigner
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        self.allowed_prefixes
+            .extend(prefixes.into_iter().map(Into::into));
+        self
+    }
+
+    /// Refuses to sign resources starting with one of the prefixes, failing with [`Error::ResourceNotAllowed`]
+    ///
+    /// Denied prefixes take precedence over allowed prefixes. The scheme and host are compared
+    /// ignoring case, and a resource with wildcards is refused if anything it matches starts with
+    /// the prefix.
+    /// # Arguments
+    /// * `prefixes` - The denied prefixes eg. https://example.cloudfront.net/internal/
+    ///
+    /// # Example
+    /// ```
+    /// use cloudfront_policy_signer::{CloudFrontSigner, Policy};
+    ///
+    /// let signer = CloudFrontSigner::new("examples/key.pem", "APKAIEXAMPLE")
+    ///     .unwrap()
+    ///     .denied_prefixes(["https://example.cloudfront.net/internal/"]);
+    /// let policy = |resource| Policy::builder(resource).expires_at(1700000000).build().unwrap();
+    ///
+    /// assert!(signer.sign_url("https://example.cloudfront.net/a.png", 1700000000).is_ok());
+    /// assert!(signer.sign_url("https://EXAMPLE.cloudfront.net/internal/x", 1700000000).is_err());
+    /// assert!(signer.sign_policy(&policy("https://example.cloudfront.net/*")).is_err());
+    /// assert!(signer.sign_policy(&policy("https://example.cloudfront.net/int?rnal/*")).is_err());
+    /// assert!(signer.sign_policy(&policy("https://example.cloudfront.net/videos/*")).is_ok());
+    /// assert!(signer
+    ///     .sign_url("https://example.cloudfront.net/videos/../internal/x", 1700000000)
+    ///     .is_err());
+    /// assert!(signer
+    ///     .sign_url("https://example.cloudfront.net/videos/%2E%2E/internal/x", 1700000000)
+    ///     .is_err());
+    /// ```
+    pub fn denied_prefixes<I, T>(mut self, prefixes: I) -> CloudFrontSigner
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        self.denied_prefixes
+            .extend(prefixes.into_iter().map(Into::into));
+        self
+    }
+
+    /// Passes a part of the issued URLs and cookies to a sink, see the [`sampling`] module
+    ///
+    /// # Arguments
+    /// * `rate` - The part of the issued grants that is sampled between 0 and 1 eg. 0.01 for 1%
+    /// * `sink` - The sink receiving the samples
+    pub fn sampling(mut self, rate: f64, sink: Arc<dyn sampling::SampleSink>) -> CloudFrontSigner {
+        self.sampler = Some(sampling::Sampler::new(rate, sink));
+        self
+    }
+
+    /// Transforms every URL signed with a canned policy, see the [`post_process`] module
+    ///
+    /// Post-processors run in the order they were registered, each receiving the result of the
+    /// previous one. They are not applied to [`CloudFrontSigner::sign_url_into`] or to cookies.
+    ///
+    /// # Arguments
+    /// * `post_processor` - The post-processor eg. a closure registering the URL with a shortener
+    pub fn post_process(
+        mut self,
+        post_processor: Arc<dyn post_process::UrlPostProcessor>,
+    ) -> CloudFrontSigner {
+        self.post_processors.push(post_processor);
+        self
+    }
+
+    /// Sets the rules deciding the TTL of URLs signed with [`CloudFrontSigner::sign_url_auto`]
+    ///
+    /// # Arguments
+    /// * `ttl_policy` - The rules mapping path patterns to TTLs
+    pub fn ttl_policy(mut self, ttl_policy: TtlPolicy) -> CloudFrontSigner {
+        self.ttl_policy = Some(ttl_policy);
+        self
+    }
+
+    /// Sets when the signing key expires, in addition to a certificate or metadata file found when loading it
+    ///
+    /// Keys loaded from a PEM file also containing a certificate expire with the certificate, and
+    /// keys loaded from a file eg. key.pem expire at the unix timestamp or date in UTC in
+    /// key.pem.expiry if it exists.
+    ///
+    /// # Arguments
+    /// * `expires_at` - When the key expires, eg. when it is scheduled to be rotated
+    pub fn key_expires_at(mut self, expires_at: SystemTime) -> CloudFrontSigner {
+        self.key_expiry
+            .expiries
+            .push((keys::ExpirySource::Configured, expires_at));
+        self
+    }
+
+    /// Sets how long before the key expires [`CloudFrontSigner::expiry_warnings`] starts warning, 30 days by default
+    ///
+    /// # Arguments
+    /// * `period` - The warning period eg. 14 days
+    pub fn expiry_warning_period(mut self, period: Duration) -> CloudFrontSigner {
+        self.key_expiry.warning_period = period;
+        self
+    }
+
+    /// Passes the expiry warnings to a callback, eg. to raise an alert to rotate the key
+    ///
+    /// The callback is called from signing at most once a day, with every warning that applies.
+    ///
+    /// # Arguments
+    /// * `callback` - The callback receiving the warnings
+    pub fn on_expiry_warning(mut self, callback: keys::ExpiryCallback) -> CloudFrontSigner {
+        self.key_expiry.callback = Some(callback);
+        self
+    }
+
+    /// Returns a warning for every known expiry of the key within the warning period, or in the past
+    ///
+    /// # Example
+    /// ```
+    /// use cloudfront_policy_signer::CloudFrontSigner;
+    /// use std::time::{Duration, SystemTime};
+    ///
+    /// let signer = CloudFrontSigner::new("examples/key.pem", "APKAIEXAMPLE")
+    ///     .unwrap()
+    ///     .key_expires_at(SystemTime::now() + Duration::from_secs(7 * 24 * 60 * 60));
+    /// let warnings = signer.expiry_warnings();
+    ///
+    /// assert_eq!(warnings.len(), 1);
+    /// println!("{}", warnings[0]);
+    /// ```
+    pub fn expiry_warnings(&self) -> Vec<keys::ExpiryWarning> {
+        self.key_expiry.warnings(self.now())
+    }
+
+    /// Returns the key, where it came from and how signing went since the signer was created
+    ///
+    /// URLs, cookies, custom policies and access tokens are counted. Meant for `/debug` endpoints and admin
+    /// pages, see [`SignerStatus::to_json`].
+    /// # Example
+    /// ```
+    /// use cloudfront_policy_signer::CloudFrontSigner;
+    ///
+    /// let signer = CloudFrontSigner::new("examples/key.pem", "APKAIEXAMPLE").unwrap();
+    ///
+    /// signer
+    ///     .sign_url("https://example.cloudfront.net/flowerpot.png", 1700000000)
+    ///     .unwrap();
+    ///
+    /// let status = signer.status();
+    ///
+    /// assert_eq!(status.signed, 1);
+    /// assert!(status.last_error.is_none());
+    /// assert!(status.to_json().starts_with(r#"{"key_pair_id":"APKAIEXAMPLE","#));
+    /// ```
+    pub fn status(&self) -> SignerStatus {
+        let (signed, failed, last_error) = self.counters.snapshot();
+        let now = self.now();
+
+        SignerStatus {
+            key_pair_id: self.key_pair_id.clone(),
+            key_fingerprint: keys::fingerprint_sha256(&self.private_key).unwrap_or_default(),
+            key_bits: self.private_key.bits(),
+            key_provider: self.key_provider.clone(),
+            key_age: self
+                .key_modified_at
+                .map(|modified| now.duration_since(modified).unwrap_or_default()),
+            started_at: self.counters.started_at,
+            signed,
+            failed,
+            last_error,
+        }
+    }
+
+    /// Returns a signer acting on behalf of a caller, whose tag is attached to samples
+    ///
+    /// # Arguments
+    /// * `caller` - A tag identifying the caller eg. a tenant ID
+    pub fn for_caller<'a>(&'a self, caller: &'a str) -> CallerSigner<'a> {
+        CallerSigner::new(self, caller)
+    }
+
+    /// Bounds how many grants every caller of [`CloudFrontSigner::for_caller`] can be issued, failing with [`Error::RateLimited`]
+    ///
+    /// Every caller tag has its own token bucket. Signing without a caller is not limited.
+    /// # Arguments
+    /// * `per_second` - How many grants a caller can be issued per second on average
+    /// * `burst` - How many grants a caller can be issued at once after being idle
+    ///
+    /// # Example
+    /// ```
+    /// use cloudfront_policy_signer::{CloudFrontSigner, Error};
+    ///
+    /// let signer = CloudFrontSigner::new("examples/key.pem", "APKAIEXAMPLE")
+    ///     .unwrap()
+    ///     .rate_limit(1.0, 1);
+    /// let resource = "https://example.cloudfront.net/flowerpot.png";
+    ///
+    /// assert!(signer.for_caller("tenant-42").sign_url(resource, 1700000000).is_ok());
+    /// assert!(matches!(
+    ///     signer.for_caller("tenant-42").sign_url(resource, 1700000000),
+    ///     Err(Error::RateLimited(_))
+    /// ));
+    /// assert!(signer.for_caller("tenant-43").sign_url(resource, 1700000000).is_ok());
+    /// ```
+    pub fn rate_limit(mut self, per_second: f64, burst: u32) -> CloudFrontSigner {
+        self.rate_limiter = Some(rate_limit::RateLimiter::new(per_second, burst));
+        self
+    }
+
+    /// Checks the rate limit of the caller before signing
+    fn check_rate_limit(&self, caller: Option<&str>) -> Result<(), Error> {
+        match (&self.rate_limiter, caller) {
+            (Some(rate_limiter), Some(caller)) => rate_limiter.acquire(caller),
+            _ => Ok(()),
+        }
+    }
+
+    /// Records that a grant was issued
+    fn issued(
+        &self,
+        attribution: Option<Attribution>,
+        resources: &[&str],
+        expires: u64,
+        kind: PolicyKind,
+    ) {
+        if let Some(sampler) = &self.sampler {
+            sampler.issued(attribution, resources, expires, kind, self.now());
+        }
+
+        self.key_expiry.notify(self.now());
+    }
+
+    /// Uses a fixed time instead of the system clock, so snapshot tests of full URLs are stable
+    ///
+    /// Expiries computed from a TTL, eg. by [`CloudFrontSigner::grant_prefix_access`] or
+    /// [`RefreshingSignedUrl`], start at this time. RSA PKCS#1 v1.5 signatures are deterministic
+    /// and signing uses no randomness, so the output then only depends on the key and the input.
+    ///
+    /// # Arguments
+    /// * `now` - The time the signer treats as now
+    ///
+    /// # Example
+    /// ```
+    /// use cloudfront_policy_signer::CloudFrontSigner;
+    /// use std::time::{Duration, UNIX_EPOCH};
+    ///
+    /// let signer = CloudFrontSigner::new("examples/key.pem", "APKAIEXAMPLE")
+    ///     .unwrap()
+    ///     .fixed_clock(UNIX_EPOCH + Duration::from_secs(1700000000));
+    /// let cookies = signer
+    ///     .grant_prefix_access("https://example.cloudfront.net/users/42/", Duration::from_secs(3600))
+    ///     .unwrap();
+    ///
+    /// assert_eq!(cookies.expires_at(), 1700003600);
+    /// ```
+    #[cfg(feature = "testing")]
+    pub fn fixed_clock(mut self, now: SystemTime) -> CloudFrontSigner {
+        self.fixed_time = Some(now);
+        self
+    }
+
+    /// Returns the current time, or the time set with `fixed_clock`
+    pub(crate) fn now(&self) -> SystemTime {
+        self.fixed_time.unwrap_or_else(SystemTime::now)
+    }
+
+    /// Returns the unix timestamp `ttl` from now
+    ///
+    /// # Arguments
+    /// * `ttl` - How long from now the timestamp should be
+    pub(crate) fn expiry_after(&self, ttl: Duration) -> Result<u64, Error> {
+        (self.now() + ttl)
+            .duration_since(UNIX_EPOCH)
+            .map(|expiry| expiry.as_secs())
+            .map_err(|e| {
+                error!("System time is before the unix epoch: {}", e);
+                Error::Unknown
+            })
+    }
+
+    /// Checks a resource against the allowed and denied prefixes
+    ///
+    /// # Arguments
+    /// * `resource` - The resource of a policy statement, `None` if the statement has no resource and applies to everything
+    fn check_resource_allowed(&self, resource: Option<&str>) -> Result<(), Error> {
+        let allowed = match resource {
+            Some(resource) => {
+                self.base_url
+                    .as_deref()
+                    .is_none_or(|base_url| same_host(base_url, resource))
+                    && ((self.allowed_prefixes.is_empty() && self.denied_prefixes.is_empty())
+                        || !has_dot_segment(resource))
+                    && (self.allowed_prefixes.is_empty()
+                        || self
+                            .allowed_prefixes
+                            .iter()
+                            .any(|prefix| prefix_covers(prefix, resource)))
+                    && !self
+                        .denied_prefixes
+                        .iter()
+                        .any(|prefix| prefix_overlaps(prefix, resource))
+            }
+            None => {
+                self.base_url.is_none()
+                    && self.allowed_prefixes.is_empty()
+                    && self.denied_prefixes.is_empty()
+            }
+        };
+
+        if allowed {
+            Ok(())
+        } else {
+            let resource = resource.unwrap_or("*");
+
+            error!("Refused to sign the resource {}", resource);
+
+            Err(Error::ResourceNotAllowed(resource.to_string()))
+        }
+    }
+
     /// Creates a URL to CloudFront which can be used to download the object
-    pub fn create_canned_policy_signature_url(
+    ///
+    /// Use [`CloudFrontSigner::sign_url`] to also get the expiry and key pair ID of the URL.
+    pub fn create_canned_policy_signature_url<R: AsRef<str>>(
         &self,
-        resource: &str,
+        resource: R,
         expiry: u64,
     ) -> Result<String, Error> {
+        self.sign_url(resource, expiry).map(SignedUrl::into_string)
+    }
+
+    /// Creates a URL to CloudFront with a canned policy, along with what it grants access to
+    ///
+    /// # Arguments
+    /// * `resource` - The protected resource eg. https://example.cloudfront.net/flowerpot.png
+    /// * `expiry` - Absolute time that the URL expires, given in the form of a unix timestamp in UTC
+    pub fn sign_url<R: AsRef<str>>(&self, resource: R, expiry: u64) -> Result<SignedUrl, Error> {
+        self.sign_url_for(None, resource.as_ref(), expiry)
+    }
+
+    /// Sets the TTL of URLs signed with [`CloudFrontSigner::sign`] when no [`TtlPolicy`] is set
+    ///
+    /// # Arguments
+    /// * `ttl` - How long URLs are valid for eg. one hour
+    pub fn default_ttl(mut self, ttl: Duration) -> CloudFrontSigner {
+        self.default_ttl = Some(ttl);
+        self
+    }
+
+    /// Sets the URL of the distribution, so resources can be given as a path eg. /img/a.png
+    ///
+    /// Resources starting with `/` are encoded with the
+    /// [encoding profile](CloudFrontSigner::encoding_profile) of the signer and appended to the
+    /// base URL. Wildcards in them are kept. Every other resource must be on
+    /// the same scheme and host as the base URL once [aliases](CloudFrontSigner::alias) are
+    /// replaced, otherwise signing fails with
+    /// [`Error::ResourceNotAllowed`]. This keeps callers from signing for foreign hosts.
+    ///
+    /// # Arguments
+    /// * `base_url` - The URL of the distribution eg. https://example.cloudfront.net
+    /// # Example
+    /// ```
+    /// use cloudfront_policy_signer::CloudFrontSigner;
+    ///
+    /// let signer = CloudFrontSigner::new("examples/key.pem", "APKAIEXAMPLE")
+    ///     .unwrap()
+    ///     .base_url("https://example.cloudfront.net");
+    /// let signed_url = signer.sign_url("/img/a.png", 1700000000).unwrap();
+    ///
+    /// assert!(signed_url.as_str().starts_with("https://example.cloudfront.net/img/a.png?"));
+    /// assert!(signer.sign_url("https://example.org/img/a.png", 1700000000).is_err());
+    /// ```
+    pub fn base_url<T: Into<String>>(mut self, base_url: T) -> CloudFrontSigner {
+        let base_url = base_url.into();
+
+        self.base_url = Some(base_url.trim_end_matches('/').to_string());
+        self
+    }
+
+    /// Sets how paths given relative to the [base URL](CloudFrontSigner::base_url) are encoded
+    ///
+    /// Defaults to [`EncodingProfile::CUSTOM_ORIGIN`]. Absolute resources are signed as given,
+    /// use [`Resource::url_with`] to encode them.
+    ///
+    /// # Arguments
+    /// * `profile` - How the origin of the distribution expects URLs to be encoded
+    /// # Example
+    /// ```
+    /// use cloudfront_policy_signer::{CloudFrontSigner, EncodingProfile};
+    ///
+    /// let signer = CloudFrontSigner::new("examples/key.pem", "APKAIEXAMPLE")
+    ///     .unwrap()
+    ///     .base_url("https://example.cloudfront.net")
+    ///     .encoding_profile(EncodingProfile::S3_ORIGIN);
+    /// let signed_url = signer.sign_url("/c++ guide.pdf", 1700000000).unwrap();
+    ///
+    /// assert!(signed_url
+    ///     .as_str()
+    ///     .starts_with("https://example.cloudfront.net/c%2B%2B%20guide.pdf?"));
+    /// ```
+    pub fn encoding_profile(mut self, profile: EncodingProfile) -> CloudFrontSigner {
+        self.encoding_profile = profile;
+        self
+    }
+
+    /// Adds an alias of a distribution, eg. a CNAME, that is signed for as the canonical host
+    ///
+    /// Resources on the alias are rewritten to the canonical host before signing, so the policy
+    /// always names the host CloudFront checks the signature against. The hosts are compared
+    /// ignoring case.
+    ///
+    /// # Arguments
+    /// * `alias` - The alternative host eg. media.example.com
+    /// * `canonical` - The host that is signed for eg. cdn.example.com
+    /// # Example
+    /// ```
+    /// use cloudfront_policy_signer::CloudFrontSigner;
+    ///
+    /// let signer = CloudFrontSigner::new("examples/key.pem", "APKAIEXAMPLE")
+    ///     .unwrap()
+    ///     .alias("media.example.com", "cdn.example.com");
+    /// let signed_url = signer
+    ///     .sign_url("https://MEDIA.example.com/img/a.png", 1700000000)
+    ///     .unwrap();
+    ///
+    /// assert!(signed_url.as_str().starts_with("https://cdn.example.com/img/a.png?"));
+    /// ```
+    pub fn alias<A: Into<String>, C: Into<String>>(
+        mut self,
+        alias: A,
+        canonical: C,
+    ) -> CloudFrontSigner {
+        self.aliases.push((alias.into(), canonical.into()));
+        self
+    }
+
+    /// Returns the resource that is signed for, borrowing it if it needs no changes
+    ///
+    /// Paths are turned into absolute resources on the [base URL](CloudFrontSigner::base_url)
+    /// and [aliases](CloudFrontSigner::alias) are replaced by their canonical host. Other
+    /// resources, the common case, are returned without copying.
+    ///
+    /// # Arguments
+    /// * `resource` - The protected resource or its path eg. /img/a.png
+    /// # Example
+    /// ```
+    /// use cloudfront_policy_signer::CloudFrontSigner;
+    /// use std::borrow::Cow;
+    ///
+    /// let signer = CloudFrontSigner::new("examples/key.pem", "APKAIEXAMPLE")
+    ///     .unwrap()
+    ///     .base_url("https://example.cloudfront.net");
+    ///
+    /// assert_eq!(signer.resolve_resource("/img/a.png"), "https://example.cloudfront.net/img/a.png");
+    /// assert!(matches!(
+    ///     signer.resolve_resource("https://example.cloudfront.net/img/a.png"),
+    ///     Cow::Borrowed(_)
+    /// ));
+    /// ```
+    pub fn resolve_resource<'a>(&self, resource: &'a str) -> Cow<'a, str> {
+        let resource = match &self.base_url {
+            Some(base_url) if resource.starts_with('/') => {
+                let mut resolved = base_url.clone();
+
+                resource::percent_encode_into(
+                    &mut resolved,
+                    resource,
+                    true,
+                    &self.encoding_profile,
+                );
+
+                Cow::Owned(resolved)
+            }
+            _ => Cow::Borrowed(resource),
+        };
+        let canonical = split_origin(&resource).and_then(|(scheme, host, rest)| {
+            self.aliases
+                .iter()
+                .find(|(alias, _)| alias.eq_ignore_ascii_case(host))
+                .map(|(_, canonical)| format!("{}://{}{}", scheme, canonical, rest))
+        });
+
+        canonical.map_or(resource, Cow::Owned)
+    }
+
+    /// Creates a URL to CloudFront with a canned policy, expiring after the default TTL of the signer
+    ///
+    /// The TTL is taken from the [`TtlPolicy`] if one is set and otherwise from
+    /// [`CloudFrontSigner::default_ttl`]. Fails with [`Error::InvalidConfig`] if neither is set.
+    /// Use [`CloudFrontSigner::sign_with_ttl`] or [`CloudFrontSigner::sign_url`] to override it.
+    ///
+    /// # Arguments
+    /// * `resource` - The protected resource eg. https://example.cloudfront.net/videos/1.mp4
+    ///
+    /// # Example
+    /// ```
+    /// use cloudfront_policy_signer::CloudFrontSigner;
+    /// use std::time::Duration;
+    ///
+    /// let signer = CloudFrontSigner::new("examples/key.pem", "APKAIEXAMPLE")
+    ///     .unwrap()
+    ///     .default_ttl(Duration::from_secs(3600));
+    /// let url = signer.sign("https://example.cloudfront.net/videos/1.mp4").unwrap();
+    ///
+    /// assert_eq!(url.key_pair_id(), "APKAIEXAMPLE");
+    /// ```
+    pub fn sign<R: AsRef<str>>(&self, resource: R) -> Result<SignedUrl, Error> {
+        let resource = resource.as_ref();
+        let ttl = match (&self.ttl_policy, self.default_ttl) {
+            (Some(ttl_policy), _) => ttl_policy.ttl_for(resource),
+            (None, Some(ttl)) => ttl,
+            (None, None) => {
+                error!("Signing {} without a default TTL", resource);
+                return Err(Error::InvalidConfig(
+                    "the signer has no default TTL".to_string(),
+                ));
+            }
+        };
+
+        self.sign_with_ttl(resource, ttl)
+    }
+
+    /// Creates a URL to CloudFront with a canned policy, expiring after the given TTL
+    ///
+    /// # Arguments
+    /// * `resource` - The protected resource eg. https://example.cloudfront.net/videos/1.mp4
+    /// * `ttl` - How long the URL is valid for
+    pub fn sign_with_ttl<R: AsRef<str>>(
+        &self,
+        resource: R,
+        ttl: Duration,
+    ) -> Result<SignedUrl, Error> {
+        let expiry = self.expiry_after(ttl)?;
+
+        self.sign_url(resource, expiry)
+    }
+
+    /// Creates a URL to CloudFront with a canned policy, expiring after the TTL the [`TtlPolicy`] of the signer gives the resource
+    ///
+    /// Fails with [`Error::InvalidConfig`] if no TTL policy was set with [`CloudFrontSigner::ttl_policy`].
+    ///
+    /// # Arguments
+    /// * `resource` - The protected resource eg. https://example.cloudfront.net/live/index.m3u8
+    ///
+    /// # Example
+    /// ```
+    /// use cloudfront_policy_signer::{CloudFrontSigner, TtlPolicy};
+    /// use std::time::{Duration, UNIX_EPOCH};
+    ///
+    /// let signer = CloudFrontSigner::new("examples/key.pem", "APKAIEXAMPLE")
+    ///     .unwrap()
+    ///     .ttl_policy(TtlPolicy::new(Duration::from_secs(3600)).rule("*.m3u8", Duration::from_secs(30)));
+    /// let url = signer
+    ///     .sign_url_auto("https://example.cloudfront.net/live/index.m3u8")
+    ///     .unwrap();
+    /// let now = UNIX_EPOCH.elapsed().unwrap().as_secs();
+    ///
+    /// assert!(url.expires_at() <= now + 30);
+    /// ```
+    pub fn sign_url_auto<R: AsRef<str>>(&self, resource: R) -> Result<SignedUrl, Error> {
+        let resource = resource.as_ref();
+        let ttl_policy = self.ttl_policy.as_ref().ok_or_else(|| {
+            error!("Signing {} without a TTL policy", resource);
+            Error::InvalidConfig("the signer has no TTL policy".to_string())
+        })?;
+        let expiry = self.expiry_after(ttl_policy.ttl_for(resource))?;
+
+        self.sign_url(resource, expiry)
+    }
+
+    /// Signs a URL with a canned policy on behalf of an optional caller
+    pub(crate) fn sign_url_for(
+        &self,
+        attribution: Option<Attribution>,
+        resource: &str,
+        expiry: u64,
+    ) -> Result<SignedUrl, Error> {
+        let result = self.try_sign_url_for(attribution, resource, expiry);
+
+        self.counters.record(result, self.now())
+    }
+
+    /// Does the work of [`CloudFrontSigner::sign_url_for`] without counting the result for [`CloudFrontSigner::status`]
+    fn try_sign_url_for(
+        &self,
+        attribution: Option<Attribution>,
+        resource: &str,
+        expiry: u64,
+    ) -> Result<SignedUrl, Error> {
+        self.check_rate_limit(attribution.map(|attribution| attribution.caller))?;
+
+        let resource = &*self.resolve_resource(resource);
+        let signature = self.canned_policy_signature_bytes(resource, expiry)?;
+
+        self.finish_canned_url(attribution, resource, expiry, &signature)
+    }
+
+    /// Creates a URL to CloudFront with a canned policy embedded at compile time
+    ///
+    /// Gives the same URL as [`CloudFrontSigner::sign_url`] for the resource of the template, but
+    /// only the expiry is formatted while signing instead of the whole policy document. The
+    /// resource is checked against the allowed prefixes and the base URL, it is not resolved
+    /// against the base URL or aliases as the document is fixed.
+    ///
+    /// # Arguments
+    /// * `template` - The policy created with the `canned_policy!` macro
+    /// * `expiry` - Absolute time that the URL expires, given in the form of a unix timestamp in UTC
+    /// # Example
+    /// ```
+    /// # #[cfg(feature = "macros")]
+    /// # {
+    /// use cloudfront_policy_signer::{canned_policy, CannedPolicyTemplate, CloudFrontSigner};
+    ///
+    /// static FLOWERPOT: CannedPolicyTemplate =
+    ///     canned_policy!("https://example.cloudfront.net/flowerpot.png");
+    ///
+    /// let signer = CloudFrontSigner::new("examples/key.pem", "APKAIEXAMPLE").unwrap();
+    ///
+    /// assert_eq!(
+    ///     signer.sign_url_embedded(&FLOWERPOT, 1700000000).unwrap(),
+    ///     signer
+    ///         .sign_url("https://example.cloudfront.net/flowerpot.png", 1700000000)
+    ///         .unwrap()
+    /// );
+    /// # }
+    /// ```
+    pub fn sign_url_embedded(
+        &self,
+        template: &CannedPolicyTemplate,
+        expiry: u64,
+    ) -> Result<SignedUrl, Error> {
+        let result = self.try_sign_url_embedded(template, expiry);
+
+        self.counters.record(result, self.now())
+    }
+
+    /// Does the work of [`CloudFrontSigner::sign_url_embedded`] without counting the result for [`CloudFrontSigner::status`]
+    fn try_sign_url_embedded(
+        &self,
+        template: &CannedPolicyTemplate,
+        expiry: u64,
+    ) -> Result<SignedUrl, Error> {
+        let resource = template.resource();
+
+        self.check_resource_allowed(Some(resource))?;
+
+        let mut digits = [0u8; 20];
+        let mut remaining = &mut digits[..];
+
+        write!(remaining, "{}", expiry).map_err(|e| {
+            error!("Could not format the expiry due to {}", e);
+            Error::Unknown
+        })?;
+
+        let digits_len = 20 - remaining.len();
+        let mut signer = Signer::new(MessageDigest::sha1(), &self.private_key).map_err(|e| {
+            error!("Could not create signer due to {}", e);
+            Error::Unknown
+        })?;
+
+        for part in [
+            template.prefix().as_bytes(),
+            &digits[..digits_len],
+            canned_template::SUFFIX.as_bytes(),
+        ] {
+            signer.update(part).map_err(|e| {
+                error!("Could not update signer due to {}", e);
+                Error::Unknown
+            })?;
+        }
+
+        let signature = signer.sign_to_vec().map_err(|e| {
+            error!("Could not sign due to {}", e);
+            Error::CouldNotSign
+        })?;
+
+        self.finish_canned_url(None, resource, expiry, &signature)
+    }
+
+    /// Builds the URL for a signed canned policy, applies the post processors and records the issue
+    fn finish_canned_url(
+        &self,
+        attribution: Option<Attribution>,
+        resource: &str,
+        expiry: u64,
+        signature: &[u8],
+    ) -> Result<SignedUrl, Error> {
+        let signed_url = canned_url(
+            resource,
+            expiry,
+            signature,
+            &self.key_pair_id,
+            self.max_url_length,
+        )?;
+        let signed_url = self
+            .post_processors
+            .iter()
+            .try_fold(signed_url, |signed_url, post_processor| {
+                post_processor.process(signed_url)
+            })?;
+
+        self.issued(attribution, &[resource], expiry, PolicyKind::Canned);
+
+        Ok(signed_url)
+    }
+
+    /// Returns the raw bytes of the RSA-SHA1 signature of a canned policy, without any encoding
+    ///
+    /// # Arguments
+    /// * `resource` - The protected resource eg. https://example.cloudfront.net/flowerpot.png
+    /// * `expiry` - Absolute time that the policy expires, given in the form of a unix timestamp in UTC
+    pub fn canned_policy_signature_bytes<R: AsRef<str>>(
+        &self,
+        resource: R,
+        expiry: u64,
+    ) -> Result<Vec<u8>, Error> {
+        let resource = &*self.resolve_resource(resource.as_ref());
+
+        self.check_resource_allowed(Some(resource))?;
+
+        sign_policy_document(&generate_canned_policy(resource, expiry), &self.private_key)
+    }
+
+    /// Writes a URL to CloudFront with a canned policy into a caller provided buffer and returns its length
+    ///
+    /// Nothing is allocated while signing with keys of up to 4096 bits and resources that need
+    /// no resolving against the base URL or aliases: the policy document is fed to the signer in
+    /// parts and the URL is written straight into the buffer. Fails with [`Error::UrlTooLong`] if the URL exceeds the
+    /// buffer or the limit of the signer, in which case the buffer is left untouched.
+    ///
+    /// The result is counted for [`CloudFrontSigner::status`] and reported to the sampler like
+    /// [`CloudFrontSigner::sign_url`], but the post-processors are not applied as they work on a
+    /// [`SignedUrl`].
+    ///
+    /// # Arguments
+    /// * `resource` - The protected resource eg. https://example.cloudfront.net/flowerpot.png
+    /// * `expiry` - Absolute time that the URL expires, given in the form of a unix timestamp in UTC
+    /// * `buffer` - The buffer the URL is written to
+    ///
+    /// # Example
+    /// ```
+    /// use cloudfront_policy_signer::CloudFrontSigner;
+    ///
+    /// let signer = CloudFrontSigner::new("examples/key.pem", "APKAIEXAMPLE").unwrap();
+    /// let mut buffer = [0u8; 1024];
+    /// let len = signer
+    ///     .sign_url_into("https://example.cloudfront.net/flowerpot.png", 1700000000, &mut buffer)
+    ///     .unwrap();
+    ///
+    /// assert!(buffer[..len].starts_with(b"https://example.cloudfront.net/flowerpot.png?Expires=1700000000&"));
+    /// assert!(signer.sign_url_into("https://example.cloudfront.net/flowerpot.png", 1700000000, &mut [0u8; 16]).is_err());
+    /// assert_eq!((signer.status().signed, signer.status().failed), (1, 1));
+    /// ```
+    pub fn sign_url_into<R: AsRef<str>>(
+        &self,
+        resource: R,
+        expiry: u64,
+        buffer: &mut [u8],
+    ) -> Result<usize, Error> {
+        let result = self.try_sign_url_into(resource.as_ref(), expiry, buffer);
+
+        self.counters.record(result, self.now())
+    }
+
+    /// Does the work of [`CloudFrontSigner::sign_url_into`] without counting the result for [`CloudFrontSigner::status`]
+    fn try_sign_url_into(
+        &self,
+        resource: &str,
+        expiry: u64,
+        buffer: &mut [u8],
+    ) -> Result<usize, Error> {
+        let resource = &*self.resolve_resource(resource);
+        let mut stack_signature = [0u8; MAX_STACK_SIGNATURE_LEN];
+        let heap_signature;
+        let signature = if self.private_key.size() <= MAX_STACK_SIGNATURE_LEN {
+            self.check_resource_allowed(Some(resource))?;
+
+            let len =
+                sign_canned_policy_into(resource, expiry, &self.private_key, &mut stack_signature)?;
+
+            &stack_signature[..len]
+        } else {
+            heap_signature = self.canned_policy_signature_bytes(resource, expiry)?;
+
+            &heap_signature[..]
+        };
+        let separator = if resource.contains('?') { '&' } else { '?' };
+        let expiry_len = expiry.checked_ilog10().unwrap_or(0) as usize + 1;
+        let len = resource.len()
+            + "?Expires=".len()
+            + expiry_len
+            + "&Signature=".len()
+            + base64::encoded_len(signature.len())
+            + "&Key-Pair-Id=".len()
+            + self.key_pair_id.len();
+        let limit = self.max_url_length.min(buffer.len());
+
+        if len > limit {
+            return Err(Error::UrlTooLong { len, limit });
+        }
+
+        let write_error = |e: std::io::Error| {
+            error!("Could not write the signed URL due to {}", e);
+            Error::Unknown
+        };
+        let mut remaining = &mut buffer[..len];
+
+        write!(
+            remaining,
+            "{}{}Expires={}&Signature=",
+            resource, separator, expiry
+        )
+        .map_err(write_error)?;
+
+        let signature_len = base64::encode_to_slice(signature, remaining);
+        let mut remaining = &mut remaining[signature_len..];
+
+        write!(remaining, "&Key-Pair-Id={}", self.key_pair_id).map_err(write_error)?;
+
+        self.issued(None, &[resource], expiry, PolicyKind::Canned);
+
+        Ok(len)
+    }
+
+    /// Creates the cookies which give access to the resource on CloudFront
+    ///
+    /// # Arguments
+    /// * `resource` - The protected resource eg. https://example.cloudfront.net/flowerpot.png
+    /// * `expiry` - Absolute time that the cookies expire, given in the form of a unix timestamp in UTC
+    pub fn create_canned_policy_signed_cookies<R: AsRef<str>>(
+        &self,
+        resource: R,
+        expiry: u64,
+    ) -> Result<SignedCookies, Error> {
+        self.canned_cookies_for(None, resource.as_ref(), expiry)
+    }
+
+    /// Creates cookies with a canned policy on behalf of an optional caller
+    pub(crate) fn canned_cookies_for(
+        &self,
+        attribution: Option<Attribution>,
+        resource: &str,
+        expiry: u64,
+    ) -> Result<SignedCookies, Error> {
+        let result = self.try_canned_cookies_for(attribution, resource, expiry);
+
+        self.counters.record(result, self.now())
+    }
+
+    /// Does the work of [`CloudFrontSigner::canned_cookies_for`] without counting the result for [`CloudFrontSigner::status`]
+    fn try_canned_cookies_for(
+        &self,
+        attribution: Option<Attribution>,
+        resource: &str,
+        expiry: u64,
+    ) -> Result<SignedCookies, Error> {
+        self.check_rate_limit(attribution.map(|attribution| attribution.caller))?;
+
+        let resource = &*self.resolve_resource(resource);
+
+        self.check_resource_allowed(Some(resource))?;
+
         let signed_policy =
-            sign_canned_policy(&generate_canned_policy(resource, expiry), &self.private_key)?;
-        let signature = encode_signature_url_safe(&signed_policy);
-        let url = format!(
-            "{}?Expires={}&Signature={}&Key-Pair-Id={}",
-            resource, expiry, signature, self.key_pair_id
+            sign_policy_document(&generate_canned_policy(resource, expiry), &self.private_key)?;
+
+        self.issued(attribution, &[resource], expiry, PolicyKind::Canned);
+
+        Ok(SignedCookies::canned(
+            expiry,
+            base64::encode(&signed_policy),
+            &self.key_pair_id,
+            resource,
+        ))
+    }
+
+    /// Signs a custom policy. The result can be attached to URLs or sent as cookies
+    ///
+    /// # Arguments
+    /// * `policy` - The policy to sign
+    pub fn sign_policy(&self, policy: &Policy) -> Result<SignedPolicy, Error> {
+        self.sign_policy_for(None, policy)
+    }
+
+    /// Signs a custom policy on behalf of an optional caller
+    pub(crate) fn sign_policy_for(
+        &self,
+        attribution: Option<Attribution>,
+        policy: &Policy,
+    ) -> Result<SignedPolicy, Error> {
+        let result = self.try_sign_policy_for(attribution, policy);
+
+        self.counters.record(result, self.now())
+    }
+
+    /// Does the work of [`CloudFrontSigner::sign_policy_for`] without counting the result for [`CloudFrontSigner::status`]
+    fn try_sign_policy_for(
+        &self,
+        attribution: Option<Attribution>,
+        policy: &Policy,
+    ) -> Result<SignedPolicy, Error> {
+        self.check_rate_limit(attribution.map(|attribution| attribution.caller))?;
+
+        let mut resolved;
+        let policy =
+            if policy.statements.iter().any(|statement| {
+                matches!(self.resolve_resource(&statement.resource), Cow::Owned(_))
+            }) {
+                resolved = policy.clone();
+
+                for statement in &mut resolved.statements {
+                    statement.resource = self.resolve_resource(&statement.resource).into_owned();
+                }
+
+                &resolved
+            } else {
+                policy
+            };
+
+        for statement in &policy.statements {
+            self.check_resource_allowed(Some(&statement.resource))?;
+        }
+
+        let validity = policy.validity();
+        let resources = policy
+            .statements
+            .iter()
+            .map(|statement| statement.resource.as_str())
+            .collect::<Vec<_>>();
+        let policy = policy.to_json();
+        let signature = sign_policy_document(policy.as_bytes(), &self.private_key)?;
+
+        self.issued(attribution, &resources, validity.0, PolicyKind::Custom);
+
+        Ok(SignedPolicy::new(
+            base64::encode(policy.as_bytes()),
+            signature,
+            validity,
+            self.key_pair_id.clone(),
+            cookies::cookie_path(resources.into_iter().map(Some)),
+            self.max_url_length,
+        ))
+    }
+
+    /// Signs a custom policy once and returns both the query parameters for URLs and the cookies
+    ///
+    /// Useful for endpoints that deliver access either way, as only one RSA operation is needed.
+    ///
+    /// # Arguments
+    /// * `policy` - The policy to sign
+    ///
+    /// # Example
+    /// ```
+    /// use cloudfront_policy_signer::{CloudFrontSigner, Policy};
+    ///
+    /// let signer = CloudFrontSigner::new("examples/key.pem", "APKAIEXAMPLE").unwrap();
+    /// let policy = Policy::builder("https://example.cloudfront.net/videos/*")
+    ///     .expires_at(1700000000)
+    ///     .build()
+    ///     .unwrap();
+    /// let artifacts = signer.sign_policy_artifacts(&policy).unwrap();
+    ///
+    /// assert_eq!(
+    ///     artifacts.query_parameters[1].1,
+    ///     artifacts.cookies.get("CloudFront-Signature").unwrap()
+    /// );
+    /// ```
+    pub fn sign_policy_artifacts(&self, policy: &Policy) -> Result<PolicyArtifacts, Error> {
+        let signed_policy = self.sign_policy(policy)?;
+
+        Ok(PolicyArtifacts {
+            query_parameters: signed_policy.query_parameters(),
+            cookies: signed_policy.cookies(),
+        })
+    }
+
+    /// Signs a custom policy that is shared by many viewers, see [`SharedPolicy`]
+    ///
+    /// Fails with [`Error::InvalidPolicy`] if a statement is restricted to an IP address, as such
+    /// a policy is specific to one viewer.
+    ///
+    /// # Arguments
+    /// * `policy` - The policy to sign
+    pub fn sign_shared_policy(&self, policy: &Policy) -> Result<SharedPolicy, Error> {
+        if policy
+            .statements
+            .iter()
+            .any(|statement| statement.condition.ip_address.is_some())
+        {
+            return Err(Error::InvalidPolicy(
+                "a policy restricted to IP addresses is specific to a viewer and cannot be shared"
+                    .to_string(),
+            ));
+        }
+
+        self.sign_policy(policy).map(SharedPolicy::new)
+    }
+
+    /// Signs an RS256 access token with the private key, see the [`jwt`] module
+    ///
+    /// The `iat` claim is set to now, the other claims are signed as given.
+    ///
+    /// # Arguments
+    /// * `claims` - The claims of the token
+    pub fn sign_jwt(&self, claims: &jwt::Claims) -> Result<String, Error> {
+        self.sign_jwt_for(None, claims)
+    }
+
+    /// Signs an access token on behalf of an optional caller
+    pub(crate) fn sign_jwt_for(
+        &self,
+        attribution: Option<Attribution>,
+        claims: &jwt::Claims,
+    ) -> Result<String, Error> {
+        let result = self.try_sign_jwt_for(attribution, claims);
+
+        self.counters.record(result, self.now())
+    }
+
+    /// Does the work of [`CloudFrontSigner::sign_jwt_for`] without counting the result for [`CloudFrontSigner::status`]
+    fn try_sign_jwt_for(
+        &self,
+        attribution: Option<Attribution>,
+        claims: &jwt::Claims,
+    ) -> Result<String, Error> {
+        self.check_rate_limit(attribution.map(|attribution| attribution.caller))?;
+        self.check_resource_allowed(Some(&claims.resource))?;
+
+        let issued_at = self
+            .now()
+            .duration_since(UNIX_EPOCH)
+            .map(|now| now.as_secs())
+            .unwrap_or_default();
+        let signing_input = jwt::signing_input(claims, &self.key_pair_id, issued_at);
+        let signature = sign_with_digest(
+            signing_input.as_bytes(),
+            &self.private_key,
+            MessageDigest::sha256(),
+        )?;
+
+        self.issued(
+            attribution,
+            &[&claims.resource],
+            claims.expires,
+            PolicyKind::Custom,
         );
 
-        Ok(url)
+        Ok(jwt::finish(signing_input, &signature))
+    }
+
+    /// Parses a custom policy from JSON and signs it
+    ///
+    /// The policy is re-serialized before signing, so formatting of the input does not matter.
+    ///
+    /// # Arguments
+    /// * `policy` - The policy as JSON, see [`Policy::from_json`]
+    pub fn sign_policy_json(&self, policy: &str) -> Result<SignedPolicy, Error> {
+        self.sign_policy(&Policy::from_json(policy)?)
+    }
+
+    /// Creates cookies granting access to everything below a prefix, eg. the private content of a user
+    ///
+    /// # Arguments
+    /// * `prefix` - The URL prefix eg. https://example.cloudfront.net/users/42/. A wildcard is appended as described in [`Resource::wildcard`]
+    /// * `ttl` - How long from now the cookies are valid for
+    ///
+    /// # Example
+    /// ```
+    /// use cloudfront_policy_signer::CloudFrontSigner;
+    /// use std::time::Duration;
+    ///
+    /// let signer = CloudFrontSigner::new("examples/key.pem", "APKAIEXAMPLE").unwrap();
+    /// let cookies = signer
+    ///     .grant_prefix_access("https://example.cloudfront.net/users/42/", Duration::from_secs(3600))
+    ///     .unwrap();
+    ///
+    /// assert_eq!(cookies.path(), "/users/42");
+    ///
+    /// for header in cookies.set_cookie_headers(Some("example.cloudfront.net"), None).unwrap() {
+    ///     println!("Set-Cookie: {}", header);
+    /// }
+    /// ```
+    pub fn grant_prefix_access<P: AsRef<str>>(
+        &self,
+        prefix: P,
+        ttl: Duration,
+    ) -> Result<SignedCookies, Error> {
+        let policy = Policy::builder(Resource::wildcard(prefix))
+            .expires_at(self.expiry_after(ttl)?)
+            .build()?;
+
+        Ok(self.sign_policy(&policy)?.cookies())
+    }
+
+    /// Starts signing a custom policy that is fed in chunks, see [`PolicyStream`]
+    ///
+    /// The streamed policy is not parsed, so signers with allowed or denied prefixes or a base URL
+    /// refuse to stream policies and fail with [`Error::ResourceNotAllowed`].
+    pub fn stream_policy(&self) -> Result<PolicyStream<'_>, Error> {
+        self.check_resource_allowed(None)?;
+
+        let signer = Signer::new(MessageDigest::sha1(), &self.private_key).map_err(|e| {
+            error!("Could not create signer due to {}", e);
+            Error::Unknown
+        })?;
+
+        Ok(PolicyStream::new(
+            signer,
+            &self.key_pair_id,
+            self.max_url_length,
+        ))
+    }
+
+    /// Fills in the placeholders of a policy template and signs the resulting policy
+    ///
+    /// # Arguments
+    /// * `template` - The policy template
+    /// * `values` - The name and value of every placeholder in the template
+    pub fn sign_policy_template(
+        &self,
+        template: &PolicyTemplate,
+        values: &[(&str, &str)],
+    ) -> Result<SignedPolicy, Error> {
+        self.sign_policy(&template.render(values)?)
+    }
+
+    /// Signs a policy document produced elsewhere, eg. by a templating system
+    ///
+    /// Unlike [`CloudFrontSigner::sign_policy_json`] the document is not checked against the
+    /// policy model. It only has to be a JSON object whose statements have a `DateLessThan`
+    /// condition, which is canonicalized by removing insignificant whitespace while keeping the
+    /// order of members. Documents with a key twice in the same object fail with
+    /// [`Error::InvalidPolicy`].
+    ///
+    /// # Arguments
+    /// * `policy` - The policy document as JSON
+    ///
+    /// # Example
+    /// ```
+    /// use cloudfront_policy_signer::{CloudFrontSigner, Error};
+    ///
+    /// let signer = CloudFrontSigner::new("examples/key.pem", "APKAIEXAMPLE").unwrap();
+    /// let condition = r#""Condition":{"DateLessThan":{"AWS:EpochTime":1700000000}}"#;
+    /// let policy = format!(
+    ///     r#"{{"Statement":[{{"Resource":"https://example.cloudfront.net/a.png",{}}}]}}"#,
+    ///     condition
+    /// );
+    /// let duplicate = format!(
+    ///     r#"{{"Statement":[{{"Resource":"https://example.cloudfront.net/a.png","Resource":"https://example.cloudfront.net/b.png",{}}}]}}"#,
+    ///     condition
+    /// );
+    ///
+    /// assert!(signer.sign_raw_policy(policy).is_ok());
+    /// assert!(matches!(signer.sign_raw_policy(duplicate), Err(Error::InvalidPolicy(_))));
+    /// assert_eq!((signer.status().signed, signer.status().failed), (1, 1));
+    /// ```
+    pub fn sign_raw_policy<T: AsRef<[u8]>>(&self, policy: T) -> Result<SignedPolicy, Error> {
+        let result = self.try_sign_raw_policy(policy.as_ref());
+
+        self.counters.record(result, self.now())
+    }
+
+    /// Does the work of [`CloudFrontSigner::sign_raw_policy`] without counting the result for [`CloudFrontSigner::status`]
+    fn try_sign_raw_policy(&self, policy: &[u8]) -> Result<SignedPolicy, Error> {
+        let document = parse_policy_document(policy)?;
+
+        if !matches!(document, json::Value::Object(_)) {
+            return Err(Error::InvalidPolicy(
+                "the policy must be a JSON object".to_string(),
+            ));
+        }
+
+        let resources = raw_policy_resources(&document);
+
+        for resource in &resources {
+            self.check_resource_allowed(*resource)?;
+        }
+
+        let validity = raw_policy_validity(&document)?;
+        let policy = document.to_json();
+        let signature = sign_policy_document(policy.as_bytes(), &self.private_key)?;
+
+        self.issued(
+            None,
+            &resources.iter().flatten().copied().collect::<Vec<_>>(),
+            validity.0,
+            PolicyKind::Custom,
+        );
+
+        Ok(SignedPolicy::new(
+            base64::encode(policy.as_bytes()),
+            signature,
+            validity,
+            self.key_pair_id.clone(),
+            cookies::cookie_path(resources),
+            self.max_url_length,
+        ))
+    }
+
+    /// Signs the exact bytes of a policy document after checking its resources against the allowed and denied prefixes
+    ///
+    /// Used by the helper process of [`sandbox::SandboxedSigner`], which must produce a signature
+    /// over the document it received rather than over a re-serialized one. As the document comes
+    /// from a process that may be compromised, it must have a non-empty `Statement` array whose
+    /// statements each have a `Resource`, and no key may appear twice in an object.
+    ///
+    /// # Arguments
+    /// * `document` - The policy document as JSON
+    pub(crate) fn sign_document(&self, document: &[u8]) -> Result<Vec<u8>, Error> {
+        let parsed = parse_policy_document(document)?;
+        let statements = parsed
+            .get("Statement")
+            .and_then(json::Value::as_array)
+            .filter(|statements| !statements.is_empty())
+            .ok_or_else(|| {
+                Error::InvalidPolicy("the policy must have a non-empty Statement array".to_string())
+            })?;
+
+        for statement in statements {
+            let resource = statement
+                .get("Resource")
+                .and_then(json::Value::as_str)
+                .ok_or_else(|| {
+                    Error::InvalidPolicy("every statement must have a Resource string".to_string())
+                })?;
+
+            self.check_resource_allowed(Some(resource))?;
+        }
+
+        sign_policy_document(document, &self.private_key)
     }
 }
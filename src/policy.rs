@@ -0,0 +1,660 @@
+/*
+MIT License
+
+Copyright (c) 2020 Martin Karlsen Jensen
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use crate::json::{self, Value};
+use crate::Error;
+use std::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A policy document describing which resources may be accessed and under which conditions
+///
+/// See [CloudFront Documentation](https://docs.aws.amazon.com/AmazonCloudFront/latest/DeveloperGuide/private-content-creating-signed-url-custom-policy.html) for more details
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Policy {
+    /// The statements of the policy. Access is granted if any statement matches
+    pub statements: Vec<Statement>,
+}
+
+/// The kind of policy a signed URL or signed cookies were created with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolicyKind {
+    /// Only the expiry is sent and the policy is implied by the resource
+    Canned,
+    /// The full policy is sent along with the signature
+    Custom,
+}
+
+/// A single statement of a policy
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Statement {
+    /// The protected resource eg. https://example.cloudfront.net/videos/*
+    pub resource: String,
+    /// The conditions under which the resource may be accessed
+    pub condition: Condition,
+}
+
+/// The conditions of a statement
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Condition {
+    /// Absolute time access expires, given in the form of a unix timestamp in UTC (`DateLessThan`)
+    pub date_less_than: u64,
+    /// Absolute time access starts, given in the form of a unix timestamp in UTC (`DateGreaterThan`)
+    pub date_greater_than: Option<u64>,
+    /// The IPv4 address or CIDR range of the viewers allowed access (`IpAddress`)
+    pub ip_address: Option<String>,
+}
+
+/// A difference between two policies, found by [`Policy::diff`]
+///
+/// Statements are compared by their position, which is what CloudFront signs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PolicyDifference {
+    /// The policies have a different number of statements. Only the statements both have are compared
+    StatementCount {
+        /// The number of statements of this policy
+        left: usize,
+        /// The number of statements of the other policy
+        right: usize,
+    },
+    /// A statement grants access to a different resource
+    Resource {
+        /// The index of the statement
+        statement: usize,
+        /// The resource of this policy
+        left: String,
+        /// The resource of the other policy
+        right: String,
+    },
+    /// A statement expires at a different time
+    DateLessThan {
+        /// The index of the statement
+        statement: usize,
+        /// The expiry of this policy
+        left: u64,
+        /// The expiry of the other policy
+        right: u64,
+    },
+    /// A statement starts at a different time
+    DateGreaterThan {
+        /// The index of the statement
+        statement: usize,
+        /// The start of this policy
+        left: Option<u64>,
+        /// The start of the other policy
+        right: Option<u64>,
+    },
+    /// A statement allows different viewers
+    IpAddress {
+        /// The index of the statement
+        statement: usize,
+        /// The IP addresses of this policy
+        left: Option<String>,
+        /// The IP addresses of the other policy
+        right: Option<String>,
+    },
+}
+
+impl fmt::Display for PolicyDifference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ip_or_none =
+            |value: &Option<String>| value.clone().unwrap_or_else(|| "none".to_string());
+
+        match self {
+            PolicyDifference::StatementCount { left, right } => {
+                write!(f, "{} statements versus {} statements", left, right)
+            }
+            PolicyDifference::Resource {
+                statement,
+                left,
+                right,
+            } => {
+                write!(
+                    f,
+                    "statement {} grants access to {} versus {}",
+                    statement + 1,
+                    left,
+                    right
+                )
+            }
+            PolicyDifference::DateLessThan {
+                statement,
+                left,
+                right,
+            } => {
+                let (earlier, difference) = if left < right {
+                    ("earlier", right - left)
+                } else {
+                    ("later", left - right)
+                };
+
+                write!(
+                    f,
+                    "statement {} expires at {} versus {}, {} seconds {}",
+                    statement + 1,
+                    left,
+                    right,
+                    difference,
+                    earlier
+                )
+            }
+            PolicyDifference::DateGreaterThan {
+                statement,
+                left,
+                right,
+            } => {
+                let time_or_none =
+                    |value: &Option<u64>| value.map_or("none".to_string(), |v| v.to_string());
+
+                write!(
+                    f,
+                    "statement {} starts at {} versus {}",
+                    statement + 1,
+                    time_or_none(left),
+                    time_or_none(right)
+                )
+            }
+            PolicyDifference::IpAddress {
+                statement,
+                left,
+                right,
+            } => {
+                write!(
+                    f,
+                    "statement {} allows the IP addresses {} versus {}",
+                    statement + 1,
+                    ip_or_none(left),
+                    ip_or_none(right)
+                )
+            }
+        }
+    }
+}
+
+impl Policy {
+    /// Returns the policy equivalent to a canned policy for the resource
+    ///
+    /// # Arguments
+    /// * `resource` - The protected resource eg. https://example.cloudfront.net/flowerpot.png
+    /// * `expiry` - Absolute time that access expires, given in the form of a unix timestamp in UTC
+    pub fn canned<R: Into<String>>(resource: R, expiry: u64) -> Policy {
+        Policy {
+            statements: vec![Statement {
+                resource: resource.into(),
+                condition: Condition {
+                    date_less_than: expiry,
+                    date_greater_than: None,
+                    ip_address: None,
+                },
+            }],
+        }
+    }
+
+    /// Starts building a custom policy for the resource
+    ///
+    /// The policy has a single statement, or one per IP range if several are allowed.
+    ///
+    /// # Arguments
+    /// * `resource` - The protected resource, which may contain wildcards eg. https://example.cloudfront.net/videos/*
+    ///
+    /// # Example
+    /// ```
+    /// use cloudfront_policy_signer::Policy;
+    ///
+    /// let policy = Policy::builder("https://example.cloudfront.net/videos/*")
+    ///     .expires_at(1579532331)
+    ///     .ip_address("192.0.2.0/24")
+    ///     .build()
+    ///     .unwrap();
+    ///
+    /// assert_eq!(policy.statements[0].condition.ip_address.as_deref(), Some("192.0.2.0/24"));
+    /// ```
+    pub fn builder<R: AsRef<str>>(resource: R) -> PolicyBuilder {
+        PolicyBuilder {
+            resource: resource.as_ref().to_string(),
+            date_less_than: None,
+            date_greater_than: None,
+            ip_addresses: Vec::new(),
+            omit_ip_condition_for_ipv6: false,
+            clock_skew: Duration::ZERO,
+        }
+    }
+
+    /// Returns how this policy differs from another one, empty if they are the same
+    ///
+    /// Useful for finding out why cookies minted by one service are not what another service
+    /// expects, eg. after decoding both with [`crate::verify::ParsedSignedUrl::policy`].
+    ///
+    /// # Arguments
+    /// * `other` - The policy to compare with
+    ///
+    /// # Example
+    /// ```
+    /// use cloudfront_policy_signer::{Policy, PolicyDifference};
+    ///
+    /// let policy = Policy::canned("https://example.cloudfront.net/videos/*", 1700000000);
+    /// let other = Policy::canned("https://example.cloudfront.net/videos/*", 1700003600);
+    ///
+    /// assert_eq!(
+    ///     policy.diff(&other),
+    ///     [PolicyDifference::DateLessThan { statement: 0, left: 1700000000, right: 1700003600 }]
+    /// );
+    /// assert_eq!(
+    ///     policy.diff(&other)[0].to_string(),
+    ///     "statement 1 expires at 1700000000 versus 1700003600, 3600 seconds earlier"
+    /// );
+    /// ```
+    pub fn diff(&self, other: &Policy) -> Vec<PolicyDifference> {
+        let mut differences = Vec::new();
+
+        if self.statements.len() != other.statements.len() {
+            differences.push(PolicyDifference::StatementCount {
+                left: self.statements.len(),
+                right: other.statements.len(),
+            });
+        }
+
+        for (statement, (left, right)) in self.statements.iter().zip(&other.statements).enumerate()
+        {
+            if left.resource != right.resource {
+                differences.push(PolicyDifference::Resource {
+                    statement,
+                    left: left.resource.clone(),
+                    right: right.resource.clone(),
+                });
+            }
+
+            let (left, right) = (&left.condition, &right.condition);
+
+            if left.date_less_than != right.date_less_than {
+                differences.push(PolicyDifference::DateLessThan {
+                    statement,
+                    left: left.date_less_than,
+                    right: right.date_less_than,
+                });
+            }
+
+            if left.date_greater_than != right.date_greater_than {
+                differences.push(PolicyDifference::DateGreaterThan {
+                    statement,
+                    left: left.date_greater_than,
+                    right: right.date_greater_than,
+                });
+            }
+
+            if left.ip_address != right.ip_address {
+                differences.push(PolicyDifference::IpAddress {
+                    statement,
+                    left: left.ip_address.clone(),
+                    right: right.ip_address.clone(),
+                });
+            }
+        }
+
+        differences
+    }
+
+    /// Returns the earliest `DateLessThan` and latest `DateGreaterThan` of the statements
+    pub(crate) fn validity(&self) -> (u64, Option<u64>) {
+        let expires = self
+            .statements
+            .iter()
+            .map(|statement| statement.condition.date_less_than)
+            .min()
+            .unwrap_or_default();
+        let not_before = self
+            .statements
+            .iter()
+            .filter_map(|statement| statement.condition.date_greater_than)
+            .max();
+
+        (expires, not_before)
+    }
+
+    /// Returns the policy as compact JSON in the form CloudFront expects
+    ///
+    /// # Example
+    /// ```
+    /// use cloudfront_policy_signer::Policy;
+    ///
+    /// let policy = Policy::canned("https://example.cloudfront.net/flowerpot.png", 1579532331);
+    ///
+    /// assert_eq!(
+    ///     policy.to_json(),
+    ///     r#"{"Statement":[{"Resource":"https://example.cloudfront.net/flowerpot.png","Condition":{"DateLessThan":{"AWS:EpochTime":1579532331}}}]}"#
+    /// );
+    /// ```
+    pub fn to_json(&self) -> String {
+        let mut json = String::from("{\"Statement\":[");
+
+        for (index, statement) in self.statements.iter().enumerate() {
+            let condition = &statement.condition;
+
+            if index > 0 {
+                json.push(',');
+            }
+
+            json.push_str("{\"Resource\":");
+            json::write_string(&mut json, &statement.resource);
+            json.push_str(&format!(
+                ",\"Condition\":{{\"DateLessThan\":{{\"AWS:EpochTime\":{}}}",
+                condition.date_less_than
+            ));
+
+            if let Some(date_greater_than) = condition.date_greater_than {
+                json.push_str(&format!(
+                    ",\"DateGreaterThan\":{{\"AWS:EpochTime\":{}}}",
+                    date_greater_than
+                ));
+            }
+
+            if let Some(ip_address) = &condition.ip_address {
+                json.push_str(",\"IpAddress\":{\"AWS:SourceIp\":");
+                json::write_string(&mut json, ip_address);
+                json.push('}');
+            }
+
+            json.push_str("}}");
+        }
+
+        json.push_str("]}");
+        json
+    }
+
+    /// Parses a policy document
+    ///
+    /// Whitespace and the order of members do not matter. Every statement needs a `Resource` and
+    /// a `DateLessThan` condition.
+    ///
+    /// # Arguments
+    /// * `policy` - The policy as JSON
+    pub fn from_json<T: AsRef<[u8]>>(policy: T) -> Result<Policy, Error> {
+        let invalid = |message: &str| Error::InvalidPolicy(message.to_string());
+        let document = json::parse(policy.as_ref()).map_err(Error::InvalidPolicy)?;
+        let statements = document
+            .get("Statement")
+            .and_then(Value::as_array)
+            .ok_or_else(|| invalid("the policy has no Statement array"))?;
+
+        if statements.is_empty() {
+            return Err(invalid("the policy has no statements"));
+        }
+
+        let statements = statements
+            .iter()
+            .map(|statement| {
+                let resource = statement
+                    .get("Resource")
+                    .and_then(Value::as_str)
+                    .ok_or_else(|| invalid("a statement has no Resource"))?;
+                let condition = statement
+                    .get("Condition")
+                    .ok_or_else(|| invalid("a statement has no Condition"))?;
+                let epoch_time = |name: &str| {
+                    condition
+                        .get(name)
+                        .map(|date| {
+                            date.get("AWS:EpochTime")
+                                .and_then(Value::as_u64)
+                                .ok_or_else(|| {
+                                    invalid(&format!("{} has no valid AWS:EpochTime", name))
+                                })
+                        })
+                        .transpose()
+                };
+                let ip_address = condition
+                    .get("IpAddress")
+                    .map(|ip| {
+                        ip.get("AWS:SourceIp")
+                            .and_then(Value::as_str)
+                            .map(str::to_string)
+                            .ok_or_else(|| invalid("IpAddress has no valid AWS:SourceIp"))
+                    })
+                    .transpose()?;
+
+                Ok(Statement {
+                    resource: resource.to_string(),
+                    condition: Condition {
+                        date_less_than: epoch_time("DateLessThan")?
+                            .ok_or_else(|| invalid("a statement has no DateLessThan"))?,
+                        date_greater_than: epoch_time("DateGreaterThan")?,
+                        ip_address,
+                    },
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        Ok(Policy { statements })
+    }
+}
+
+/// Builder for a custom policy with a single statement, created by [`Policy::builder`]
+#[derive(Debug, Clone)]
+pub struct PolicyBuilder {
+    resource: String,
+    date_less_than: Option<u64>,
+    date_greater_than: Option<u64>,
+    ip_addresses: Vec<String>,
+    omit_ip_condition_for_ipv6: bool,
+    clock_skew: Duration,
+}
+
+impl PolicyBuilder {
+    /// Sets when access expires
+    ///
+    /// # Arguments
+    /// * `expiry` - Absolute time that access expires, given in the form of a unix timestamp in UTC
+    pub fn expires_at(mut self, expiry: u64) -> PolicyBuilder {
+        self.date_less_than = Some(expiry);
+        self
+    }
+
+    /// Sets when access starts, so links can be created ahead of a scheduled release
+    ///
+    /// # Arguments
+    /// * `valid_from` - The time from which the resource may be accessed (`DateGreaterThan`)
+    pub fn valid_from(mut self, valid_from: SystemTime) -> PolicyBuilder {
+        self.date_greater_than = Some(
+            valid_from
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |since_epoch| since_epoch.as_secs()),
+        );
+        self
+    }
+
+    /// Moves the start of access set by [`PolicyBuilder::valid_from`] earlier to tolerate clock drift
+    ///
+    /// Without it, a clock running ahead of the one at AWS makes CloudFront reject freshly created
+    /// links as not yet valid. The expiry is not changed.
+    ///
+    /// # Arguments
+    /// * `skew` - The largest expected difference between the local clock and the one at AWS eg. 30 seconds
+    pub fn clock_skew(mut self, skew: Duration) -> PolicyBuilder {
+        self.clock_skew = skew;
+        self
+    }
+
+    /// Restricts access to viewers from the given IPv4 address or CIDR range
+    ///
+    /// Can be called several times to allow several ranges.
+    ///
+    /// # Arguments
+    /// * `ip_address` - The address or range eg. 192.0.2.0/24
+    pub fn ip_address<T: Into<String>>(mut self, ip_address: T) -> PolicyBuilder {
+        self.ip_addresses.push(ip_address.into());
+        self
+    }
+
+    /// Restricts access to viewers from any of the given IPv4 addresses or CIDR ranges
+    ///
+    /// CloudFront only allows one `IpAddress` per statement, so the policy gets an identical
+    /// statement for every range.
+    ///
+    /// # Arguments
+    /// * `ip_addresses` - The addresses or ranges eg. 192.0.2.0/24 and 198.51.100.0/24
+    ///
+    /// # Example
+    /// ```
+    /// use cloudfront_policy_signer::Policy;
+    ///
+    /// let policy = Policy::builder("https://example.cloudfront.net/videos/*")
+    ///     .expires_at(1579532331)
+    ///     .ip_addresses(["192.0.2.0/24", "198.51.100.0/24"])
+    ///     .build()
+    ///     .unwrap();
+    ///
+    /// assert_eq!(policy.statements.len(), 2);
+    /// ```
+    pub fn ip_addresses<I, T>(mut self, ip_addresses: I) -> PolicyBuilder
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        self.ip_addresses
+            .extend(ip_addresses.into_iter().map(Into::into));
+        self
+    }
+
+    /// Leaves out IPv6 addresses instead of failing, and the IP condition entirely if only IPv6 addresses are given
+    ///
+    /// CloudFront does not support IPv6 in the `IpAddress` condition and would block the viewer.
+    /// Dual-stack callers that restrict access to the viewer's address can use this to still grant
+    /// access to IPv6 viewers, at the cost of not restricting by IP for them. IPv4 addresses given
+    /// along with IPv6 ones are kept and still validated, so access is never widened beyond them.
+    ///
+    /// # Example
+    /// ```
+    /// use cloudfront_policy_signer::Policy;
+    ///
+    /// let builder = Policy::builder("https://example.cloudfront.net/videos/*")
+    ///     .expires_at(1579532331)
+    ///     .ip_address("2001:db8::1");
+    ///
+    /// assert!(builder.clone().build().is_err());
+    ///
+    /// let policy = builder.omit_ip_condition_for_ipv6().build().unwrap();
+    ///
+    /// assert_eq!(policy.statements[0].condition.ip_address, None);
+    ///
+    /// let mixed = Policy::builder("https://example.cloudfront.net/videos/*")
+    ///     .expires_at(1579532331)
+    ///     .ip_addresses(["203.0.113.0/24", "2001:db8::/32"])
+    ///     .omit_ip_condition_for_ipv6()
+    ///     .build()
+    ///     .unwrap();
+    ///
+    /// assert_eq!(mixed.statements.len(), 1);
+    /// assert_eq!(mixed.statements[0].condition.ip_address.as_deref(), Some("203.0.113.0/24"));
+    /// ```
+    pub fn omit_ip_condition_for_ipv6(mut self) -> PolicyBuilder {
+        self.omit_ip_condition_for_ipv6 = true;
+        self
+    }
+
+    /// Builds the policy
+    ///
+    /// Fails if no expiry was set, access would start after it expires, or an IP address is not a
+    /// valid IPv4 address or CIDR range. IPv6 addresses fail with
+    /// [`Error::Ipv6NotSupportedByCloudfront`] unless
+    /// [`PolicyBuilder::omit_ip_condition_for_ipv6`] was used.
+    pub fn build(mut self) -> Result<Policy, Error> {
+        let date_less_than = self
+            .date_less_than
+            .ok_or_else(|| Error::InvalidPolicy("the policy needs an expiry".to_string()))?;
+
+        self.date_greater_than = self
+            .date_greater_than
+            .map(|date_greater_than| date_greater_than.saturating_sub(self.clock_skew.as_secs()));
+
+        if self
+            .date_greater_than
+            .is_some_and(|date_greater_than| date_greater_than >= date_less_than)
+        {
+            return Err(Error::InvalidPolicy(
+                "the policy becomes valid after it expires".to_string(),
+            ));
+        }
+
+        let mut ip_addresses = Vec::with_capacity(self.ip_addresses.len());
+
+        for ip_address in self.ip_addresses.drain(..) {
+            match validate_ip_address(&ip_address) {
+                Err(Error::Ipv6NotSupportedByCloudfront(_)) if self.omit_ip_condition_for_ipv6 => {}
+                result => {
+                    result?;
+                    ip_addresses.push(ip_address);
+                }
+            }
+        }
+
+        self.ip_addresses = ip_addresses;
+
+        let statement = |ip_address| Statement {
+            resource: self.resource.clone(),
+            condition: Condition {
+                date_less_than,
+                date_greater_than: self.date_greater_than,
+                ip_address,
+            },
+        };
+        let statements = if self.ip_addresses.is_empty() {
+            vec![statement(None)]
+        } else {
+            self.ip_addresses
+                .iter()
+                .map(|ip_address| statement(Some(ip_address.clone())))
+                .collect()
+        };
+
+        Ok(Policy { statements })
+    }
+}
+
+/// Checks that the address is an IPv4 address or CIDR range as accepted by CloudFront
+///
+/// # Arguments
+/// * `ip_address` - The address or range eg. 192.0.2.0/24
+fn validate_ip_address(ip_address: &str) -> Result<(), Error> {
+    let (address, prefix) = match ip_address.split_once('/') {
+        Some((address, prefix)) => (address, Some(prefix)),
+        None => (ip_address, None),
+    };
+
+    if address.parse::<Ipv6Addr>().is_ok() {
+        return Err(Error::Ipv6NotSupportedByCloudfront(ip_address.to_string()));
+    }
+
+    let valid_prefix = prefix.is_none_or(|prefix| {
+        prefix.bytes().all(|b| b.is_ascii_digit()) && prefix.parse::<u8>().is_ok_and(|p| p <= 32)
+    });
+
+    if address.parse::<Ipv4Addr>().is_err() || !valid_prefix {
+        return Err(Error::InvalidPolicy(format!(
+            "'{}' is not an IPv4 address or CIDR range",
+            ip_address
+        )));
+    }
+
+    Ok(())
+}
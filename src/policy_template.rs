@@ -0,0 +1,182 @@
+/*
+MIT License
+
+Copyright (c) 2020 Martin Karlsen Jensen
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use crate::json;
+use crate::{Error, Policy};
+
+/// A policy document with `{name}` placeholders which are filled in when signing
+///
+/// Placeholders inside JSON strings are replaced by the escaped value. Placeholders outside
+/// strings, such as `{expiry}` for an epoch time, only accept unsigned integers.
+///
+/// # Example
+/// ```
+/// use cloudfront_policy_signer::PolicyTemplate;
+///
+/// let template = PolicyTemplate::new(
+///     r#"{"Statement":[{"Resource":"https://example.cloudfront.net/users/{user_id}/*","Condition":{"DateLessThan":{"AWS:EpochTime":{expiry}}}}]}"#,
+/// )
+/// .unwrap();
+/// let policy = template
+///     .render(&[("user_id", "42"), ("expiry", "1579532331")])
+///     .unwrap();
+///
+/// assert_eq!(policy.statements[0].resource, "https://example.cloudfront.net/users/42/*");
+/// assert!(template.render(&[("user_id", "42")]).is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyTemplate {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Placeholder { name: String, in_string: bool },
+}
+
+impl PolicyTemplate {
+    /// Parses a template
+    ///
+    /// # Arguments
+    /// * `template` - The policy document as JSON containing `{name}` placeholders. Names consist of ASCII letters, digits and underscores
+    pub fn new<T: AsRef<str>>(template: T) -> Result<PolicyTemplate, Error> {
+        let template = template.as_ref();
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut in_string = false;
+        let mut escaped = false;
+        let mut rest = template;
+
+        while let Some(c) = rest.chars().next() {
+            if c == '{' {
+                if let Some(name) = placeholder_name(rest) {
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+
+                    parts.push(Part::Placeholder {
+                        name: name.to_string(),
+                        in_string,
+                    });
+                    rest = &rest[name.len() + 2..];
+                    continue;
+                }
+            }
+
+            if in_string {
+                match c {
+                    _ if escaped => escaped = false,
+                    '\\' => escaped = true,
+                    '"' => in_string = false,
+                    _ => {}
+                }
+            } else if c == '"' {
+                in_string = true;
+            }
+
+            literal.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+
+        if in_string {
+            return Err(Error::InvalidPolicy(
+                "the template contains an unterminated string".to_string(),
+            ));
+        }
+
+        Ok(PolicyTemplate { parts })
+    }
+
+    /// Returns the names of the placeholders in the order they appear
+    pub fn placeholders(&self) -> impl Iterator<Item = &str> {
+        self.parts.iter().filter_map(|part| match part {
+            Part::Placeholder { name, .. } => Some(name.as_str()),
+            Part::Literal(_) => None,
+        })
+    }
+
+    /// Fills in every placeholder and parses the resulting policy
+    ///
+    /// Fails if a placeholder has no value or a value is given for a name that is not in the template.
+    ///
+    /// # Arguments
+    /// * `values` - The name and value of every placeholder
+    pub fn render(&self, values: &[(&str, &str)]) -> Result<Policy, Error> {
+        if let Some((name, _)) = values
+            .iter()
+            .find(|(name, _)| !self.placeholders().any(|placeholder| placeholder == *name))
+        {
+            return Err(Error::UnknownPlaceholder(name.to_string()));
+        }
+
+        let mut document = String::new();
+
+        for part in &self.parts {
+            match part {
+                Part::Literal(literal) => document.push_str(literal),
+                Part::Placeholder { name, in_string } => {
+                    let value = values
+                        .iter()
+                        .find(|(value_name, _)| value_name == name)
+                        .map(|(_, value)| *value)
+                        .ok_or_else(|| Error::UnfilledPlaceholder(name.clone()))?;
+
+                    if *in_string {
+                        let mut escaped = String::new();
+
+                        json::write_string(&mut escaped, value);
+                        document.push_str(&escaped[1..escaped.len() - 1]);
+                    } else if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) {
+                        document.push_str(value);
+                    } else {
+                        return Err(Error::InvalidPolicy(format!(
+                            "the placeholder {{{}}} is outside a string and must be an unsigned integer",
+                            name
+                        )));
+                    }
+                }
+            }
+        }
+
+        Policy::from_json(document)
+    }
+}
+
+/// Returns the name if `input` starts with a placeholder such as `{user_id}`
+fn placeholder_name(input: &str) -> Option<&str> {
+    let end = input.find('}')?;
+    let name = &input[1..end];
+    let mut chars = name.chars();
+
+    (chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_'))
+    .then_some(name)
+}
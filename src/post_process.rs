@@ -0,0 +1,78 @@
+/*
+MIT License
+
+Copyright (c) 2020 Martin Karlsen Jensen
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+//! Transforming signed URLs after they were signed
+//!
+//! A [`UrlPostProcessor`] registered with [`crate::CloudFrontSigner::post_process`] receives every
+//! URL the signer signs with a canned policy and returns the URL handed to the application, eg.
+//! after registering it with an internal shortener. Closures can be registered directly.
+//!
+//! ```
+//! use cloudfront_policy_signer::{CloudFrontSigner, SignedUrl};
+//! use std::collections::HashMap;
+//! use std::sync::{Arc, Mutex};
+//!
+//! let links = Arc::new(Mutex::new(HashMap::new()));
+//! let shortener = links.clone();
+//! let signer = CloudFrontSigner::new("examples/key.pem", "APKAIEXAMPLE")
+//!     .unwrap()
+//!     .post_process(Arc::new(move |signed_url: SignedUrl| {
+//!         let mut links = shortener.lock().unwrap();
+//!         let short = format!("https://sho.rt/{}", links.len());
+//!
+//!         links.insert(short.clone(), signed_url.as_str().to_string());
+//!
+//!         Ok(signed_url.with_url(short))
+//!     }));
+//!
+//! let url = signer
+//!     .sign_url("https://example.cloudfront.net/flowerpot.png", 1700000000)
+//!     .unwrap();
+//!
+//! assert_eq!(url.as_str(), "https://sho.rt/0");
+//! assert_eq!(url.expires_at(), 1700000000);
+//! assert!(links.lock().unwrap()["https://sho.rt/0"].contains("&Signature="));
+//! ```
+
+use crate::{Error, SignedUrl};
+
+/// Transforms a signed URL before it is returned by the signer
+pub trait UrlPostProcessor: Send + Sync {
+    /// Returns the URL handed to the application, usually with [`SignedUrl::with_url`] so what it grants access to is kept
+    ///
+    /// Fail with [`Error::PostProcessFailed`] if eg. the shortener could not be reached.
+    ///
+    /// # Arguments
+    /// * `signed_url` - The signed URL, or the result of the previous post-processor
+    fn process(&self, signed_url: SignedUrl) -> Result<SignedUrl, Error>;
+}
+
+impl<F> UrlPostProcessor for F
+where
+    F: Fn(SignedUrl) -> Result<SignedUrl, Error> + Send + Sync,
+{
+    fn process(&self, signed_url: SignedUrl) -> Result<SignedUrl, Error> {
+        self(signed_url)
+    }
+}
@@ -0,0 +1,128 @@
+/*
+MIT License
+
+Copyright (c) 2020 Martin Karlsen Jensen
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use crate::{CloudFrontSigner, Error, SignedUrl};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// A signed URL that is re-signed when it gets close to its expiry
+///
+/// Meant for long running processes that hand out the same signed URL many times, eg. in API
+/// responses. The URL is signed once and reused until less than the margin of its lifetime
+/// remains, so clients never receive a URL that is about to expire.
+///
+/// # Example
+/// ```no_run
+/// use cloudfront_policy_signer::{CloudFrontSigner, RefreshingSignedUrl};
+/// use std::sync::Arc;
+/// use std::time::Duration;
+///
+/// let signer = Arc::new(CloudFrontSigner::new("private_key.pem", "APKAEIBAERJR2EXAMPLE")?);
+/// let url = RefreshingSignedUrl::new(
+///     signer,
+///     "https://example.cloudfront.net/flowerpot.png",
+///     Duration::from_secs(60 * 60),
+/// )?
+/// .margin(0.25);
+///
+/// println!("{}", url.get()?);
+/// # Ok::<(), cloudfront_policy_signer::Error>(())
+/// ```
+pub struct RefreshingSignedUrl {
+    signer: Arc<CloudFrontSigner>,
+    resource: String,
+    ttl: Duration,
+    margin: f64,
+    clock_skew: Duration,
+    current: Mutex<SignedUrl>,
+}
+
+impl RefreshingSignedUrl {
+    /// Signs the resource and constructs a new `RefreshingSignedUrl` with a margin of 10%
+    ///
+    /// # Arguments
+    /// * `signer` - The signer used for every refresh
+    /// * `resource` - The protected resource eg. https://example.cloudfront.net/flowerpot.png
+    /// * `ttl` - How long every signed URL is valid for
+    pub fn new<R: Into<String>>(
+        signer: Arc<CloudFrontSigner>,
+        resource: R,
+        ttl: Duration,
+    ) -> Result<RefreshingSignedUrl, Error> {
+        let resource = resource.into();
+        let current = signer.sign_url(&resource, signer.expiry_after(ttl)?)?;
+
+        Ok(RefreshingSignedUrl {
+            signer,
+            resource,
+            ttl,
+            margin: 0.1,
+            clock_skew: Duration::ZERO,
+            current: Mutex::new(current),
+        })
+    }
+
+    /// Sets the part of the TTL that has to remain, below which the URL is re-signed
+    ///
+    /// # Arguments
+    /// * `margin` - A fraction between 0 and 1 eg. 0.1 for 10%, other values are clamped
+    pub fn margin(mut self, margin: f64) -> RefreshingSignedUrl {
+        self.margin = margin.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Re-signs the URL earlier to tolerate a local clock lagging behind the one at AWS
+    ///
+    /// # Arguments
+    /// * `skew` - The largest expected difference between the local clock and the one at AWS eg. 30 seconds
+    pub fn clock_skew(mut self, skew: Duration) -> RefreshingSignedUrl {
+        self.clock_skew = skew;
+        self
+    }
+
+    /// Returns the cached signed URL, re-signing it first if it is within the margin of expiry
+    pub fn get(&self) -> Result<SignedUrl, Error> {
+        let mut current = self.current.lock().unwrap_or_else(|e| e.into_inner());
+
+        if current.remaining(self.signer.now() + self.clock_skew) <= self.ttl.mul_f64(self.margin) {
+            *current = self
+                .signer
+                .sign_url(&self.resource, self.signer.expiry_after(self.ttl)?)?;
+        }
+
+        Ok(current.clone())
+    }
+
+    /// Re-signs the URL regardless of how long the cached URL is still valid for
+    pub fn refresh(&self) -> Result<SignedUrl, Error> {
+        let signed_url = self
+            .signer
+            .sign_url(&self.resource, self.signer.expiry_after(self.ttl)?)?;
+        let mut current = self.current.lock().unwrap_or_else(|e| e.into_inner());
+
+        *current = signed_url.clone();
+
+        Ok(signed_url)
+    }
+}
@@ -0,0 +1,158 @@
+/*
+MIT License
+
+Copyright (c) 2020 Martin Karlsen Jensen
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use crate::{CloudFrontSigner, Error};
+
+/// How a [`RegionalSigner`] picks the signer for a request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegionSelection {
+    /// Always use the signer of this region, eg. the region the application is deployed to
+    Explicit(String),
+    /// Use the signer of this region if configured, otherwise one in the same geography
+    /// eg. eu-central-1 for eu-west-1, otherwise the first configured signer
+    Nearest(String),
+    /// Use the region named by this request header eg. X-Region, or the first configured signer
+    /// if the header is missing or names an unknown region
+    Header(String),
+}
+
+/// Signers for several AWS regions or distributions, each with its own key group
+///
+/// # Example
+/// ```
+/// use cloudfront_policy_signer::{CloudFrontSigner, RegionSelection, RegionalSigner};
+///
+/// let signer = RegionalSigner::new(RegionSelection::Header("X-Region".to_string()))
+///     .region("us-east-1", CloudFrontSigner::new("examples/key.pem", "APKAIEXAMPLE").unwrap())
+///     .region("eu-west-1", CloudFrontSigner::new("examples/key.pem", "APKAIEXAMPLE2").unwrap());
+/// let selected = signer.select(&[("x-region", "eu-west-1")]).unwrap();
+///
+/// assert_eq!(selected.key_pair_id(), "APKAIEXAMPLE2");
+/// ```
+#[derive(Debug)]
+pub struct RegionalSigner {
+    regions: Vec<(String, CloudFrontSigner)>,
+    selection: RegionSelection,
+}
+
+impl RegionalSigner {
+    /// Constructs a signer without any regions
+    ///
+    /// # Arguments
+    /// * `selection` - How the signer for a request is picked
+    pub fn new(selection: RegionSelection) -> RegionalSigner {
+        RegionalSigner {
+            regions: Vec::new(),
+            selection,
+        }
+    }
+
+    /// Constructs a signer from profiles in the configuration file, see [`CloudFrontSigner::from_profile`]
+    ///
+    /// # Arguments
+    /// * `selection` - How the signer for a request is picked
+    /// * `profiles` - The region and profile name of every signer eg. `[("eu-west-1", "prod-eu")]`
+    pub fn from_profiles<'a, I>(
+        selection: RegionSelection,
+        profiles: I,
+    ) -> Result<RegionalSigner, Error>
+    where
+        I: IntoIterator<Item = (&'a str, &'a str)>,
+    {
+        profiles.into_iter().try_fold(
+            RegionalSigner::new(selection),
+            |signer, (region, profile)| {
+                Ok(signer.region(region, CloudFrontSigner::from_profile(profile)?))
+            },
+        )
+    }
+
+    /// Adds the signer of a region, replacing an earlier signer of the same region
+    ///
+    /// # Arguments
+    /// * `region` - The AWS region or another name for the distribution eg. eu-west-1
+    /// * `signer` - The signer for the key group of the region
+    pub fn region<T: Into<String>>(
+        mut self,
+        region: T,
+        signer: CloudFrontSigner,
+    ) -> RegionalSigner {
+        let region = region.into();
+
+        self.regions.retain(|(name, _)| *name != region);
+        self.regions.push((region, signer));
+        self
+    }
+
+    /// Returns the signer of a region, if configured
+    ///
+    /// # Arguments
+    /// * `region` - The AWS region eg. eu-west-1
+    pub fn get(&self, region: &str) -> Option<&CloudFrontSigner> {
+        self.regions
+            .iter()
+            .find(|(name, _)| name == region)
+            .map(|(_, signer)| signer)
+    }
+
+    /// Picks the signer for a request according to the selection strategy
+    ///
+    /// Fails with [`Error::InvalidConfig`] if no signer is configured, or if an explicitly selected region is missing.
+    ///
+    /// # Arguments
+    /// * `headers` - The name and value of the request headers, only used by [`RegionSelection::Header`]
+    pub fn select(&self, headers: &[(&str, &str)]) -> Result<&CloudFrontSigner, Error> {
+        let first = || {
+            self.regions
+                .first()
+                .map(|(_, signer)| signer)
+                .ok_or_else(|| Error::InvalidConfig("no region has a signer".to_string()))
+        };
+
+        match &self.selection {
+            RegionSelection::Explicit(region) => self.get(region).ok_or_else(|| {
+                error!("No signer is configured for the region {}", region);
+                Error::InvalidConfig(format!("no signer is configured for the region {}", region))
+            }),
+            RegionSelection::Nearest(region) => match self.get(region) {
+                Some(signer) => Ok(signer),
+                None => self
+                    .regions
+                    .iter()
+                    .find(|(name, _)| geography(name) == geography(region))
+                    .map_or_else(first, |(_, signer)| Ok(signer)),
+            },
+            RegionSelection::Header(header) => headers
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(header))
+                .and_then(|(_, region)| self.get(region.trim()))
+                .map_or_else(first, Ok),
+        }
+    }
+}
+
+/// Returns the geography of a region, eg. eu for eu-west-1
+fn geography(region: &str) -> &str {
+    region.split('-').next().unwrap_or(region)
+}
@@ -0,0 +1,493 @@
+/*
+MIT License
+
+Copyright (c) 2020 Martin Karlsen Jensen
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use std::borrow::Cow;
+use std::fmt;
+
+/// A resource that a policy grants access to, eg. https://example.cloudfront.net/flowerpot.png
+///
+/// CloudFront compares the resource in the policy byte for byte with the requested URL, so a
+/// stray double slash or an unencoded space results in a 403. The constructors on this type take
+/// care of joining, encoding and wildcard placement so the policy matches the URL the viewer
+/// requests.
+///
+/// Non-ASCII characters are percent-encoded as UTF-8, which is how browsers request such URLs,
+/// and the same encoded resource ends up in both the policy and the signed URL. Use
+/// [`Resource::raw`] if the policy has to contain the resource exactly as given instead, or an
+/// [`EncodingProfile`] for origins that treat `+`, spaces or non-ASCII characters differently.
+///
+/// # Example
+/// ```
+/// # #[cfg(feature = "sign")]
+/// # {
+/// use cloudfront_policy_signer::verify::ParsedSignedUrl;
+/// use cloudfront_policy_signer::{CloudFrontSigner, Resource};
+///
+/// let signer = CloudFrontSigner::new("examples/key.pem", "APKAIEXAMPLE").unwrap();
+/// let resource = Resource::object("example.cloudfront.net", "/фото/☀️.jpg");
+/// let url = signer.sign_url(&resource, 1700000000).unwrap();
+/// let parsed = ParsedSignedUrl::parse(url.as_str()).unwrap();
+///
+/// assert_eq!(
+///     resource.as_str(),
+///     "https://example.cloudfront.net/%D1%84%D0%BE%D1%82%D0%BE/%E2%98%80%EF%B8%8F.jpg"
+/// );
+/// assert!(url.as_str().is_ascii());
+/// assert_eq!(parsed.resource(), resource.as_str());
+/// assert_eq!(parsed.policy().unwrap().statements[0].resource, resource.as_str());
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Resource(String);
+
+impl Resource {
+    /// Creates a resource for a single object on a distribution
+    ///
+    /// # Arguments
+    /// * `distribution_domain` - The domain of the distribution eg. example.cloudfront.net. `https://` is assumed if no scheme is given
+    /// * `path` - The path of the object eg. /images/flowerpot.png. Repeated slashes are collapsed into one, characters which are not allowed in a URL are percent-encoded and a literal `*` is encoded so it is not treated as a wildcard
+    ///
+    /// # Example
+    /// ```
+    /// use cloudfront_policy_signer::Resource;
+    ///
+    /// let resource = Resource::object("example.cloudfront.net/", "images/flower pot.png");
+    /// let nested = Resource::object("example.cloudfront.net", "//images//2024///flowerpot.png");
+    ///
+    /// assert_eq!(resource.as_str(), "https://example.cloudfront.net/images/flower%20pot.png");
+    /// assert_eq!(nested.as_str(), "https://example.cloudfront.net/images/2024/flowerpot.png");
+    /// ```
+    pub fn object<D: AsRef<str>, P: AsRef<str>>(distribution_domain: D, path: P) -> Resource {
+        Resource::object_with(distribution_domain, path, &EncodingProfile::CUSTOM_ORIGIN)
+    }
+
+    /// Creates a resource for a single object on a distribution, encoding the path with a profile
+    ///
+    /// # Arguments
+    /// * `distribution_domain` - The domain of the distribution eg. example.cloudfront.net. `https://` is assumed if no scheme is given
+    /// * `path` - The path of the object eg. /images/flowerpot.png. Repeated slashes are collapsed into one
+    /// * `profile` - How the origin expects the path to be encoded
+    ///
+    /// # Example
+    /// ```
+    /// use cloudfront_policy_signer::{EncodingProfile, Resource};
+    ///
+    /// let resource = Resource::object_with(
+    ///     "example.cloudfront.net",
+    ///     "/c++ guide.pdf",
+    ///     &EncodingProfile::S3_ORIGIN,
+    /// );
+    ///
+    /// assert_eq!(resource.as_str(), "https://example.cloudfront.net/c%2B%2B%20guide.pdf");
+    /// ```
+    pub fn object_with<D: AsRef<str>, P: AsRef<str>>(
+        distribution_domain: D,
+        path: P,
+        profile: &EncodingProfile,
+    ) -> Resource {
+        let mut resource = base_url(distribution_domain.as_ref());
+
+        resource.push('/');
+        percent_encode_into(
+            &mut resource,
+            &collapse_slashes(path.as_ref()),
+            false,
+            profile,
+        );
+
+        Resource(resource)
+    }
+
+    /// Creates a resource for a single object from its complete URL
+    ///
+    /// # Arguments
+    /// * `url` - The URL of the object eg. https://example.cloudfront.net/фото/☀️.jpg. Characters which are not allowed in a URL are percent-encoded and a literal `*` is encoded so it is not treated as a wildcard
+    ///
+    /// # Example
+    /// ```
+    /// use cloudfront_policy_signer::Resource;
+    ///
+    /// let resource = Resource::url("https://example.cloudfront.net/café.jpg");
+    ///
+    /// assert_eq!(resource.as_str(), "https://example.cloudfront.net/caf%C3%A9.jpg");
+    /// ```
+    pub fn url<U: AsRef<str>>(url: U) -> Resource {
+        Resource::url_with(url, &EncodingProfile::CUSTOM_ORIGIN)
+    }
+
+    /// Creates a resource for a single object from its complete URL, encoding it with a profile
+    ///
+    /// # Arguments
+    /// * `url` - The URL of the object eg. https://example.cloudfront.net/фото/☀️.jpg
+    /// * `profile` - How the origin expects the URL to be encoded
+    pub fn url_with<U: AsRef<str>>(url: U, profile: &EncodingProfile) -> Resource {
+        let url = url.as_ref();
+        let mut resource = String::with_capacity(url.len());
+
+        percent_encode_into(&mut resource, url, false, profile);
+
+        Resource(resource)
+    }
+
+    /// Percent-encodes a URL like [`Resource::url`], borrowing it if it is already encoded
+    ///
+    /// Most resources in high-throughput services are already canonical, so they are returned
+    /// without copying.
+    ///
+    /// # Arguments
+    /// * `url` - The URL of the object eg. https://example.cloudfront.net/flowerpot.png
+    ///
+    /// # Example
+    /// ```
+    /// use cloudfront_policy_signer::Resource;
+    /// use std::borrow::Cow;
+    ///
+    /// assert!(matches!(
+    ///     Resource::normalize("https://example.cloudfront.net/flowerpot.png"),
+    ///     Cow::Borrowed(_)
+    /// ));
+    /// assert_eq!(
+    ///     Resource::normalize("https://example.cloudfront.net/flower pot.png"),
+    ///     "https://example.cloudfront.net/flower%20pot.png"
+    /// );
+    /// ```
+    pub fn normalize(url: &str) -> Cow<'_, str> {
+        Resource::normalize_with(url, &EncodingProfile::CUSTOM_ORIGIN)
+    }
+
+    /// Percent-encodes a URL like [`Resource::url_with`], borrowing it if it is already encoded
+    ///
+    /// # Arguments
+    /// * `url` - The URL of the object eg. https://example.cloudfront.net/flowerpot.png
+    /// * `profile` - How the origin expects the URL to be encoded
+    pub fn normalize_with<'a>(url: &'a str, profile: &EncodingProfile) -> Cow<'a, str> {
+        let bytes = url.as_bytes();
+        let mut in_query = false;
+        let unchanged = url.char_indices().all(|(i, c)| {
+            in_query |= c == '?';
+            is_unchanged(bytes, i, c, in_query, false, profile)
+        });
+
+        if unchanged {
+            Cow::Borrowed(url)
+        } else {
+            Cow::Owned(Resource::url_with(url, profile).0)
+        }
+    }
+
+    /// Creates a resource matching every URL starting with `prefix`
+    ///
+    /// # Arguments
+    /// * `prefix` - The URL prefix eg. https://example.cloudfront.net/videos/. Any `*` already in the prefix is kept as a wildcard and a trailing `*` is appended if missing
+    ///
+    /// # Example
+    /// ```
+    /// use cloudfront_policy_signer::Resource;
+    ///
+    /// let resource = Resource::wildcard("https://example.cloudfront.net/videos/");
+    ///
+    /// assert_eq!(resource.as_str(), "https://example.cloudfront.net/videos/*");
+    /// ```
+    pub fn wildcard<P: AsRef<str>>(prefix: P) -> Resource {
+        let prefix = prefix.as_ref().trim_end_matches('*');
+        let mut resource = String::with_capacity(prefix.len() + 1);
+
+        percent_encode_into(&mut resource, prefix, true, &EncodingProfile::CUSTOM_ORIGIN);
+        resource.push('*');
+
+        Resource(resource)
+    }
+
+    /// Wraps an already formatted resource without any normalization
+    ///
+    /// Non-ASCII characters are kept as they are in the policy, which only matches when the
+    /// viewer requests the URL without encoding them.
+    ///
+    /// # Arguments
+    /// * `resource` - The resource exactly as it should appear in the policy
+    pub fn raw<T: Into<String>>(resource: T) -> Resource {
+        Resource(resource.into())
+    }
+
+    /// Returns the resource as it will appear in the policy
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for Resource {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for Resource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// How characters that origins interpret differently are encoded in resources
+///
+/// The encoded resource is used for both the policy and the signed URL, as CloudFront compares
+/// them byte for byte. What differs between origins is how they decode the request:
+/// S3 decodes a literal `+` in the path as a space, so an object key containing `+` is only found
+/// if the `+` is encoded as `%2B`, while custom origins usually take `+` in a path literally.
+///
+/// Existing `%XX` escapes are always kept as they are.
+///
+/// # Example
+/// ```
+/// use cloudfront_policy_signer::{EncodingProfile, Resource};
+///
+/// let profile = EncodingProfile::CUSTOM_ORIGIN
+///     .space_as_plus(true)
+///     .encode_non_ascii(false);
+/// let resource = Resource::url_with("https://example.cloudfront.net/café?q=a b", &profile);
+///
+/// assert_eq!(resource.as_str(), "https://example.cloudfront.net/café?q=a+b");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EncodingProfile {
+    encode_plus: bool,
+    space_as_plus: bool,
+    encode_non_ascii: bool,
+}
+
+impl EncodingProfile {
+    /// For S3 origins: `+` is encoded as `%2B`, spaces as `%20` and non-ASCII characters as UTF-8
+    pub const S3_ORIGIN: EncodingProfile = EncodingProfile {
+        encode_plus: true,
+        space_as_plus: false,
+        encode_non_ascii: true,
+    };
+
+    /// For custom origins and the default of [`Resource`]: `+` is kept, spaces are encoded as `%20`
+    /// and non-ASCII characters as UTF-8
+    pub const CUSTOM_ORIGIN: EncodingProfile = EncodingProfile {
+        encode_plus: false,
+        space_as_plus: false,
+        encode_non_ascii: true,
+    };
+
+    /// Sets whether a literal `+` is encoded as `%2B`
+    ///
+    /// # Arguments
+    /// * `encode_plus` - Whether the origin decodes `+` as a space
+    pub const fn encode_plus(mut self, encode_plus: bool) -> EncodingProfile {
+        self.encode_plus = encode_plus;
+        self
+    }
+
+    /// Sets whether spaces in the query string are encoded as `+` instead of `%20`
+    ///
+    /// Spaces in the path are always encoded as `%20`.
+    ///
+    /// # Arguments
+    /// * `space_as_plus` - Whether the query string is form encoded
+    pub const fn space_as_plus(mut self, space_as_plus: bool) -> EncodingProfile {
+        self.space_as_plus = space_as_plus;
+        self
+    }
+
+    /// Sets whether non-ASCII characters are percent-encoded as UTF-8
+    ///
+    /// Keeping them only matches viewers which request the URL without encoding them, as with
+    /// [`Resource::raw`].
+    ///
+    /// # Arguments
+    /// * `encode_non_ascii` - Whether non-ASCII characters are percent-encoded
+    pub const fn encode_non_ascii(mut self, encode_non_ascii: bool) -> EncodingProfile {
+        self.encode_non_ascii = encode_non_ascii;
+        self
+    }
+}
+
+impl Default for EncodingProfile {
+    fn default() -> EncodingProfile {
+        EncodingProfile::CUSTOM_ORIGIN
+    }
+}
+
+/// Returns the scheme and host of the distribution without a trailing slash
+///
+/// # Arguments
+/// * `distribution_domain` - The domain of the distribution, optionally with a scheme
+fn base_url(distribution_domain: &str) -> String {
+    let domain = distribution_domain.trim_end_matches('/');
+
+    if domain.contains("://") {
+        domain.to_string()
+    } else {
+        format!("https://{}", domain)
+    }
+}
+
+/// Removes leading slashes from the path of an object and collapses repeated slashes into one
+///
+/// The query string, if any, is kept as it is.
+///
+/// # Arguments
+/// * `path` - The path of the object eg. //images//flowerpot.png
+fn collapse_slashes(path: &str) -> String {
+    let (key, query) = path.split_at(path.find('?').unwrap_or(path.len()));
+    let mut collapsed = String::with_capacity(path.len());
+
+    for c in key.trim_start_matches('/').chars() {
+        if c != '/' || !collapsed.ends_with('/') {
+            collapsed.push(c);
+        }
+    }
+
+    collapsed.push_str(query);
+    collapsed
+}
+
+/// Percent-encodes every byte that may not appear literally in a URL and appends the result
+///
+/// Existing `%XX` escapes are kept as they are so already encoded input is not encoded twice.
+///
+/// # Arguments
+/// * `out` - The string the encoded input is appended to
+/// * `input` - The text to encode
+/// * `keep_wildcards` - Whether `*` is kept as a wildcard or encoded as a literal character
+/// * `profile` - How `+`, spaces and non-ASCII characters are encoded
+pub(crate) fn percent_encode_into(
+    out: &mut String,
+    input: &str,
+    keep_wildcards: bool,
+    profile: &EncodingProfile,
+) {
+    let bytes = input.as_bytes();
+    let mut in_query = false;
+
+    for (i, c) in input.char_indices() {
+        let byte = bytes[i];
+
+        in_query |= byte == b'?';
+
+        if is_unchanged(bytes, i, c, in_query, keep_wildcards, profile) {
+            out.push(c);
+        } else if byte == b' ' && in_query && profile.space_as_plus {
+            out.push('+');
+        } else if byte == b'+' && profile.encode_plus {
+            out.push_str("%2B");
+        } else {
+            let mut buffer = [0; 4];
+
+            for byte in c.encode_utf8(&mut buffer).bytes() {
+                out.push_str(&format!("%{:02X}", byte));
+            }
+        }
+    }
+}
+
+/// Whether the character at `i` is kept as it is when percent-encoding
+///
+/// # Arguments
+/// * `bytes` - The text being encoded
+/// * `i` - The index of the character in `bytes`
+/// * `c` - The character
+/// * `in_query` - Whether the character is in the query string
+/// * `keep_wildcards` - Whether `*` is kept as a wildcard or encoded as a literal character
+/// * `profile` - How `+`, spaces and non-ASCII characters are encoded
+fn is_unchanged(
+    bytes: &[u8],
+    i: usize,
+    c: char,
+    in_query: bool,
+    keep_wildcards: bool,
+    profile: &EncodingProfile,
+) -> bool {
+    let byte = bytes[i];
+    let is_escape = byte == b'%'
+        && bytes.len() > i + 2
+        && bytes[i + 1].is_ascii_hexdigit()
+        && bytes[i + 2].is_ascii_hexdigit();
+
+    if !c.is_ascii() {
+        !profile.encode_non_ascii
+    } else if (byte == b' ' && in_query && profile.space_as_plus)
+        || (byte == b'+' && profile.encode_plus)
+    {
+        false
+    } else {
+        is_escape || is_url_safe(byte) || (keep_wildcards && byte == b'*')
+    }
+}
+
+/// Whether the byte can appear in the path or query of a URL without being encoded
+fn is_url_safe(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric()
+        || matches!(
+            byte,
+            b'-' | b'.'
+                | b'_'
+                | b'~'
+                | b'/'
+                | b':'
+                | b'@'
+                | b'!'
+                | b'$'
+                | b'&'
+                | b'\''
+                | b'('
+                | b')'
+                | b'+'
+                | b','
+                | b';'
+                | b'='
+                | b'?'
+        )
+}
+
+/// Whether the value matches the pattern, where `*` matches any number of characters and `?` one, as in policies
+pub(crate) fn wildcard_matches(pattern: &str, value: &str) -> bool {
+    let (pattern, path) = (pattern.as_bytes(), value.as_bytes());
+    let (mut p, mut s) = (0, 0);
+    let mut backtrack = None;
+
+    while s < path.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                backtrack = Some((p, s));
+                p += 1;
+            }
+            Some(&c) if c == b'?' || c == path[s] => {
+                p += 1;
+                s += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    s = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == b'*')
+}
@@ -0,0 +1,213 @@
+/*
+MIT License
+
+Copyright (c) 2020 Martin Karlsen Jensen
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+//! Rewriting of CloudFront URLs in HTML documents
+//!
+//! [`HtmlRewriter`] finds every URL pointing at one of the configured CloudFront hosts and replaces
+//! it with a signed URL, which lets a response middleware sign the assets of a legacy application
+//! without touching its templates. A tower or actix middleware only has to buffer `text/html`
+//! bodies and pass them through [`HtmlRewriter::rewrite`]:
+//!
+//! ```ignore
+//! let body = String::from_utf8(body_bytes.to_vec())?;
+//! let rewritten = rewriter.rewrite(&body)?;
+//!
+//! response.headers_mut().remove(header::CONTENT_LENGTH);
+//! *response.body_mut() = rewritten.into();
+//! ```
+
+use crate::{CloudFrontSigner, Error};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Rewrites URLs to configured CloudFront hosts in HTML into signed URLs
+pub struct HtmlRewriter {
+    signer: Arc<CloudFrontSigner>,
+    hosts: Vec<String>,
+    path_ttls: Vec<(String, Duration)>,
+    default_ttl: Duration,
+}
+
+impl HtmlRewriter {
+    /// Constructs a new `HtmlRewriter` without any hosts and a default TTL of one hour
+    ///
+    /// # Arguments
+    /// * `signer` - The signer used for every rewritten URL
+    pub fn new(signer: Arc<CloudFrontSigner>) -> HtmlRewriter {
+        HtmlRewriter {
+            signer,
+            hosts: Vec::new(),
+            path_ttls: Vec::new(),
+            default_ttl: Duration::from_secs(60 * 60),
+        }
+    }
+
+    /// Adds a host whose URLs should be signed
+    ///
+    /// # Arguments
+    /// * `host` - The host of the distribution eg. example.cloudfront.net
+    pub fn host<T: Into<String>>(mut self, host: T) -> HtmlRewriter {
+        self.hosts.push(host.into());
+        self
+    }
+
+    /// Uses a specific TTL for URLs whose path starts with `prefix`. The longest matching prefix wins
+    ///
+    /// # Arguments
+    /// * `prefix` - The path prefix eg. /videos/
+    /// * `ttl` - How long URLs below the prefix are valid for
+    pub fn path_ttl<T: Into<String>>(mut self, prefix: T, ttl: Duration) -> HtmlRewriter {
+        self.path_ttls.push((prefix.into(), ttl));
+        self
+    }
+
+    /// Sets the TTL used for URLs not matching any path prefix
+    ///
+    /// # Arguments
+    /// * `ttl` - How long the URLs are valid for
+    pub fn default_ttl(mut self, ttl: Duration) -> HtmlRewriter {
+        self.default_ttl = ttl;
+        self
+    }
+
+    /// Returns the HTML with every URL to a configured host replaced by a signed URL
+    ///
+    /// A URL ends at whitespace, quotes, `<`, `>`, `` ` ``, `)`, `,` or a `;` that does not end
+    /// `&amp;`, so URLs in CSS `url(...)` and `srcset` lists are found as well. URLs which already
+    /// carry a signature are left untouched, as are URLs that cannot be signed, eg. because of
+    /// [`Error::ResourceNotAllowed`], for which a warning is logged instead.
+    ///
+    /// # Arguments
+    /// * `html` - The HTML document to rewrite
+    ///
+    /// # Example
+    /// ```
+    /// use cloudfront_policy_signer::rewrite::HtmlRewriter;
+    /// use cloudfront_policy_signer::CloudFrontSigner;
+    /// use std::sync::Arc;
+    ///
+    /// let signer = CloudFrontSigner::new("examples/key.pem", "APKAIEXAMPLE")
+    ///     .unwrap()
+    ///     .denied_prefixes(["https://example.cloudfront.net/internal/"]);
+    /// let rewriter = HtmlRewriter::new(Arc::new(signer)).host("example.cloudfront.net");
+    /// let html = rewriter
+    ///     .rewrite(concat!(
+    ///         "<div style=\"background: url(https://example.cloudfront.net/bg.png)\"></div>",
+    ///         "<img src=\"https://example.cloudfront.net/internal/a.png\">",
+    ///     ))
+    ///     .unwrap();
+    ///
+    /// assert!(html.starts_with("<div style=\"background: url(https://example.cloudfront.net/bg.png?Expires="));
+    /// assert!(html.contains("&amp;Key-Pair-Id=APKAIEXAMPLE)\"></div>"));
+    /// assert!(html.ends_with("<img src=\"https://example.cloudfront.net/internal/a.png\">"));
+    /// ```
+    pub fn rewrite(&self, html: &str) -> Result<String, Error> {
+        let mut output = String::with_capacity(html.len());
+        let mut rest = html;
+
+        while let Some((start, host)) = self.find_url(rest) {
+            let end = start + url_length(&rest[start..]);
+            let escaped_url = &rest[start..end];
+            let url = escaped_url.replace("&amp;", "&");
+
+            output.push_str(&rest[..start]);
+
+            if url.contains("Signature=") {
+                output.push_str(escaped_url);
+            } else {
+                let path = &url[url.find(host).map_or(0, |index| index + host.len())..];
+                let signed_url = self
+                    .signer
+                    .expiry_after(self.ttl_for(path))
+                    .and_then(|expiry| {
+                        self.signer.create_canned_policy_signature_url(&url, expiry)
+                    });
+
+                match signed_url {
+                    Ok(signed_url) => output.push_str(&signed_url.replace('&', "&amp;")),
+                    Err(e) => {
+                        warn!("Left the URL {} unsigned due to {}", url, e);
+                        output.push_str(escaped_url);
+                    }
+                }
+            }
+
+            rest = &rest[end..];
+        }
+
+        output.push_str(rest);
+
+        Ok(output)
+    }
+
+    /// Returns the byte offset and host of the first URL in `html` pointing to a configured host
+    fn find_url(&self, html: &str) -> Option<(usize, &str)> {
+        self.hosts
+            .iter()
+            .flat_map(|host| {
+                ["https://", "http://"].iter().filter_map(move |scheme| {
+                    let mut offset = 0;
+
+                    while let Some(index) = html[offset..].find(scheme) {
+                        let start = offset + index;
+                        let after_scheme = &html[start + scheme.len()..];
+
+                        if after_scheme.starts_with(host.as_str())
+                            && after_scheme[host.len()..]
+                                .chars()
+                                .next()
+                                .is_none_or(|c| !c.is_alphanumeric() && c != '.' && c != '-')
+                        {
+                            return Some((start, host.as_str()));
+                        }
+
+                        offset = start + scheme.len();
+                    }
+
+                    None
+                })
+            })
+            .min_by_key(|(start, _)| *start)
+    }
+
+    /// Returns the TTL of the longest configured prefix matching the path
+    fn ttl_for(&self, path: &str) -> Duration {
+        self.path_ttls
+            .iter()
+            .filter(|(prefix, _)| path.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map_or(self.default_ttl, |(_, ttl)| *ttl)
+    }
+}
+
+/// Returns the length of the URL at the start of the text, see [`HtmlRewriter::rewrite`]
+fn url_length(text: &str) -> usize {
+    text.char_indices()
+        .find(|&(index, c)| {
+            c.is_whitespace()
+                || matches!(c, '"' | '\'' | '<' | '>' | '`' | ')' | ',')
+                || (c == ';' && !text[..index].ends_with("&amp"))
+        })
+        .map_or(text.len(), |(index, _)| index)
+}
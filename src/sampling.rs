@@ -0,0 +1,159 @@
+/*
+MIT License
+
+Copyright (c) 2020 Martin Karlsen Jensen
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+//! Sampling of issued signed URLs and cookies for analytics
+//!
+//! A [`SampleSink`] registered with [`crate::CloudFrontSigner::sampling`] receives a [`Sample`]
+//! for a configurable part of everything the signer issues. Resources are hashed, so the samples
+//! show how access is granted without logging the URLs themselves.
+//!
+//! ```
+//! use cloudfront_policy_signer::sampling::{Sample, SampleSink};
+//! use cloudfront_policy_signer::CloudFrontSigner;
+//! use std::sync::{Arc, Mutex};
+//!
+//! #[derive(Default)]
+//! struct Collect(Mutex<Vec<Sample>>);
+//!
+//! impl SampleSink for Collect {
+//!     fn record(&self, sample: Sample) {
+//!         self.0.lock().unwrap().push(sample);
+//!     }
+//! }
+//!
+//! let sink = Arc::new(Collect::default());
+//! let signer = CloudFrontSigner::new("examples/key.pem", "APKAIEXAMPLE")
+//!     .unwrap()
+//!     .sampling(0.5, sink.clone());
+//!
+//! for _ in 0..4 {
+//!     signer
+//!         .for_caller("tenant-42")
+//!         .sign_url("https://example.cloudfront.net/flowerpot.png", 1700000000)
+//!         .unwrap();
+//! }
+//!
+//! assert_eq!(sink.0.lock().unwrap().len(), 2);
+//! ```
+
+use crate::caller::Attribution;
+use crate::PolicyKind;
+use openssl::hash::{hash, MessageDigest};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Receives the samples of issued signed URLs and cookies
+pub trait SampleSink: Send + Sync {
+    /// Records a sample. Called on the thread that signed, so it should not block
+    fn record(&self, sample: Sample);
+}
+
+/// A signed URL or signed cookies that were issued
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sample {
+    /// The hex encoded SHA-256 hash of the resource, for custom policies of the resources of all statements
+    pub resource_hash: String,
+    /// How long the grant was valid for when it was issued
+    pub ttl: Duration,
+    /// The caller tag given to [`crate::CloudFrontSigner::for_caller`]
+    pub caller: Option<String>,
+    /// The metadata given to [`crate::CallerSigner::metadata`], in the order it was given
+    pub metadata: Vec<(String, String)>,
+    /// Whether a canned or a custom policy was signed
+    pub kind: PolicyKind,
+}
+
+/// Decides which issued grants are sampled and passes them to the sink
+pub(crate) struct Sampler {
+    rate: f64,
+    sink: Arc<dyn SampleSink>,
+    issued: AtomicU64,
+}
+
+impl Sampler {
+    pub(crate) fn new(rate: f64, sink: Arc<dyn SampleSink>) -> Sampler {
+        Sampler {
+            rate: rate.clamp(0.0, 1.0),
+            sink,
+            issued: AtomicU64::new(0),
+        }
+    }
+
+    /// Records an issued grant if it falls within the sampling rate
+    ///
+    /// Grants are sampled evenly rather than randomly, see [`evenly_selected`].
+    ///
+    /// # Arguments
+    /// * `attribution` - The caller tag and metadata, if any
+    /// * `resources` - The resources the grant covers
+    /// * `expires` - The expiry of the grant as a unix timestamp
+    /// * `kind` - Whether a canned or a custom policy was signed
+    /// * `now` - The time the grant was issued at
+    pub(crate) fn issued(
+        &self,
+        attribution: Option<Attribution>,
+        resources: &[&str],
+        expires: u64,
+        kind: PolicyKind,
+        now: SystemTime,
+    ) {
+        if !evenly_selected(&self.issued, self.rate) {
+            return;
+        }
+
+        let resource_hash = match hash(MessageDigest::sha256(), resources.join("\n").as_bytes()) {
+            Ok(digest) => digest.iter().map(|byte| format!("{:02x}", byte)).collect(),
+            Err(e) => {
+                error!("Could not hash the resource of a sample due to {}", e);
+                return;
+            }
+        };
+        let ttl = (UNIX_EPOCH + Duration::from_secs(expires))
+            .duration_since(now)
+            .unwrap_or_default();
+
+        self.sink.record(Sample {
+            resource_hash,
+            ttl,
+            caller: attribution.map(|attribution| attribution.caller.to_string()),
+            metadata: attribution
+                .map_or_else(Vec::new, |attribution| attribution.metadata.to_vec()),
+            kind,
+        });
+    }
+}
+
+/// Counts an event and returns whether it is selected, selecting events evenly rather than randomly
+///
+/// eg. every tenth event at a rate of 0.1.
+///
+/// # Arguments
+/// * `counter` - The number of events so far
+/// * `rate` - The part of events that is selected, between 0 and 1
+pub(crate) fn evenly_selected(counter: &AtomicU64, rate: f64) -> bool {
+    let count = counter.fetch_add(1, Ordering::Relaxed) as f64;
+
+    ((count + 1.0) * rate).floor() > (count * rate).floor()
+}
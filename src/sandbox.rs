@@ -0,0 +1,309 @@
+/*
+MIT License
+
+Copyright (c) 2020 Martin Karlsen Jensen
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+//! Signing in a helper process, so the process handling requests never loads the private key
+//!
+//! [`SandboxedSigner`] spawns a helper, usually `cloudfront-sign sign-helper`, which loads the key
+//! and signs policy documents it receives over its standard input. A compromised web server can
+//! then still request signatures, but cannot read the key. The helper enforces its own allowed
+//! and denied prefixes, so they keep applying even to such a compromised server.
+//!
+//! Every request is a single line `sign <document>`, where the document is encoded with
+//! [`crate::base64`]. The helper answers with one of these lines:
+//! * `ok <signature>` - the signature, encoded with [`crate::base64`]
+//! * `denied <resource>` - the resource is not allowed by the prefixes of the helper
+//! * `error <message>` - the document could not be signed
+//!
+//! On unix the helper can be run as a dedicated user that can read the key, by setting
+//! `std::os::unix::process::CommandExt::uid` and `gid` on the command. The process starting
+//! the helper needs the privileges to do so.
+
+use crate::{
+    base64, canned_url, cookies, generate_canned_policy, keys, CloudFrontSigner, Error, Policy,
+    SignedCookies, SignedPolicy, SignedUrl, DEFAULT_MAX_URL_LENGTH,
+};
+use std::fmt;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::Mutex;
+
+/// Answers the signing requests read from the input until it is closed, see the [module documentation](self)
+///
+/// # Arguments
+/// * `signer` - The signer holding the private key
+/// * `input` - Where the requests are read from, usually standard input
+/// * `output` - Where the answers are written to, usually standard output
+///
+/// # Example
+/// ```
+/// use cloudfront_policy_signer::{base64, sandbox, CloudFrontSigner};
+/// use std::io::Cursor;
+///
+/// let signer = CloudFrontSigner::new("examples/key.pem", "APKAIEXAMPLE")
+///     .unwrap()
+///     .denied_prefixes(["https://example.cloudfront.net/internal/"]);
+/// let document = r#"{"Statement":[{"Resource":"https://example.cloudfront.net/internal/a","Condition":{"DateLessThan":{"AWS:EpochTime":1700000000}}}]}"#;
+/// let unchecked = r#"{"Condition":{"DateLessThan":{"AWS:EpochTime":1700000000}}}"#;
+/// let input = format!(
+///     "sign {}\nsign {}\n",
+///     base64::encode(document.as_bytes()),
+///     base64::encode(unchecked.as_bytes())
+/// );
+/// let mut output = Vec::new();
+///
+/// sandbox::serve(&signer, Cursor::new(input), &mut output).unwrap();
+///
+/// let answers = String::from_utf8(output).unwrap();
+/// let mut answers = answers.lines();
+///
+/// assert_eq!(answers.next(), Some("denied https://example.cloudfront.net/internal/a"));
+/// assert!(answers.next().unwrap().starts_with("error "));
+/// ```
+pub fn serve<R: BufRead, W: Write>(
+    signer: &CloudFrontSigner,
+    input: R,
+    mut output: W,
+) -> Result<(), Error> {
+    for line in input.lines() {
+        let line = line.map_err(|e| {
+            error!("Could not read signing request due to {}", e);
+            Error::IOError(e)
+        })?;
+        let document = match line.strip_prefix("sign ") {
+            Some(document) => base64::decode(document)
+                .map_err(|_| Error::InvalidPolicy("the document is not valid base64".to_string())),
+            None => Err(Error::InvalidPolicy("unknown request".to_string())),
+        };
+        let answer = match document.and_then(|document| signer.sign_document(&document)) {
+            Ok(signature) => format!("ok {}\n", base64::encode(&signature)),
+            Err(Error::ResourceNotAllowed(resource)) => format!("denied {}\n", resource),
+            Err(e) => format!("error {}\n", e.to_string().replace('\n', " ")),
+        };
+
+        output
+            .write_all(answer.as_bytes())
+            .and_then(|_| output.flush())
+            .map_err(|e| {
+                error!("Could not write signing answer due to {}", e);
+                Error::IOError(e)
+            })?;
+    }
+
+    Ok(())
+}
+
+/// Signs URLs, cookies and policies by sending the documents to a helper process, see the [module documentation](self)
+///
+/// Requests are sent one at a time. The helper is killed when the signer is dropped.
+///
+/// # Example
+/// ```no_run
+/// use cloudfront_policy_signer::sandbox::SandboxedSigner;
+/// use std::process::Command;
+///
+/// let mut helper = Command::new("cloudfront-sign");
+///
+/// helper.args(["sign-helper", "--key", "/etc/cloudfront/key.pem", "--key-pair-id", "APKAIEXAMPLE"]);
+///
+/// let signer = SandboxedSigner::spawn(helper, "APKAIEXAMPLE").unwrap();
+/// let url = signer
+///     .sign_url("https://example.cloudfront.net/flowerpot.png", 1700000000)
+///     .unwrap();
+/// ```
+pub struct SandboxedSigner {
+    helper: Mutex<Helper>,
+    key_pair_id: String,
+    max_url_length: usize,
+}
+
+struct Helper {
+    child: Child,
+    input: ChildStdin,
+    output: BufReader<ChildStdout>,
+}
+
+impl Drop for Helper {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+impl fmt::Debug for SandboxedSigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pid = self
+            .helper
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .child
+            .id();
+
+        f.debug_struct("SandboxedSigner")
+            .field("helper", &pid)
+            .field("key_pair_id", &self.key_pair_id)
+            .field("max_url_length", &self.max_url_length)
+            .finish()
+    }
+}
+
+impl SandboxedSigner {
+    /// Spawns the helper process
+    ///
+    /// # Arguments
+    /// * `command` - The command starting the helper, its standard input and output are replaced by pipes
+    /// * `key_pair_id` - The key pair ID from AWS CloudFront, matching the key of the helper
+    pub fn spawn<T: ToString>(
+        mut command: Command,
+        key_pair_id: T,
+    ) -> Result<SandboxedSigner, Error> {
+        let key_pair_id = keys::normalize_key_pair_id(&key_pair_id.to_string())?;
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| {
+                error!("Could not spawn signing helper due to {}", e);
+                Error::IOError(e)
+            })?;
+        let (Some(input), Some(output)) = (child.stdin.take(), child.stdout.take()) else {
+            error!("Signing helper has no pipes");
+            return Err(Error::Unknown);
+        };
+
+        Ok(SandboxedSigner {
+            helper: Mutex::new(Helper {
+                child,
+                input,
+                output: BufReader::new(output),
+            }),
+            key_pair_id,
+            max_url_length: DEFAULT_MAX_URL_LENGTH,
+        })
+    }
+
+    /// Sets the maximum length of signed URLs, see [`CloudFrontSigner::max_url_length`]
+    ///
+    /// # Arguments
+    /// * `limit` - The maximum length in bytes
+    pub fn max_url_length(mut self, limit: usize) -> SandboxedSigner {
+        self.max_url_length = limit;
+        self
+    }
+
+    /// Returns the key pair ID the helper signs with
+    pub fn key_pair_id(&self) -> &str {
+        &self.key_pair_id
+    }
+
+    /// Creates a URL with a canned policy, see [`CloudFrontSigner::sign_url`]
+    ///
+    /// # Arguments
+    /// * `resource` - The protected resource eg. https://example.cloudfront.net/flowerpot.png
+    /// * `expiry` - Absolute time that the URL expires, given in the form of a unix timestamp in UTC
+    pub fn sign_url<R: AsRef<str>>(&self, resource: R, expiry: u64) -> Result<SignedUrl, Error> {
+        let resource = resource.as_ref();
+        let signature = self.sign_document(&generate_canned_policy(resource, expiry))?;
+
+        canned_url(
+            resource,
+            expiry,
+            &signature,
+            &self.key_pair_id,
+            self.max_url_length,
+        )
+    }
+
+    /// Creates cookies with a canned policy, see [`CloudFrontSigner::create_canned_policy_signed_cookies`]
+    ///
+    /// # Arguments
+    /// * `resource` - The protected resource eg. https://example.cloudfront.net/flowerpot.png
+    /// * `expiry` - Absolute time that the cookies expire, given in the form of a unix timestamp in UTC
+    pub fn create_canned_policy_signed_cookies<R: AsRef<str>>(
+        &self,
+        resource: R,
+        expiry: u64,
+    ) -> Result<SignedCookies, Error> {
+        let resource = resource.as_ref();
+        let signature = self.sign_document(&generate_canned_policy(resource, expiry))?;
+
+        Ok(SignedCookies::canned(
+            expiry,
+            base64::encode(&signature),
+            &self.key_pair_id,
+            resource,
+        ))
+    }
+
+    /// Signs a custom policy, see [`CloudFrontSigner::sign_policy`]
+    ///
+    /// # Arguments
+    /// * `policy` - The policy to sign
+    pub fn sign_policy(&self, policy: &Policy) -> Result<SignedPolicy, Error> {
+        let document = policy.to_json();
+        let signature = self.sign_document(document.as_bytes())?;
+
+        Ok(SignedPolicy::new(
+            base64::encode(document.as_bytes()),
+            signature,
+            policy.validity(),
+            self.key_pair_id.clone(),
+            cookies::cookie_path(
+                policy
+                    .statements
+                    .iter()
+                    .map(|statement| Some(statement.resource.as_str())),
+            ),
+            self.max_url_length,
+        ))
+    }
+
+    /// Sends a policy document to the helper and returns the raw signature
+    fn sign_document(&self, document: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut helper = self.helper.lock().unwrap_or_else(|e| e.into_inner());
+        let io_error = |e| {
+            error!("Could not communicate with signing helper due to {}", e);
+            Error::IOError(e)
+        };
+        let mut answer = String::new();
+
+        writeln!(helper.input, "sign {}", base64::encode(document)).map_err(io_error)?;
+        helper.input.flush().map_err(io_error)?;
+
+        if helper.output.read_line(&mut answer).map_err(io_error)? == 0 {
+            error!("Signing helper exited");
+            return Err(Error::CouldNotSign);
+        }
+
+        let answer = answer.trim_end();
+
+        if let Some(signature) = answer.strip_prefix("ok ") {
+            base64::decode(signature)
+        } else if let Some(resource) = answer.strip_prefix("denied ") {
+            Err(Error::ResourceNotAllowed(resource.to_string()))
+        } else {
+            error!("Signing helper answered {}", answer);
+            Err(Error::CouldNotSign)
+        }
+    }
+}
@@ -0,0 +1,676 @@
+/*
+MIT License
+
+Copyright (c) 2020 Martin Karlsen Jensen
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+//! Policies that are only valid during scheduled windows, eg. a ticketed live stream
+//!
+//! A [`Schedule`] describes an event window or recurring weekly hours in the local time of a
+//! [`TimeZone`], and turns the current or next window into the `DateGreaterThan` and
+//! `DateLessThan` of a policy. Time zones are read from the tz database of the system, so
+//! daylight saving time is taken into account.
+//!
+//! ```
+//! use cloudfront_policy_signer::schedule::{Schedule, TimeZone};
+//! use cloudfront_policy_signer::CloudFrontSigner;
+//! use std::time::{Duration, UNIX_EPOCH};
+//!
+//! let zone = TimeZone::fixed(2 * 60 * 60);
+//! let schedule = Schedule::event(zone, "2030-06-01T20:00", "2030-06-01T23:30").unwrap();
+//! let policy = schedule
+//!     .policy_builder("https://example.cloudfront.net/live/*", UNIX_EPOCH + Duration::from_secs(1700000000))
+//!     .unwrap()
+//!     .build()
+//!     .unwrap();
+//!
+//! assert_eq!(policy.statements[0].condition.date_greater_than, Some(1906567200));
+//! assert_eq!(policy.statements[0].condition.date_less_than, 1906579800);
+//!
+//! let signer = CloudFrontSigner::new("examples/key.pem", "APKAIEXAMPLE").unwrap();
+//! let cookies = signer.sign_policy(&policy).unwrap().cookies();
+//!
+//! assert_eq!(cookies.expires_at(), 1906579800);
+//! ```
+
+use crate::{Error, Policy, PolicyBuilder};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::{env, fs};
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// A time zone, either with a fixed offset or with the rules of the tz database
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimeZone {
+    /// The offset before the first transition, in seconds east of UTC
+    initial: i32,
+    /// The times at which the offset changes, with the offset from then on
+    transitions: Vec<(i64, i32)>,
+    /// The daylight saving time rule for times after the last transition
+    rule: Option<DstRule>,
+}
+
+/// A daylight saving time rule from the POSIX TZ string at the end of a TZif file
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DstRule {
+    std_offset: i32,
+    dst_offset: i32,
+    start: RuleDate,
+    end: RuleDate,
+}
+
+/// A transition of a [`DstRule`]: the `day` of week `week` of `month`, at `time` seconds local time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct RuleDate {
+    month: u32,
+    week: u32,
+    day: u32,
+    time: i64,
+}
+
+impl TimeZone {
+    /// Returns the UTC time zone
+    pub fn utc() -> TimeZone {
+        TimeZone::fixed(0)
+    }
+
+    /// Returns a time zone with a fixed offset and without daylight saving time
+    ///
+    /// # Arguments
+    /// * `offset` - The offset in seconds east of UTC eg. 3600 for UTC+1
+    pub fn fixed(offset: i32) -> TimeZone {
+        TimeZone {
+            initial: offset,
+            transitions: Vec::new(),
+            rule: None,
+        }
+    }
+
+    /// Reads a time zone from the tz database of the system
+    ///
+    /// The database is read from the directory in the `TZDIR` environment variable, or
+    /// /usr/share/zoneinfo.
+    ///
+    /// # Arguments
+    /// * `name` - The name of the time zone eg. Europe/Amsterdam
+    pub fn named(name: &str) -> Result<TimeZone, Error> {
+        if name.is_empty() || name.split('/').any(|part| part.is_empty() || part == "..") {
+            return Err(Error::InvalidConfig(format!(
+                "'{}' is not a time zone name",
+                name
+            )));
+        }
+
+        let directory = env::var_os("TZDIR").unwrap_or_else(|| "/usr/share/zoneinfo".into());
+        let tzif = fs::read(Path::new(&directory).join(name)).map_err(|e| {
+            error!("Could not read the time zone {} due to {}", name, e);
+            Error::InvalidConfig(format!("the time zone '{}' could not be read", name))
+        })?;
+
+        TimeZone::from_tzif(&tzif)
+    }
+
+    /// Parses a time zone from a file in the TZif format of the tz database
+    ///
+    /// # Arguments
+    /// * `tzif` - The contents of the file eg. /usr/share/zoneinfo/Europe/Amsterdam
+    pub fn from_tzif(tzif: &[u8]) -> Result<TimeZone, Error> {
+        parse_tzif(tzif).ok_or_else(|| {
+            error!("Could not parse a TZif file of {} bytes", tzif.len());
+            Error::InvalidConfig("the time zone is not a valid TZif file".to_string())
+        })
+    }
+
+    /// Returns the offset from UTC in seconds at the given time
+    ///
+    /// # Arguments
+    /// * `time` - The time as a unix timestamp
+    pub fn offset_at(&self, time: i64) -> i32 {
+        let index = self.transitions.partition_point(|&(at, _)| at <= time);
+
+        match (&self.rule, self.transitions.get(index)) {
+            (Some(rule), None) => rule.offset_at(time),
+            _ => index
+                .checked_sub(1)
+                .map_or(self.initial, |index| self.transitions[index].1),
+        }
+    }
+
+    /// Converts a local date and time in this time zone to a unix timestamp
+    ///
+    /// Times skipped when clocks go forward are moved forward by the change, and times occurring
+    /// twice when clocks go back resolve to the first occurrence.
+    ///
+    /// # Arguments
+    /// * `local` - The local date and time eg. 2030-06-01T20:00 or 2030-06-01 20:00:30
+    pub fn to_unix(&self, local: &str) -> Result<u64, Error> {
+        let invalid = || {
+            Error::InvalidConfig(format!(
+                "'{}' is not a local date and time such as 2030-06-01T20:00",
+                local
+            ))
+        };
+        let (date, time) = local.split_once(['T', ' ']).ok_or_else(invalid)?;
+        let mut date = date.splitn(3, '-').map(|part| part.parse::<i64>().ok());
+        let (year, month, day) = match (date.next(), date.next(), date.next()) {
+            (Some(Some(year)), Some(Some(month)), Some(Some(day)))
+                if (1..=9999).contains(&year)
+                    && (1..=12).contains(&month)
+                    && day >= 1
+                    && day <= days_in_month(year, month) =>
+            {
+                (year, month, day)
+            }
+            _ => return Err(invalid()),
+        };
+        let time = parse_time_of_day(time).ok_or_else(invalid)?;
+
+        u64::try_from(self.resolve(days_from_civil(year, month, day) * SECONDS_PER_DAY + time))
+            .map_err(|_| invalid())
+    }
+
+    /// Returns the unix timestamp of a local time given in seconds since the epoch
+    fn resolve(&self, local: i64) -> i64 {
+        let before = self.offset_at(local - SECONDS_PER_DAY);
+        let after = self.offset_at(local + SECONDS_PER_DAY);
+        let mut candidates = [before, after];
+
+        candidates.sort_unstable_by(|a, b| b.cmp(a));
+
+        candidates
+            .iter()
+            .map(|&offset| local - i64::from(offset))
+            .find(|&time| local - i64::from(self.offset_at(time)) == time)
+            .unwrap_or(local - i64::from(before))
+    }
+}
+
+impl DstRule {
+    fn offset_at(&self, time: i64) -> i32 {
+        let (year, _, _) =
+            civil_from_days((time + i64::from(self.std_offset)).div_euclid(SECONDS_PER_DAY));
+        let start = self.start.local_time(year) - i64::from(self.std_offset);
+        let end = self.end.local_time(year) - i64::from(self.dst_offset);
+        let dst = if start < end {
+            start <= time && time < end
+        } else {
+            !(end <= time && time < start)
+        };
+
+        if dst {
+            self.dst_offset
+        } else {
+            self.std_offset
+        }
+    }
+}
+
+impl RuleDate {
+    /// Returns the local time of the transition in the given year, in seconds since the epoch
+    fn local_time(&self, year: i64) -> i64 {
+        let month = i64::from(self.month);
+        let first = days_from_civil(year, month, 1);
+        let first_day = weekday(first);
+        let mut day =
+            1 + (i64::from(self.day) - first_day).rem_euclid(7) + 7 * (i64::from(self.week) - 1);
+
+        while day > days_in_month(year, month) {
+            day -= 7;
+        }
+
+        (first + day - 1) * SECONDS_PER_DAY + self.time
+    }
+}
+
+/// A day of the week
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Weekday {
+    /// Monday
+    Monday,
+    /// Tuesday
+    Tuesday,
+    /// Wednesday
+    Wednesday,
+    /// Thursday
+    Thursday,
+    /// Friday
+    Friday,
+    /// Saturday
+    Saturday,
+    /// Sunday
+    Sunday,
+}
+
+impl Weekday {
+    /// Monday to Friday
+    pub const WORKDAYS: [Weekday; 5] = [
+        Weekday::Monday,
+        Weekday::Tuesday,
+        Weekday::Wednesday,
+        Weekday::Thursday,
+        Weekday::Friday,
+    ];
+
+    /// Returns the day of the week for a number of days since the epoch, which was a Thursday
+    fn from_days(days: i64) -> Weekday {
+        match weekday(days) {
+            0 => Weekday::Sunday,
+            1 => Weekday::Monday,
+            2 => Weekday::Tuesday,
+            3 => Weekday::Wednesday,
+            4 => Weekday::Thursday,
+            5 => Weekday::Friday,
+            _ => Weekday::Saturday,
+        }
+    }
+}
+
+/// When access is granted, in the local time of a time zone
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schedule {
+    zone: TimeZone,
+    kind: ScheduleKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ScheduleKind {
+    Event {
+        start: u64,
+        end: u64,
+    },
+    Weekly {
+        days: Vec<Weekday>,
+        start: i64,
+        end: i64,
+    },
+}
+
+impl Schedule {
+    /// Grants access during a single window, eg. a live event
+    ///
+    /// # Arguments
+    /// * `zone` - The time zone of the start and end
+    /// * `start` - The local date and time access starts eg. 2030-06-01T20:00
+    /// * `end` - The local date and time access ends eg. 2030-06-01T23:30
+    pub fn event(zone: TimeZone, start: &str, end: &str) -> Result<Schedule, Error> {
+        let start = zone.to_unix(start)?;
+        let end = zone.to_unix(end)?;
+
+        if start >= end {
+            return Err(Error::InvalidConfig(
+                "the event ends before it starts".to_string(),
+            ));
+        }
+
+        Ok(Schedule {
+            zone,
+            kind: ScheduleKind::Event { start, end },
+        })
+    }
+
+    /// Grants access during the same hours on the given days of every week, eg. business hours
+    ///
+    /// An end before the start means the window ends the next day.
+    ///
+    /// # Arguments
+    /// * `zone` - The time zone of the hours
+    /// * `days` - The days the window starts on eg. [`Weekday::WORKDAYS`]
+    /// * `start` - The local time access starts eg. 09:00
+    /// * `end` - The local time access ends eg. 17:30
+    ///
+    /// # Example
+    /// ```
+    /// use cloudfront_policy_signer::schedule::{Schedule, TimeZone, Weekday};
+    /// use std::time::{Duration, UNIX_EPOCH};
+    ///
+    /// let schedule = Schedule::weekly(TimeZone::utc(), &Weekday::WORKDAYS, "09:00", "17:30").unwrap();
+    ///
+    /// // Saturday 2023-11-18 12:00 UTC, so the next window is on Monday
+    /// let window = schedule.window_at(UNIX_EPOCH + Duration::from_secs(1700308800));
+    ///
+    /// assert_eq!(window, Some((1700470800, 1700501400)));
+    /// ```
+    pub fn weekly(
+        zone: TimeZone,
+        days: &[Weekday],
+        start: &str,
+        end: &str,
+    ) -> Result<Schedule, Error> {
+        let time = |time: &str| {
+            parse_time_of_day(time).ok_or_else(|| {
+                Error::InvalidConfig(format!("'{}' is not a time of day such as 09:00", time))
+            })
+        };
+        let start = time(start)?;
+        let mut end = time(end)?;
+
+        if end <= start {
+            end += SECONDS_PER_DAY;
+        }
+
+        if days.is_empty() {
+            return Err(Error::InvalidConfig("the schedule has no days".to_string()));
+        }
+
+        Ok(Schedule {
+            zone,
+            kind: ScheduleKind::Weekly {
+                days: days.to_vec(),
+                start,
+                end,
+            },
+        })
+    }
+
+    /// Returns the window that is open at the given time, or else the next one, as unix timestamps
+    ///
+    /// Returns `None` once an event has ended.
+    ///
+    /// # Arguments
+    /// * `now` - The current time, usually `SystemTime::now()`
+    pub fn window_at(&self, now: SystemTime) -> Option<(u64, u64)> {
+        let now = now
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since_epoch| since_epoch.as_secs());
+
+        match &self.kind {
+            ScheduleKind::Event { start, end } => (now < *end).then_some((*start, *end)),
+            ScheduleKind::Weekly { days, start, end } => {
+                let local = i64::try_from(now).ok()? + i64::from(self.zone.offset_at(now as i64));
+                let today = local.div_euclid(SECONDS_PER_DAY);
+
+                (today - 1..=today + 7)
+                    .filter(|&day| days.contains(&Weekday::from_days(day)))
+                    .map(|day| {
+                        let midnight = day * SECONDS_PER_DAY;
+
+                        (
+                            self.zone.resolve(midnight + start),
+                            self.zone.resolve(midnight + end),
+                        )
+                    })
+                    .find(|&(_, end)| end > now as i64)
+                    .and_then(|(start, end)| {
+                        Some((u64::try_from(start).ok()?, u64::try_from(end).ok()?))
+                    })
+            }
+        }
+    }
+
+    /// Returns a policy builder for the resource whose validity is the current or next window
+    ///
+    /// Fails with [`Error::InvalidPolicy`] once an event has ended. Further conditions, such as
+    /// IP addresses, can be added to the builder.
+    ///
+    /// # Arguments
+    /// * `resource` - The protected resource eg. https://example.cloudfront.net/live/*
+    /// * `now` - The current time, usually `SystemTime::now()`
+    pub fn policy_builder<R: AsRef<str>>(
+        &self,
+        resource: R,
+        now: SystemTime,
+    ) -> Result<PolicyBuilder, Error> {
+        let (start, end) = self.window_at(now).ok_or_else(|| {
+            Error::InvalidPolicy("the schedule has no upcoming window".to_string())
+        })?;
+
+        Ok(Policy::builder(resource)
+            .valid_from(UNIX_EPOCH + std::time::Duration::from_secs(start))
+            .expires_at(end))
+    }
+}
+
+/// Parses a time of day such as 09:00 or 09:00:30 into seconds since midnight
+fn parse_time_of_day(time: &str) -> Option<i64> {
+    let mut parts = time.split(':').map(|part| part.parse::<i64>().ok());
+    let hours = parts.next()??;
+    let minutes = parts.next()??;
+    let seconds = parts.next().unwrap_or(Some(0))?;
+
+    if parts.next().is_some()
+        || !(0..24).contains(&hours)
+        || !(0..60).contains(&minutes)
+        || !(0..60).contains(&seconds)
+    {
+        return None;
+    }
+
+    Some(hours * 60 * 60 + minutes * 60 + seconds)
+}
+
+/// Returns the number of days since the epoch of a date in the proleptic Gregorian calendar
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146097 + day_of_era - 719468
+}
+
+/// Returns the year, month and day of a number of days since the epoch
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
+
+/// Returns the day of the week of a number of days since the epoch, 0 being Sunday
+fn weekday(days: i64) -> i64 {
+    (days + 4).rem_euclid(7)
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Parses a TZif file, see RFC 8536
+fn parse_tzif(tzif: &[u8]) -> Option<TimeZone> {
+    let header = |data: &[u8]| -> Option<[usize; 6]> {
+        if data.get(..4)? != b"TZif" {
+            return None;
+        }
+
+        let mut counts = [0; 6];
+
+        for (index, count) in counts.iter_mut().enumerate() {
+            let start = 20 + index * 4;
+
+            *count = u32::from_be_bytes(data.get(start..start + 4)?.try_into().ok()?) as usize;
+
+            // Real files have a few hundred entries, the limit keeps the lengths below from overflowing
+            if *count > 1 << 20 {
+                return None;
+            }
+        }
+
+        Some(counts)
+    };
+    let block_len = |[isut, isstd, leap, time, kind, chars]: [usize; 6], time_len: usize| {
+        time * time_len + time + kind * 6 + chars + leap * (time_len + 4) + isstd + isut
+    };
+    let counts = header(tzif)?;
+    let version = *tzif.get(4)?;
+    let (data, counts, time_len) = if version >= b'2' {
+        let data = tzif.get(44 + block_len(counts, 4)..)?;
+
+        (data, header(data)?, 8)
+    } else {
+        (tzif, counts, 4)
+    };
+    let [_, _, _, time_count, type_count, _] = counts;
+    let body = data.get(44..44 + block_len(counts, time_len))?;
+    let times = &body[..time_count * time_len];
+    let indices = &body[time_count * time_len..time_count * (time_len + 1)];
+    let types = &body[time_count * (time_len + 1)..time_count * (time_len + 1) + type_count * 6];
+    let offset = |index: usize| -> Option<i32> {
+        Some(i32::from_be_bytes(
+            types.get(index * 6..index * 6 + 4)?.try_into().ok()?,
+        ))
+    };
+    let mut transitions = Vec::with_capacity(time_count);
+
+    for (index, &kind) in indices.iter().enumerate() {
+        let time = &times[index * time_len..(index + 1) * time_len];
+        let time = if time_len == 8 {
+            i64::from_be_bytes(time.try_into().ok()?)
+        } else {
+            i64::from(i32::from_be_bytes(time.try_into().ok()?))
+        };
+
+        transitions.push((time, offset(kind as usize)?));
+    }
+
+    let rule = if time_len == 8 {
+        let footer = data.get(44 + block_len(counts, time_len)..)?;
+        let footer = std::str::from_utf8(footer).ok()?.trim_matches('\n');
+
+        parse_posix_tz(footer)?
+    } else {
+        None
+    };
+
+    Some(TimeZone {
+        initial: offset(0)?,
+        transitions,
+        rule,
+    })
+}
+
+/// Parses the daylight saving time rule of a POSIX TZ string such as CET-1CEST,M3.5.0,M10.5.0/3
+///
+/// Returns `Some(None)` for time zones without daylight saving time and `None` for strings that
+/// can not be parsed, which includes the rarely used Julian day rules.
+fn parse_posix_tz(tz: &str) -> Option<Option<DstRule>> {
+    if tz.is_empty() {
+        return Some(None);
+    }
+
+    let mut rest = tz;
+    let name = |rest: &mut &str| -> Option<()> {
+        let len = if let Some(quoted) = rest.strip_prefix('<') {
+            quoted.find('>')? + 2
+        } else {
+            rest.find(|c: char| !c.is_ascii_alphabetic())
+                .unwrap_or(rest.len())
+        };
+
+        if len < 3 {
+            return None;
+        }
+
+        *rest = &rest[len..];
+        Some(())
+    };
+    // Offsets have at most 24 hours, the times of rules at most 167 as allowed by TZif version 3
+    let time = |rest: &mut &str, max_hours: i64| -> Option<i64> {
+        let len = rest
+            .find(|c: char| !(c.is_ascii_digit() || matches!(c, ':' | '+' | '-')))
+            .unwrap_or(rest.len());
+        let (sign, value) = match rest[..len].strip_prefix('-') {
+            Some(value) => (-1, value),
+            None => (1, rest[..len].strip_prefix('+').unwrap_or(&rest[..len])),
+        };
+        let mut parts = value.split(':').map(|part| {
+            (!part.is_empty() && part.len() <= 3 && part.bytes().all(|b| b.is_ascii_digit()))
+                .then(|| part.parse::<i64>().ok())
+                .flatten()
+        });
+        let hours = parts.next()??;
+        let minutes = parts.next().unwrap_or(Some(0))?;
+        let seconds = parts.next().unwrap_or(Some(0))?;
+
+        if parts.next().is_some() || hours > max_hours || minutes > 59 || seconds > 59 {
+            return None;
+        }
+
+        *rest = &rest[len..];
+        Some(sign * (hours * 60 * 60 + minutes * 60 + seconds))
+    };
+    let date = |rest: &mut &str| -> Option<RuleDate> {
+        let spec = rest.strip_prefix(",M")?;
+        let len = spec.find([',', '/']).unwrap_or(spec.len());
+        let mut parts = spec[..len].split('.').map(|part| part.parse::<u32>().ok());
+        let (month, week, day) = (parts.next()??, parts.next()??, parts.next()??);
+
+        if !(1..=12).contains(&month) || !(1..=5).contains(&week) || day > 6 {
+            return None;
+        }
+
+        *rest = &spec[len..];
+
+        let time = match rest.strip_prefix('/') {
+            Some(stripped) => {
+                *rest = stripped;
+                time(rest, 167)?
+            }
+            None => 2 * 60 * 60,
+        };
+
+        Some(RuleDate {
+            month,
+            week,
+            day,
+            time,
+        })
+    };
+
+    name(&mut rest)?;
+
+    let std_offset = i32::try_from(time(&mut rest, 24)?).ok()?.checked_neg()?;
+
+    if rest.is_empty() {
+        return Some(None);
+    }
+
+    name(&mut rest)?;
+
+    let dst_offset = if rest.starts_with(',') {
+        std_offset.checked_add(60 * 60)?
+    } else {
+        i32::try_from(time(&mut rest, 24)?).ok()?.checked_neg()?
+    };
+    let start = date(&mut rest)?;
+    let end = date(&mut rest)?;
+
+    rest.is_empty().then_some(Some(DstRule {
+        std_offset,
+        dst_offset,
+        start,
+        end,
+    }))
+}
@@ -0,0 +1,71 @@
+/*
+MIT License
+
+Copyright (c) 2020 Martin Karlsen Jensen
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use crate::SignedPolicy;
+use std::ops::Deref;
+use std::sync::Arc;
+
+/// A signed policy meant to be handed to many viewers, created by [`crate::CloudFrontSigner::sign_shared_policy`]
+///
+/// Cloning only increments a reference count, so one signature can be reused for every request,
+/// eg. for public beta content behind a wildcard. Policies restricted to IP addresses belong to a
+/// single viewer and cannot be shared. Policies granting per-user prefixes should not be shared
+/// either and are better signed per request with [`crate::CloudFrontSigner::sign_policy`].
+///
+/// # Example
+/// ```
+/// use cloudfront_policy_signer::{CloudFrontSigner, Policy};
+///
+/// let signer = CloudFrontSigner::new("examples/key.pem", "APKAIEXAMPLE").unwrap();
+/// let policy = Policy::builder("https://example.cloudfront.net/beta/*")
+///     .expires_at(1700000000)
+///     .build()
+///     .unwrap();
+/// let shared = signer.sign_shared_policy(&policy).unwrap();
+/// let for_request = shared.clone();
+///
+/// for_request.url("https://example.cloudfront.net/beta/app.js").unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SharedPolicy(Arc<SignedPolicy>);
+
+impl SharedPolicy {
+    pub(crate) fn new(signed_policy: SignedPolicy) -> SharedPolicy {
+        SharedPolicy(Arc::new(signed_policy))
+    }
+}
+
+impl Deref for SharedPolicy {
+    type Target = SignedPolicy;
+
+    fn deref(&self) -> &SignedPolicy {
+        &self.0
+    }
+}
+
+impl AsRef<SignedPolicy> for SharedPolicy {
+    fn as_ref(&self) -> &SignedPolicy {
+        &self.0
+    }
+}
@@ -0,0 +1,162 @@
+/*
+MIT License
+
+Copyright (c) 2020 Martin Karlsen Jensen
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use crate::signed_url::{Grant, REDACTED};
+use crate::{base64, check_url_length, Error, PolicyKind, SignedCookies, SignedUrl};
+use openssl::base64::encode_block;
+use std::fmt;
+
+/// A custom policy signed with a key pair
+///
+/// The same signed policy can be attached to any URL it grants access to, or be sent as cookies.
+/// The `Debug` output redacts the signature.
+#[derive(Clone, PartialEq, Eq)]
+pub struct SignedPolicy {
+    policy: String,
+    signature: String,
+    raw_signature: Vec<u8>,
+    grant: Grant,
+    cookie_path: String,
+    max_url_length: usize,
+}
+
+impl SignedPolicy {
+    /// Constructs a signed policy from the URL safe encoded policy and its signature
+    ///
+    /// # Arguments
+    /// * `policy` - The URL safe encoded policy
+    /// * `signature` - The signature of the policy as raw bytes
+    /// * `validity` - The earliest expiry and latest start of the statements of the policy
+    /// * `key_pair_id` - The key pair ID from AWS CloudFront
+    /// * `cookie_path` - The default path of cookies, see [`SignedCookies::path`]
+    /// * `max_url_length` - The maximum length of URLs created from the policy
+    pub(crate) fn new(
+        policy: String,
+        signature: Vec<u8>,
+        (expires, not_before): (u64, Option<u64>),
+        key_pair_id: String,
+        cookie_path: String,
+        max_url_length: usize,
+    ) -> SignedPolicy {
+        SignedPolicy {
+            policy,
+            signature: base64::encode(&signature),
+            raw_signature: signature,
+            grant: Grant {
+                expires,
+                not_before,
+                key_pair_id,
+                kind: PolicyKind::Custom,
+            },
+            cookie_path,
+            max_url_length,
+        }
+    }
+
+    /// Returns the policy in the URL safe encoding used by CloudFront
+    pub fn policy(&self) -> &str {
+        &self.policy
+    }
+
+    /// Returns the signature in the URL safe encoding used by CloudFront
+    pub fn signature(&self) -> &str {
+        &self.signature
+    }
+
+    /// Returns the signature in standard base64 with padding, eg. to store it in other systems
+    pub fn signature_base64(&self) -> String {
+        encode_block(&self.raw_signature)
+    }
+
+    /// Returns the raw bytes of the RSA-SHA1 signature
+    pub fn signature_bytes(&self) -> &[u8] {
+        &self.raw_signature
+    }
+
+    /// Returns the key pair ID the policy was signed with
+    pub fn key_pair_id(&self) -> &str {
+        &self.grant.key_pair_id
+    }
+
+    /// Returns the query parameters to append to a URL, in the order CloudFront documents them
+    pub fn query_parameters(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("Policy", self.policy.clone()),
+            ("Signature", self.signature.clone()),
+            ("Key-Pair-Id", self.grant.key_pair_id.clone()),
+        ]
+    }
+
+    /// Returns a signed URL for the resource
+    ///
+    /// Fails with [`Error::UrlTooLong`] if the URL exceeds the limit of the signer, in which case
+    /// the policy should be sent as cookies instead.
+    ///
+    /// # Arguments
+    /// * `resource` - The URL to sign. It must be matched by a statement of the policy for CloudFront to accept it
+    pub fn url<R: AsRef<str>>(&self, resource: R) -> Result<SignedUrl, Error> {
+        let resource = resource.as_ref();
+        let separator = if resource.contains('?') { '&' } else { '?' };
+        let url = format!(
+            "{}{}Policy={}&Signature={}&Key-Pair-Id={}",
+            resource, separator, self.policy, self.signature, self.grant.key_pair_id
+        );
+
+        Ok(SignedUrl::new(
+            check_url_length(url, self.max_url_length)?,
+            self.grant.clone(),
+        ))
+    }
+
+    /// Returns the cookies granting access to every resource matched by the policy
+    pub fn cookies(&self) -> SignedCookies {
+        SignedCookies::custom(
+            self.policy.clone(),
+            self.signature.clone(),
+            self.grant.clone(),
+            self.cookie_path.clone(),
+        )
+    }
+}
+
+/// Both delivery methods of a signed policy, created by [`crate::CloudFrontSigner::sign_policy_artifacts`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyArtifacts {
+    /// The query parameters for signed URLs, see [`SignedPolicy::query_parameters`]
+    pub query_parameters: Vec<(&'static str, String)>,
+    /// The signed cookies
+    pub cookies: SignedCookies,
+}
+
+impl fmt::Debug for SignedPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SignedPolicy")
+            .field("policy", &self.policy)
+            .field("signature", &REDACTED)
+            .field("grant", &self.grant)
+            .field("cookie_path", &self.cookie_path)
+            .field("max_url_length", &self.max_url_length)
+            .finish()
+    }
+}
@@ -0,0 +1,272 @@
+/*
+MIT License
+
+Copyright (c) 2020 Martin Karlsen Jensen
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use crate::verify::ParsedSignedUrl;
+use crate::PolicyKind;
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// What a signed URL or signed cookies grant, recorded when signing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Grant {
+    /// The earliest `DateLessThan` of the policy
+    pub(crate) expires: u64,
+    /// The latest `DateGreaterThan` of the policy
+    pub(crate) not_before: Option<u64>,
+    pub(crate) key_pair_id: String,
+    pub(crate) kind: PolicyKind,
+}
+
+impl Grant {
+    pub(crate) fn is_expired(&self, now: SystemTime) -> bool {
+        self.remaining(now).is_zero()
+    }
+
+    pub(crate) fn remaining(&self, now: SystemTime) -> Duration {
+        (UNIX_EPOCH + Duration::from_secs(self.expires))
+            .duration_since(now)
+            .unwrap_or_default()
+    }
+}
+
+/// Placeholder for signatures in `Debug` output and redacted URLs
+pub(crate) const REDACTED: &str = "<redacted>";
+
+/// Returns the URL with the value of its `Signature` parameter replaced by a placeholder
+///
+/// The last parameter is redacted, as signing appends its parameters after those of the resource.
+///
+/// # Arguments
+/// * `url` - A signed URL
+pub(crate) fn redact_signature(url: &str) -> String {
+    let Some(start) = url
+        .rmatch_indices("Signature=")
+        .map(|(index, name)| index + name.len())
+        .find(|&start| {
+            matches!(
+                url[..start - "Signature=".len()].chars().last(),
+                Some('?' | '&')
+            )
+        })
+    else {
+        return url.to_string();
+    };
+    let end = url[start..].find('&').map_or(url.len(), |end| start + end);
+
+    format!("{}{}{}", &url[..start], REDACTED, &url[end..])
+}
+
+/// A signed URL together with what it grants access to
+///
+/// The `Debug` output redacts the signature, while `Display` shows the complete URL.
+#[derive(Clone, PartialEq, Eq)]
+pub struct SignedUrl {
+    url: String,
+    grant: Grant,
+}
+
+impl SignedUrl {
+    pub(crate) fn new(url: String, grant: Grant) -> SignedUrl {
+        SignedUrl { url, grant }
+    }
+
+    /// Returns the signed URL
+    pub fn as_str(&self) -> &str {
+        &self.url
+    }
+
+    /// Returns the signed URL with its signature redacted, for logging
+    pub fn redacted(&self) -> String {
+        redact_signature(&self.url)
+    }
+
+    /// Replaces the URL while keeping what it grants access to, eg. with a short link redirecting to it
+    ///
+    /// # Arguments
+    /// * `url` - The new URL
+    pub fn with_url<T: Into<String>>(mut self, url: T) -> SignedUrl {
+        self.url = url.into();
+        self
+    }
+
+    /// Returns the signed URL, consuming the metadata
+    pub fn into_string(self) -> String {
+        self.url
+    }
+
+    /// Returns the absolute time access expires, given in the form of a unix timestamp in UTC
+    ///
+    /// For custom policies with several statements this is the earliest expiry of any statement.
+    pub fn expires_at(&self) -> u64 {
+        self.grant.expires
+    }
+
+    /// Returns the absolute time access starts, given in the form of a unix timestamp in UTC
+    pub fn not_before(&self) -> Option<u64> {
+        self.grant.not_before
+    }
+
+    /// Returns the key pair ID the URL was signed with
+    pub fn key_pair_id(&self) -> &str {
+        &self.grant.key_pair_id
+    }
+
+    /// Returns whether the URL was signed with a canned or a custom policy
+    pub fn kind(&self) -> PolicyKind {
+        self.grant.kind
+    }
+
+    /// Whether the URL has expired at the given time
+    ///
+    /// Pass a time ahead of now, eg. `SystemTime::now() + Duration::from_secs(30)`, to treat it as
+    /// expired early when the local clock may lag behind the one at AWS. The signed expiry is not
+    /// affected.
+    ///
+    /// # Arguments
+    /// * `now` - The current time, usually `SystemTime::now()`
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        self.grant.is_expired(now)
+    }
+
+    /// Returns how long the URL is still valid for at the given time, zero if it has expired
+    ///
+    /// # Arguments
+    /// * `now` - The current time, usually `SystemTime::now()`
+    pub fn remaining(&self, now: SystemTime) -> Duration {
+        self.grant.remaining(now)
+    }
+
+    /// Whether both URLs request the same resource under the same policy, ignoring the signatures
+    ///
+    /// A canned policy and the equivalent custom policy grant the same access. The key pair ID is
+    /// ignored as well, so URLs signed before and after a key rotation compare equal as long as
+    /// CloudFront trusts both keys.
+    ///
+    /// # Arguments
+    /// * `other` - The URL to compare with
+    ///
+    /// # Example
+    /// ```
+    /// use cloudfront_policy_signer::{CloudFrontSigner, Policy};
+    ///
+    /// let signer = CloudFrontSigner::new("examples/key.pem", "APKAIEXAMPLE").unwrap();
+    /// let resource = "https://example.cloudfront.net/flowerpot.png";
+    /// let canned = signer.sign_url(resource, 1700000000).unwrap();
+    /// let custom = signer
+    ///     .sign_policy(&Policy::canned(resource, 1700000000))
+    ///     .unwrap()
+    ///     .url(resource)
+    ///     .unwrap();
+    ///
+    /// assert!(canned.grants_same_access(&custom));
+    /// assert!(!canned.grants_same_access(&signer.sign_url(resource, 1700000001).unwrap()));
+    /// ```
+    pub fn grants_same_access(&self, other: &SignedUrl) -> bool {
+        let access = |signed_url: &SignedUrl| {
+            let parsed = ParsedSignedUrl::parse(&signed_url.url).ok()?;
+            let policy = parsed.policy().ok()?;
+
+            Some((parsed.resource().to_string(), policy))
+        };
+
+        match (access(self), access(other)) {
+            (Some(access), Some(other)) => access == other,
+            _ => false,
+        }
+    }
+
+    /// Returns an HTML anchor linking to the URL, eg. for email templates
+    ///
+    /// The URL and the text are HTML-escaped, so the `&` before `Signature=` can not be read as
+    /// the start of a character reference, which breaks the link in some mail clients.
+    ///
+    /// # Arguments
+    /// * `text` - The text of the link, which is escaped as well
+    ///
+    /// # Example
+    /// ```
+    /// use cloudfront_policy_signer::CloudFrontSigner;
+    ///
+    /// let signer = CloudFrontSigner::new("examples/key.pem", "APKAIEXAMPLE").unwrap();
+    /// let url = signer
+    ///     .sign_url("https://example.cloudfront.net/flowerpot.png", 1700000000)
+    ///     .unwrap();
+    /// let anchor = url.html_anchor("Download <flowerpot>");
+    ///
+    /// assert!(anchor.starts_with("<a href=\"https://example.cloudfront.net/flowerpot.png?Expires=1700000000&amp;Signature="));
+    /// assert!(anchor.ends_with("\">Download &lt;flowerpot&gt;</a>"));
+    /// ```
+    pub fn html_anchor(&self, text: &str) -> String {
+        format!(
+            "<a href=\"{}\">{}</a>",
+            escape_html(&self.url),
+            escape_html(text)
+        )
+    }
+}
+
+/// Escapes the characters with a special meaning in HTML text and attribute values
+fn escape_html(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+impl fmt::Debug for SignedUrl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SignedUrl")
+            .field("url", &self.redacted())
+            .field("grant", &self.grant)
+            .finish()
+    }
+}
+
+impl AsRef<str> for SignedUrl {
+    fn as_ref(&self) -> &str {
+        &self.url
+    }
+}
+
+impl fmt::Display for SignedUrl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.url)
+    }
+}
+
+impl From<SignedUrl> for String {
+    fn from(signed_url: SignedUrl) -> String {
+        signed_url.url
+    }
+}
@@ -0,0 +1,287 @@
+/*
+MIT License
+
+Copyright (c) 2020 Martin Karlsen Jensen
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+//! Links that can only be used once, approximated with a nonce and a store
+//!
+//! CloudFront accepts a signed URL for as long as it is valid. A [`SingleUseSigner`] adds a random
+//! nonce to the signed resource and records it in a [`NonceStore`], and a [`SingleUseValidator`]
+//! running at the edge, eg. in a Lambda@Edge viewer request trigger, consumes the nonce on first
+//! use and rejects the URL afterwards with [`Error::UrlAlreadyUsed`]. Keep the TTL short, as a
+//! URL is only single use where the validator runs.
+//!
+//! The [`MemoryNonceStore`] only works when signer and validator share a process. Across
+//! machines, implement [`NonceStore`] on a shared database with an atomic conditional delete.
+//!
+//! ```
+//! # #[cfg(feature = "sign")]
+//! # {
+//! use cloudfront_policy_signer::single_use::{MemoryNonceStore, SingleUseSigner, SingleUseValidator};
+//! use cloudfront_policy_signer::{CloudFrontSigner, Error};
+//! use std::sync::Arc;
+//! use std::time::{Duration, SystemTime};
+//!
+//! let store = Arc::new(MemoryNonceStore::default());
+//! let signer = Arc::new(CloudFrontSigner::new("examples/key.pem", "APKAIEXAMPLE").unwrap());
+//! let single_use = SingleUseSigner::new(signer, store.clone());
+//! let validator = SingleUseValidator::new(store);
+//!
+//! let url = single_use
+//!     .sign_url("https://example.cloudfront.net/invoice.pdf", Duration::from_secs(60))
+//!     .unwrap();
+//!
+//! assert!(validator.check(url.as_str(), SystemTime::now()).is_ok());
+//! assert!(matches!(
+//!     validator.check(url.as_str(), SystemTime::now()),
+//!     Err(Error::UrlAlreadyUsed(_))
+//! ));
+//! # }
+//! ```
+
+use crate::verify::ParsedSignedUrl;
+use crate::Error;
+#[cfg(feature = "sign")]
+use crate::{CloudFrontSigner, SignedUrl};
+use openssl::pkey::{PKey, Public};
+#[cfg(feature = "sign")]
+use openssl::rand::rand_bytes;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "sign")]
+use std::time::Duration;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The query parameter carrying the nonce unless another one is configured
+pub const DEFAULT_NONCE_PARAMETER: &str = "nonce";
+
+/// Records the nonces of issued URLs until they are used or expire
+pub trait NonceStore: Send + Sync {
+    /// Records that a URL with the nonce was issued
+    ///
+    /// # Arguments
+    /// * `nonce` - The nonce of the URL
+    /// * `expires` - Absolute time the URL expires, given in the form of a unix timestamp in UTC, after which the nonce can be forgotten
+    fn issue(&self, nonce: &str, expires: u64) -> Result<(), Error>;
+
+    /// Removes the nonce and returns whether it was issued, not yet used and not expired
+    ///
+    /// Must be atomic, so two concurrent requests with the same nonce can not both succeed.
+    ///
+    /// # Arguments
+    /// * `nonce` - The nonce of the requested URL
+    /// * `now` - The current time as a unix timestamp
+    fn consume(&self, nonce: &str, now: u64) -> Result<bool, Error>;
+}
+
+/// A [`NonceStore`] in memory, for a signer and validator in the same process
+#[derive(Debug, Default)]
+pub struct MemoryNonceStore {
+    nonces: Mutex<HashMap<String, u64>>,
+}
+
+impl NonceStore for MemoryNonceStore {
+    fn issue(&self, nonce: &str, expires: u64) -> Result<(), Error> {
+        let mut nonces = self.nonces.lock().unwrap_or_else(|e| e.into_inner());
+        let now = UNIX_EPOCH
+            .elapsed()
+            .map_or(0, |since_epoch| since_epoch.as_secs());
+
+        nonces.retain(|_, expires| *expires > now);
+        nonces.insert(nonce.to_string(), expires);
+
+        Ok(())
+    }
+
+    fn consume(&self, nonce: &str, now: u64) -> Result<bool, Error> {
+        let mut nonces = self.nonces.lock().unwrap_or_else(|e| e.into_inner());
+
+        Ok(nonces.remove(nonce).is_some_and(|expires| expires > now))
+    }
+}
+
+/// Signs URLs carrying a nonce that is recorded in a [`NonceStore`]
+#[cfg(feature = "sign")]
+pub struct SingleUseSigner {
+    signer: Arc<CloudFrontSigner>,
+    store: Arc<dyn NonceStore>,
+    parameter: String,
+}
+
+#[cfg(feature = "sign")]
+impl SingleUseSigner {
+    /// Constructs a new `SingleUseSigner` adding the nonce as [`DEFAULT_NONCE_PARAMETER`]
+    ///
+    /// # Arguments
+    /// * `signer` - The signer used for every URL
+    /// * `store` - The store the nonces are recorded in
+    pub fn new(signer: Arc<CloudFrontSigner>, store: Arc<dyn NonceStore>) -> SingleUseSigner {
+        SingleUseSigner {
+            signer,
+            store,
+            parameter: DEFAULT_NONCE_PARAMETER.to_string(),
+        }
+    }
+
+    /// Sets the query parameter carrying the nonce, which must match the one of the validator
+    ///
+    /// # Arguments
+    /// * `parameter` - The name of the query parameter eg. token
+    pub fn parameter<T: Into<String>>(mut self, parameter: T) -> SingleUseSigner {
+        self.parameter = parameter.into();
+        self
+    }
+
+    /// Creates a URL with a canned policy and a new nonce, and records the nonce
+    ///
+    /// # Arguments
+    /// * `resource` - The protected resource eg. https://example.cloudfront.net/invoice.pdf
+    /// * `ttl` - How long the URL can be used for, which should be short
+    pub fn sign_url<R: AsRef<str>>(&self, resource: R, ttl: Duration) -> Result<SignedUrl, Error> {
+        let resource = resource.as_ref();
+        let mut nonce = [0u8; 16];
+
+        rand_bytes(&mut nonce).map_err(|e| {
+            error!("Could not generate a nonce due to {}", e);
+            Error::Unknown
+        })?;
+
+        let nonce = nonce
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<String>();
+        let separator = if resource.contains('?') { '&' } else { '?' };
+        let expiry = self.signer.expiry_after(ttl)?;
+        let signed_url = self.signer.sign_url(
+            format!("{}{}{}={}", resource, separator, self.parameter, nonce),
+            expiry,
+        )?;
+
+        self.store.issue(&nonce, expiry)?;
+
+        Ok(signed_url)
+    }
+}
+
+/// Rejects signed URLs whose nonce was already used, see the [`crate::single_use`] module
+pub struct SingleUseValidator {
+    store: Arc<dyn NonceStore>,
+    parameter: String,
+    public_key: Option<PKey<Public>>,
+}
+
+impl SingleUseValidator {
+    /// Constructs a new `SingleUseValidator` reading the nonce from [`DEFAULT_NONCE_PARAMETER`]
+    ///
+    /// # Arguments
+    /// * `store` - The store the signer records the nonces in
+    pub fn new(store: Arc<dyn NonceStore>) -> SingleUseValidator {
+        SingleUseValidator {
+            store,
+            parameter: DEFAULT_NONCE_PARAMETER.to_string(),
+            public_key: None,
+        }
+    }
+
+    /// Sets the query parameter carrying the nonce
+    ///
+    /// # Arguments
+    /// * `parameter` - The name of the query parameter eg. token
+    pub fn parameter<T: Into<String>>(mut self, parameter: T) -> SingleUseValidator {
+        self.parameter = parameter.into();
+        self
+    }
+
+    /// Also verifies the signature, so forged URLs can not consume nonces of genuine ones
+    ///
+    /// # Arguments
+    /// * `public_key` - The public key uploaded to CloudFront, see [`crate::verify::parse_public_key`]
+    pub fn public_key(mut self, public_key: PKey<Public>) -> SingleUseValidator {
+        self.public_key = Some(public_key);
+        self
+    }
+
+    /// Checks that the signed URL has not expired and consumes its nonce
+    ///
+    /// Fails with [`Error::UrlAlreadyUsed`] if the nonce was used before or never issued, and with
+    /// [`Error::InvalidSignedUrl`] if the URL is malformed, expired, has no nonce or, when a
+    /// public key is set, an invalid signature.
+    ///
+    /// # Arguments
+    /// * `signed_url` - The requested URL
+    /// * `now` - The current time, usually `SystemTime::now()`
+    pub fn check(&self, signed_url: &str, now: SystemTime) -> Result<(), Error> {
+        let parsed = ParsedSignedUrl::parse(signed_url)?;
+
+        if let Some(public_key) = &self.public_key {
+            if !parsed.verify(public_key)? {
+                return Err(Error::InvalidSignedUrl(
+                    "the signature does not match".to_string(),
+                ));
+            }
+        }
+
+        let now = now
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since_epoch| since_epoch.as_secs());
+
+        if parsed.policy()?.validity().0 <= now {
+            return Err(Error::InvalidSignedUrl("the URL has expired".to_string()));
+        }
+
+        let nonce = parsed
+            .resource()
+            .split_once('?')
+            .and_then(|(_, query)| {
+                query.split('&').find_map(|parameter| {
+                    parameter
+                        .strip_prefix(self.parameter.as_str())
+                        .and_then(|value| value.strip_prefix('='))
+                })
+            })
+            .ok_or_else(|| Error::InvalidSignedUrl("the URL has no nonce".to_string()))?;
+
+        if self.store.consume(nonce, now)? {
+            Ok(())
+        } else {
+            warn!("Rejected a reused or unknown nonce {}", nonce);
+            Err(Error::UrlAlreadyUsed(nonce.to_string()))
+        }
+    }
+
+    /// Checks the request of a CloudFront viewer request event, eg. in a Lambda@Edge function
+    ///
+    /// # Arguments
+    /// * `host` - The value of the Host header eg. example.cloudfront.net
+    /// * `uri` - The `uri` of the request eg. /invoice.pdf
+    /// * `querystring` - The `querystring` of the request, without the leading `?`
+    /// * `now` - The current time, usually `SystemTime::now()`
+    pub fn check_viewer_request(
+        &self,
+        host: &str,
+        uri: &str,
+        querystring: &str,
+        now: SystemTime,
+    ) -> Result<(), Error> {
+        self.check(&format!("https://{}{}?{}", host, uri, querystring), now)
+    }
+}
@@ -0,0 +1,173 @@
+/*
+MIT License
+
+Copyright (c) 2020 Martin Karlsen Jensen
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use crate::{json, Error};
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Where the private key of a signer was loaded from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyProvider {
+    /// Read from a file, see [`crate::CloudFrontSigner::new`]
+    File(PathBuf),
+    /// Passed in memory, see [`crate::CloudFrontSigner::new_in_memory`]
+    Memory,
+}
+
+impl fmt::Display for KeyProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyProvider::File(path) => write!(f, "file {}", path.display()),
+            KeyProvider::Memory => write!(f, "memory"),
+        }
+    }
+}
+
+/// The state of a signer for operational visibility, eg. in a `/debug` endpoint
+///
+/// Returned by [`crate::CloudFrontSigner::status`]. [`SignerStatus::to_json`] gives a JSON object
+/// with the same fields, times as unix timestamps and durations in seconds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignerStatus {
+    /// The key pair ID the signer signs with
+    pub key_pair_id: String,
+    /// The SHA-256 fingerprint of the key, see [`crate::keys::fingerprint_sha256`]
+    pub key_fingerprint: String,
+    /// The size of the key in bits
+    pub key_bits: u32,
+    /// Where the key was loaded from
+    pub key_provider: KeyProvider,
+    /// How long ago the key file was last modified, `None` for keys passed in memory
+    pub key_age: Option<Duration>,
+    /// When the signer was created
+    pub started_at: SystemTime,
+    /// How many URLs, cookies and policies were signed since the signer was created
+    pub signed: u64,
+    /// How many attempts to sign failed since the signer was created
+    pub failed: u64,
+    /// The latest failure and when it happened
+    pub last_error: Option<(SystemTime, String)>,
+}
+
+impl SignerStatus {
+    /// Returns the status as a JSON object
+    pub fn to_json(&self) -> String {
+        let mut json = String::from("{\"key_pair_id\":");
+
+        json::write_string(&mut json, &self.key_pair_id);
+        json.push_str(",\"key_fingerprint\":");
+        json::write_string(&mut json, &self.key_fingerprint);
+        json.push_str(&format!(
+            ",\"key_bits\":{},\"key_provider\":",
+            self.key_bits
+        ));
+        json::write_string(&mut json, &self.key_provider.to_string());
+        json.push_str(",\"key_age\":");
+
+        match self.key_age {
+            Some(key_age) => json.push_str(&key_age.as_secs().to_string()),
+            None => json.push_str("null"),
+        }
+
+        json.push_str(&format!(
+            ",\"started_at\":{},\"signed\":{},\"failed\":{},\"last_error\":",
+            unix_timestamp(self.started_at),
+            self.signed,
+            self.failed
+        ));
+
+        match &self.last_error {
+            Some((at, message)) => {
+                json.push_str(&format!("{{\"at\":{},\"message\":", unix_timestamp(*at)));
+                json::write_string(&mut json, message);
+                json.push('}');
+            }
+            None => json.push_str("null"),
+        }
+
+        json.push('}');
+        json
+    }
+}
+
+/// Counts the outcomes of signing for [`SignerStatus`]
+#[derive(Debug)]
+pub(crate) struct Counters {
+    pub(crate) started_at: SystemTime,
+    signed: AtomicU64,
+    failed: AtomicU64,
+    last_error: Mutex<Option<(SystemTime, String)>>,
+}
+
+impl Counters {
+    pub(crate) fn new() -> Counters {
+        Counters {
+            started_at: SystemTime::now(),
+            signed: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+            last_error: Mutex::new(None),
+        }
+    }
+
+    /// Counts the result of an attempt to sign and passes it on
+    ///
+    /// # Arguments
+    /// * `result` - The result of signing
+    /// * `now` - The current time
+    pub(crate) fn record<T>(&self, result: Result<T, Error>, now: SystemTime) -> Result<T, Error> {
+        match &result {
+            Ok(_) => {
+                self.signed.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(e) => {
+                self.failed.fetch_add(1, Ordering::Relaxed);
+                *self.last_error.lock().unwrap_or_else(|e| e.into_inner()) =
+                    Some((now, e.to_string()));
+            }
+        }
+
+        result
+    }
+
+    /// Returns how many attempts succeeded and failed and the latest failure
+    pub(crate) fn snapshot(&self) -> (u64, u64, Option<(SystemTime, String)>) {
+        (
+            self.signed.load(Ordering::Relaxed),
+            self.failed.load(Ordering::Relaxed),
+            self.last_error
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .clone(),
+        )
+    }
+}
+
+/// Returns the time as a unix timestamp, zero for times before the epoch
+fn unix_timestamp(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |since_epoch| since_epoch.as_secs())
+}
@@ -0,0 +1,114 @@
+/*
+MIT License
+
+Copyright (c) 2020 Martin Karlsen Jensen
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use crate::{base64, Error, SignedPolicy};
+use openssl::sign::Signer;
+
+/// Signs a custom policy that is fed in chunks, created by [`crate::CloudFrontSigner::stream_policy`]
+///
+/// The policy is hashed and encoded as it arrives, so policies with hundreds of statements never
+/// have to be held in memory as one document. The chunks are signed exactly as given, so they
+/// should already be compact JSON.
+///
+/// # Example
+/// ```
+/// use cloudfront_policy_signer::CloudFrontSigner;
+///
+/// let signer = CloudFrontSigner::new("examples/key.pem", "APKAIEXAMPLE").unwrap();
+/// let mut stream = signer.stream_policy().unwrap();
+///
+/// stream.update(br#"{"Statement":["#).unwrap();
+///
+/// for (index, user) in ["alice", "bob"].iter().enumerate() {
+///     if index > 0 {
+///         stream.update(b",").unwrap();
+///     }
+///
+///     stream.update(format!(
+///         r#"{{"Resource":"https://example.cloudfront.net/{}/*","Condition":{{"DateLessThan":{{"AWS:EpochTime":1700000000}}}}}}"#,
+///         user
+///     ).as_bytes()).unwrap();
+/// }
+///
+/// stream.update(b"]}").unwrap();
+///
+/// let signed = stream.finish(1700000000).unwrap();
+/// ```
+pub struct PolicyStream<'a> {
+    signer: Signer<'a>,
+    encoder: base64::Encoder,
+    key_pair_id: &'a str,
+    max_url_length: usize,
+}
+
+impl<'a> PolicyStream<'a> {
+    pub(crate) fn new(
+        signer: Signer<'a>,
+        key_pair_id: &'a str,
+        max_url_length: usize,
+    ) -> PolicyStream<'a> {
+        PolicyStream {
+            signer,
+            encoder: base64::Encoder::default(),
+            key_pair_id,
+            max_url_length,
+        }
+    }
+
+    /// Feeds the next chunk of the policy to the signer
+    ///
+    /// # Arguments
+    /// * `chunk` - The next bytes of the policy document
+    pub fn update(&mut self, chunk: &[u8]) -> Result<(), Error> {
+        self.signer.update(chunk).map_err(|e| {
+            error!("Could not update signer due to {}", e);
+            Error::Unknown
+        })?;
+        self.encoder.update(chunk);
+
+        Ok(())
+    }
+
+    /// Signs the policy fed so far
+    ///
+    /// The resources of a streamed policy are not known, so cookies created from it default to the path /.
+    ///
+    /// # Arguments
+    /// * `expiry` - The earliest `DateLessThan` of the policy, reported by the URLs and cookies created from it
+    pub fn finish(self, expiry: u64) -> Result<SignedPolicy, Error> {
+        let signature = self.signer.sign_to_vec().map_err(|e| {
+            error!("Could not sign due to {}", e);
+            Error::CouldNotSign
+        })?;
+
+        Ok(SignedPolicy::new(
+            self.encoder.finish(),
+            signature,
+            (expiry, None),
+            self.key_pair_id.to_string(),
+            "/".to_string(),
+            self.max_url_length,
+        ))
+    }
+}
@@ -0,0 +1,136 @@
+/*
+MIT License
+
+Copyright (c) 2020 Martin Karlsen Jensen
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+//! Helpers for signing URLs from within templates
+//!
+//! Template engines call filters without access to application state, so the signer is registered
+//! once at startup with [`register_signer`] and [`cloudfront_sign`] uses it from then on.
+//!
+//! With Askama the function can be exposed directly from the `filters` module of the template:
+//!
+//! ```ignore
+//! mod filters {
+//!     pub fn cloudfront_sign(resource: &str, ttl: &str) -> askama::Result<String> {
+//!         cloudfront_policy_signer::template::cloudfront_sign(resource, ttl)
+//!             .map_err(|e| askama::Error::Custom(Box::new(e)))
+//!     }
+//! }
+//! ```
+//!
+//! With Tera it is registered as a filter which reads the `ttl` argument:
+//!
+//! ```ignore
+//! tera.register_filter("cloudfront_sign", |value: &tera::Value, args: &HashMap<String, tera::Value>| {
+//!     let resource = tera::try_get_value!("cloudfront_sign", "value", String, value);
+//!     let ttl = args.get("ttl").and_then(|ttl| ttl.as_str()).unwrap_or("1h");
+//!
+//!     cloudfront_policy_signer::template::cloudfront_sign(resource, ttl)
+//!         .map(tera::Value::String)
+//!         .map_err(|e| tera::Error::msg(e.to_string()))
+//! });
+//! ```
+
+use crate::{CloudFrontSigner, Error};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+static SIGNER: OnceLock<CloudFrontSigner> = OnceLock::new();
+
+/// Registers the signer used by [`cloudfront_sign`]. It can only be registered once
+///
+/// # Arguments
+/// * `signer` - The signer used for every URL signed from a template
+pub fn register_signer(signer: CloudFrontSigner) -> Result<(), Error> {
+    SIGNER
+        .set(signer)
+        .map_err(|_| Error::SignerAlreadyRegistered)
+}
+
+/// Signs the resource with the registered signer and returns the signed URL
+///
+/// # Arguments
+/// * `resource` - The protected resource eg. https://example.cloudfront.net/flowerpot.png
+/// * `ttl` - How long the URL should be valid for, see [`parse_ttl`] for the format
+pub fn cloudfront_sign<R: AsRef<str>>(resource: R, ttl: &str) -> Result<String, Error> {
+    let signer = SIGNER.get().ok_or(Error::NoSignerRegistered)?;
+    let expiry = signer.expiry_after(parse_ttl(ttl)?)?;
+
+    signer.create_canned_policy_signature_url(resource, expiry)
+}
+
+/// Parses a duration such as `90s`, `30m`, `1h`, `2d` or a combination like `1h30m`
+///
+/// # Arguments
+/// * `ttl` - The duration to parse. A plain number is interpreted as seconds
+///
+/// # Example
+/// ```
+/// use cloudfront_policy_signer::template::parse_ttl;
+/// use std::time::Duration;
+///
+/// assert_eq!(parse_ttl("1h30m").unwrap(), Duration::from_secs(5400));
+/// ```
+pub fn parse_ttl(ttl: &str) -> Result<Duration, Error> {
+    let invalid = || Error::InvalidTtl(ttl.to_string());
+    let trimmed = ttl.trim();
+
+    if trimmed.is_empty() {
+        return Err(invalid());
+    }
+
+    if let Ok(seconds) = trimmed.parse::<u64>() {
+        return Ok(Duration::from_secs(seconds));
+    }
+
+    let mut total: u64 = 0;
+    let mut number = String::new();
+
+    for c in trimmed.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+
+        let multiplier = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            _ => return Err(invalid()),
+        };
+        let value: u64 = number.parse().map_err(|_| invalid())?;
+
+        total = value
+            .checked_mul(multiplier)
+            .and_then(|seconds| total.checked_add(seconds))
+            .ok_or_else(invalid)?;
+        number.clear();
+    }
+
+    if !number.is_empty() {
+        return Err(invalid());
+    }
+
+    Ok(Duration::from_secs(total))
+}
@@ -0,0 +1,475 @@
+/*
+MIT License
+
+Copyright (c) 2020 Martin Karlsen Jensen
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+//! Test doubles for applications using the signer, enabled with the `testing` feature
+//!
+//! [`MockSigner`] implements [`UrlSigner`](crate::UrlSigner) like
+//! [`CloudFrontSigner`](crate::CloudFrontSigner) does, but without a private key. It returns deterministic URLs and cookies, whose signature is the
+//! literal [`MOCK_SIGNATURE`], and records every call so handlers can be unit tested. Signed
+//! policies carry the bytes of [`MOCK_SIGNATURE`] as their raw signature.
+//!
+//! ```
+//! use cloudfront_policy_signer::testing::{MockCall, MockSigner};
+//!
+//! let signer = MockSigner::new("APKAIEXAMPLE");
+//! let url = signer
+//!     .sign_url("https://example.cloudfront.net/flowerpot.png", 1700000000)
+//!     .unwrap();
+//!
+//! assert_eq!(
+//!     url.as_str(),
+//!     "https://example.cloudfront.net/flowerpot.png?Expires=1700000000&Signature=MOCK-SIGNATURE&Key-Pair-Id=APKAIEXAMPLE"
+//! );
+//! assert_eq!(
+//!     signer.calls(),
+//!     [MockCall::SignUrl {
+//!         resource: "https://example.cloudfront.net/flowerpot.png".to_string(),
+//!         expiry: 1700000000,
+//!     }]
+//! );
+//! ```
+//!
+//! The module also contains invariants the signer guarantees, such as
+//! [`check_sign_round_trip`], and a generator of [`resources`] to check them with. They can be
+//! used with any property testing framework or on their own. [`TempKey`] provides a real key
+//! pair for such tests without key material on disk.
+
+use crate::cookies;
+use crate::keys::CLOUDFRONT_KEY_BITS;
+use crate::signed_url::Grant;
+use crate::signed_url::REDACTED;
+use crate::verify::parse_public_key;
+use crate::verify::ParsedSignedUrl;
+use crate::{
+    base64, CloudFrontSigner, Error, Policy, PolicyKind, SignedCookies, SignedPolicy, SignedUrl,
+};
+use openssl::error::ErrorStack;
+use openssl::pkey::{PKey, Public};
+use openssl::rsa::Rsa;
+use std::fmt;
+use std::sync::Mutex;
+
+/// The signature of every URL and cookie created by [`MockSigner`]
+pub const MOCK_SIGNATURE: &str = "MOCK-SIGNATURE";
+
+/// A call made to a [`MockSigner`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MockCall {
+    /// [`MockSigner::sign_url`] was called
+    SignUrl {
+        /// The resource that was signed
+        resource: String,
+        /// The expiry that was requested
+        expiry: u64,
+    },
+    /// [`MockSigner::create_canned_policy_signed_cookies`] was called
+    SignCookies {
+        /// The resource that was signed
+        resource: String,
+        /// The expiry that was requested
+        expiry: u64,
+    },
+    /// [`MockSigner::sign_policy`] was called
+    SignPolicy(Policy),
+}
+
+/// A signer for tests that does not need a private key, see the [`testing`](self) module
+#[derive(Debug, Default)]
+pub struct MockSigner {
+    key_pair_id: String,
+    calls: Mutex<Vec<MockCall>>,
+}
+
+impl MockSigner {
+    /// Constructs a new `MockSigner`
+    ///
+    /// # Arguments
+    /// * `key_pair_id` - The key pair ID put in the URLs and cookies
+    pub fn new<T: ToString>(key_pair_id: T) -> MockSigner {
+        MockSigner {
+            key_pair_id: key_pair_id.to_string(),
+            calls: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Returns every call made so far, oldest first
+    pub fn calls(&self) -> Vec<MockCall> {
+        self.calls.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    fn record(&self, call: MockCall) {
+        self.calls
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(call);
+    }
+
+    /// Creates a URL with a fake canned policy signature
+    ///
+    /// # Arguments
+    /// * `resource` - The protected resource eg. https://example.cloudfront.net/flowerpot.png
+    /// * `expiry` - Absolute time that the URL expires, given in the form of a unix timestamp in UTC
+    pub fn sign_url<R: AsRef<str>>(&self, resource: R, expiry: u64) -> Result<SignedUrl, Error> {
+        let resource = resource.as_ref();
+        let separator = if resource.contains('?') { '&' } else { '?' };
+
+        self.record(MockCall::SignUrl {
+            resource: resource.to_string(),
+            expiry,
+        });
+
+        Ok(SignedUrl::new(
+            format!(
+                "{}{}Expires={}&Signature={}&Key-Pair-Id={}",
+                resource, separator, expiry, MOCK_SIGNATURE, self.key_pair_id
+            ),
+            Grant {
+                expires: expiry,
+                not_before: None,
+                key_pair_id: self.key_pair_id.clone(),
+                kind: PolicyKind::Canned,
+            },
+        ))
+    }
+
+    /// Creates cookies with a fake canned policy signature
+    ///
+    /// # Arguments
+    /// * `resource` - The protected resource eg. https://example.cloudfront.net/flowerpot.png
+    /// * `expiry` - Absolute time that the cookies expire, given in the form of a unix timestamp in UTC
+    pub fn create_canned_policy_signed_cookies<R: AsRef<str>>(
+        &self,
+        resource: R,
+        expiry: u64,
+    ) -> Result<SignedCookies, Error> {
+        self.record(MockCall::SignCookies {
+            resource: resource.as_ref().to_string(),
+            expiry,
+        });
+
+        Ok(SignedCookies::canned(
+            expiry,
+            MOCK_SIGNATURE.to_string(),
+            &self.key_pair_id,
+            resource.as_ref(),
+        ))
+    }
+
+    /// Signs a custom policy with a fake signature
+    ///
+    /// The policy is encoded as the real signer would, so tests can decode and inspect it.
+    ///
+    /// # Arguments
+    /// * `policy` - The policy to sign
+    pub fn sign_policy(&self, policy: &Policy) -> Result<SignedPolicy, Error> {
+        self.record(MockCall::SignPolicy(policy.clone()));
+
+        Ok(SignedPolicy::new(
+            base64::encode(policy.to_json().as_bytes()),
+            MOCK_SIGNATURE.as_bytes().to_vec(),
+            policy.validity(),
+            self.key_pair_id.clone(),
+            cookies::cookie_path(
+                policy
+                    .statements
+                    .iter()
+                    .map(|statement| Some(statement.resource.as_str())),
+            ),
+            crate::DEFAULT_MAX_URL_LENGTH,
+        ))
+    }
+}
+
+/// A key pair generated in memory for tests, so no key material is written to the repository or disk
+///
+/// The PEM encoding of the private key is overwritten with zeros when the key is dropped. This is
+/// best effort, as copies made by the allocator or by OpenSSL while signing are out of reach.
+/// The `Debug` output redacts the private key.
+///
+/// # Example
+/// ```
+/// use cloudfront_policy_signer::testing::{check_sign_round_trip, TempKey};
+///
+/// let key = TempKey::generate().unwrap();
+/// let signer = key.signer("K2JCJMDEHXQW5F").unwrap();
+///
+/// check_sign_round_trip(
+///     &signer,
+///     &key.public_key().unwrap(),
+///     "https://example.cloudfront.net/flowerpot.png",
+///     1700000000,
+/// )
+/// .unwrap();
+/// ```
+pub struct TempKey {
+    private_key_pem: String,
+    public_key_pem: String,
+}
+
+impl TempKey {
+    /// Generates a new key pair of the size CloudFront accepts
+    pub fn generate() -> Result<TempKey, Error> {
+        let key = Rsa::generate(CLOUDFRONT_KEY_BITS).map_err(|e| {
+            error!("Could not generate RSA key due to {}", e);
+            Error::Unknown
+        })?;
+        let pem = |pem: Result<Vec<u8>, ErrorStack>| {
+            pem.ok()
+                .and_then(|pem| String::from_utf8(pem).ok())
+                .ok_or_else(|| {
+                    error!("Could not encode the generated key");
+                    Error::Unknown
+                })
+        };
+
+        Ok(TempKey {
+            private_key_pem: pem(key.private_key_to_pem())?,
+            public_key_pem: pem(key.public_key_to_pem())?,
+        })
+    }
+
+    /// Returns the private key as PEM-encoded PKCS#1, as accepted by [`CloudFrontSigner::new_in_memory`]
+    pub fn private_key_pem(&self) -> &str {
+        &self.private_key_pem
+    }
+
+    /// Returns the public key as PEM-encoded SubjectPublicKeyInfo
+    pub fn public_key_pem(&self) -> &str {
+        &self.public_key_pem
+    }
+
+    /// Returns the public key, eg. for verifying what the signer signed
+    pub fn public_key(&self) -> Result<PKey<Public>, Error> {
+        parse_public_key(self.public_key_pem.as_bytes())
+    }
+
+    /// Constructs a signer with the private key
+    ///
+    /// # Arguments
+    /// * `key_pair_id` - The key pair ID put in the URLs and cookies
+    pub fn signer<T: ToString>(&self, key_pair_id: T) -> Result<CloudFrontSigner, Error> {
+        CloudFrontSigner::new_in_memory(&self.private_key_pem, key_pair_id)
+    }
+}
+
+impl Drop for TempKey {
+    fn drop(&mut self) {
+        let mut private_key_pem = std::mem::take(&mut self.private_key_pem).into_bytes();
+
+        private_key_pem.fill(0);
+        std::hint::black_box(&private_key_pem);
+    }
+}
+
+impl fmt::Debug for TempKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TempKey")
+            .field("private_key_pem", &REDACTED)
+            .field("public_key_pem", &self.public_key_pem)
+            .finish()
+    }
+}
+
+/// Checks that URLs signed with a canned and with a custom policy parse back to the resource and policy and verify
+///
+/// Canned policies do not support wildcards, so a resource with `*` is only signed with a custom
+/// policy, for a URL where the wildcards are replaced by `index.html`.
+///
+/// # Arguments
+/// * `signer` - A signer without allowed or denied prefixes
+/// * `public_key` - The public key matching the key of the signer
+/// * `resource` - The protected resource, eg. one of [`resources`]
+/// * `expiry` - Absolute time that the URL expires, given in the form of a unix timestamp in UTC
+///
+/// # Example
+/// ```
+/// use cloudfront_policy_signer::testing::{check_sign_round_trip, resources};
+/// use cloudfront_policy_signer::verify::parse_public_key;
+/// use cloudfront_policy_signer::CloudFrontSigner;
+///
+/// let signer = CloudFrontSigner::new("examples/key.pem", "APKAIEXAMPLE").unwrap();
+/// let public_key = parse_public_key(signer.public_key_pem().unwrap().as_bytes()).unwrap();
+///
+/// for resource in resources(42).take(10) {
+///     check_sign_round_trip(&signer, &public_key, &resource, 1700000000).unwrap();
+/// }
+/// ```
+pub fn check_sign_round_trip(
+    signer: &CloudFrontSigner,
+    public_key: &PKey<Public>,
+    resource: &str,
+    expiry: u64,
+) -> Result<(), String> {
+    let policy = Policy::canned(resource, expiry);
+    let url = resource.replace('*', "index.html");
+    let custom = signer
+        .sign_policy(&policy)
+        .and_then(|signed_policy| signed_policy.url(&url))
+        .map_err(|e| e.to_string())?;
+    let mut signed_urls = vec![(PolicyKind::Custom, custom)];
+
+    if !resource.contains('*') {
+        let canned = signer
+            .sign_url(resource, expiry)
+            .map_err(|e| e.to_string())?;
+
+        signed_urls.push((PolicyKind::Canned, canned));
+    }
+
+    for (kind, signed_url) in signed_urls {
+        let parsed = ParsedSignedUrl::parse(signed_url.as_str()).map_err(|e| e.to_string())?;
+        let failure =
+            |what: &str| format!("{} differs for {:?} {}", what, kind, signed_url.redacted());
+
+        if parsed.resource() != url {
+            return Err(failure("the resource"));
+        }
+
+        if parsed.kind() != kind {
+            return Err(failure("the kind of policy"));
+        }
+
+        if parsed.policy().map_err(|e| e.to_string())? != policy {
+            return Err(failure("the policy"));
+        }
+
+        if parsed.key_pair_id() != signer.key_pair_id() {
+            return Err(failure("the key pair ID"));
+        }
+
+        if !parsed.verify(public_key).map_err(|e| e.to_string())? {
+            return Err(format!(
+                "the signature of {} is invalid",
+                signed_url.redacted()
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks that the bytes decode back from their [`base64`] encoding and that the encoding is URL safe
+///
+/// If the bytes are also a string that decodes, its decoding has to encode back to the same
+/// string, as every byte string has exactly one encoding.
+///
+/// # Arguments
+/// * `bytes` - Any bytes
+///
+/// # Example
+/// ```
+/// use cloudfront_policy_signer::testing::check_base64_round_trip;
+///
+/// for input in [&b""[..], b"\xfb\xff", b"AA__", b"AB__", b"-~8_"] {
+///     check_base64_round_trip(input).unwrap();
+/// }
+/// ```
+pub fn check_base64_round_trip(bytes: &[u8]) -> Result<(), String> {
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        if let Ok(decoded) = base64::decode(text) {
+            let encoded = base64::encode(&decoded);
+
+            if encoded != text {
+                return Err(format!(
+                    "{} decoded to {:?}, which encodes to {}",
+                    text, decoded, encoded
+                ));
+            }
+        }
+    }
+
+    let encoded = base64::encode(bytes);
+
+    if let Some(c) = encoded
+        .chars()
+        .find(|c| !c.is_ascii_alphanumeric() && !matches!(c, '-' | '~' | '_'))
+    {
+        return Err(format!("the encoding {} contains '{}'", encoded, c));
+    }
+
+    match base64::decode(&encoded) {
+        Ok(decoded) if decoded == bytes => Ok(()),
+        Ok(decoded) => Err(format!("{:?} decoded to {:?}", bytes, decoded)),
+        Err(e) => Err(format!("{} could not be decoded: {}", encoded, e)),
+    }
+}
+
+/// Generates an endless sequence of valid resources, the same for every run with the same seed
+///
+/// The resources vary in host, path, non-ASCII and percent-encoded characters, wildcards and
+/// query strings.
+///
+/// # Arguments
+/// * `seed` - Selects the sequence
+pub fn resources(seed: u64) -> impl Iterator<Item = String> {
+    const HOSTS: [&str; 3] = [
+        "example.cloudfront.net",
+        "d111111abcdef8.cloudfront.net",
+        "cdn.example.com",
+    ];
+    const SEGMENTS: [&str; 8] = [
+        "videos",
+        "flowerpot.png",
+        "2024",
+        "m%C3%BCnchen",
+        "münchen",
+        "a%20b.txt",
+        "~user",
+        "index.m3u8",
+    ];
+    // xorshift64 started from the splitmix64 mix of the seed, so neighbouring seeds give different
+    // sequences, and which must not start at zero
+    let mut state = {
+        let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        (z ^ (z >> 31)) | 1
+    };
+    let mut next = move |bound: usize| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+
+        (state % bound as u64) as usize
+    };
+
+    std::iter::repeat_with(move || {
+        let mut resource = format!("https://{}", HOSTS[next(HOSTS.len())]);
+
+        for _ in 0..next(4) {
+            resource.push('/');
+            resource.push_str(SEGMENTS[next(SEGMENTS.len())]);
+        }
+
+        match next(4) {
+            0 => resource.push_str("/*"),
+            1 => resource.push_str(&format!("?v={}", next(1000))),
+            2 => resource.push_str(&format!("?v={}&lang=en", next(1000))),
+            _ => {}
+        }
+
+        resource
+    })
+}
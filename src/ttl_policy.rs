@@ -0,0 +1,90 @@
+/*
+MIT License
+
+Copyright (c) 2020 Martin Karlsen Jensen
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use crate::resource::wildcard_matches;
+use std::time::Duration;
+
+/// Rules mapping path patterns to the TTL of signed URLs, used by [`crate::CloudFrontSigner::sign_url_auto`]
+///
+/// A pattern is matched against the path of the resource, without the query string. `*` matches
+/// any number of characters including `/` and `?` matches a single character, as in the policies
+/// of CloudFront. The first matching rule wins, so list specific patterns before general ones.
+///
+/// # Example
+/// ```
+/// use cloudfront_policy_signer::TtlPolicy;
+/// use std::time::Duration;
+///
+/// let ttls = TtlPolicy::new(Duration::from_secs(60 * 60))
+///     .rule("*.m3u8", Duration::from_secs(30))
+///     .rule("/videos/*.mp4", Duration::from_secs(6 * 60 * 60));
+///
+/// assert_eq!(ttls.ttl_for("https://example.cloudfront.net/live/index.m3u8"), Duration::from_secs(30));
+/// assert_eq!(ttls.ttl_for("https://example.cloudfront.net/videos/1.mp4?lang=en"), Duration::from_secs(6 * 60 * 60));
+/// assert_eq!(ttls.ttl_for("https://example.cloudfront.net/flowerpot.png"), Duration::from_secs(60 * 60));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TtlPolicy {
+    rules: Vec<(String, Duration)>,
+    default_ttl: Duration,
+}
+
+impl TtlPolicy {
+    /// Constructs a new `TtlPolicy` without rules
+    ///
+    /// # Arguments
+    /// * `default_ttl` - The TTL of resources not matching any rule
+    pub fn new(default_ttl: Duration) -> TtlPolicy {
+        TtlPolicy {
+            rules: Vec::new(),
+            default_ttl,
+        }
+    }
+
+    /// Adds a rule after the existing ones
+    ///
+    /// # Arguments
+    /// * `pattern` - The path pattern eg. *.m3u8 or /videos/*
+    /// * `ttl` - How long URLs for matching resources are valid for
+    pub fn rule<T: Into<String>>(mut self, pattern: T, ttl: Duration) -> TtlPolicy {
+        self.rules.push((pattern.into(), ttl));
+        self
+    }
+
+    /// Returns the TTL of the first rule matching the resource, or the default TTL
+    ///
+    /// # Arguments
+    /// * `resource` - The resource eg. https://example.cloudfront.net/flowerpot.png or just its path
+    pub fn ttl_for(&self, resource: &str) -> Duration {
+        let path = resource.split_once("://").map_or(resource, |(_, rest)| {
+            rest.find('/').map_or("/", |start| &rest[start..])
+        });
+        let path = &path[..path.find('?').unwrap_or(path.len())];
+
+        self.rules
+            .iter()
+            .find(|(pattern, _)| wildcard_matches(pattern, path))
+            .map_or(self.default_ttl, |(_, ttl)| *ttl)
+    }
+}
@@ -0,0 +1,182 @@
+/*
+MIT License
+
+Copyright (c) 2020 Martin Karlsen Jensen
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use crate::{CloudFrontSigner, Error, Policy, SignedCookies, SignedPolicy, SignedUrl};
+use std::sync::Arc;
+
+/// The signing operations of [`CloudFrontSigner`] behind a trait that can be used as `dyn UrlSigner`
+///
+/// Applications can depend on `&dyn UrlSigner` or `Arc<dyn UrlSigner>` and inject the real
+/// signer, a mock in tests or a signer for another CDN.
+///
+/// # Example
+/// ```
+/// use cloudfront_policy_signer::{CloudFrontSigner, Error, UrlSigner};
+/// use std::sync::Arc;
+///
+/// fn avatar_url(signer: &dyn UrlSigner, user: u64) -> Result<String, Error> {
+///     let resource = format!("https://example.cloudfront.net/avatars/{}.png", user);
+///
+///     Ok(signer.sign_url(&resource, 1700000000)?.into_string())
+/// }
+///
+/// let signer: Arc<dyn UrlSigner> =
+///     Arc::new(CloudFrontSigner::new("examples/key.pem", "APKAIEXAMPLE").unwrap());
+///
+/// avatar_url(signer.as_ref(), 42).unwrap();
+/// ```
+pub trait UrlSigner: Send + Sync {
+    /// Creates a URL with a canned policy, see [`CloudFrontSigner::sign_url`]
+    ///
+    /// # Arguments
+    /// * `resource` - The protected resource eg. https://example.cloudfront.net/flowerpot.png
+    /// * `expiry` - Absolute time that the URL expires, given in the form of a unix timestamp in UTC
+    fn sign_url(&self, resource: &str, expiry: u64) -> Result<SignedUrl, Error>;
+
+    /// Creates cookies with a canned policy, see [`CloudFrontSigner::create_canned_policy_signed_cookies`]
+    ///
+    /// # Arguments
+    /// * `resource` - The protected resource eg. https://example.cloudfront.net/flowerpot.png
+    /// * `expiry` - Absolute time that the cookies expire, given in the form of a unix timestamp in UTC
+    fn create_canned_policy_signed_cookies(
+        &self,
+        resource: &str,
+        expiry: u64,
+    ) -> Result<SignedCookies, Error>;
+
+    /// Signs a custom policy, see [`CloudFrontSigner::sign_policy`]
+    ///
+    /// # Arguments
+    /// * `policy` - The policy to sign
+    fn sign_policy(&self, policy: &Policy) -> Result<SignedPolicy, Error>;
+}
+
+impl UrlSigner for CloudFrontSigner {
+    fn sign_url(&self, resource: &str, expiry: u64) -> Result<SignedUrl, Error> {
+        CloudFrontSigner::sign_url(self, resource, expiry)
+    }
+
+    fn create_canned_policy_signed_cookies(
+        &self,
+        resource: &str,
+        expiry: u64,
+    ) -> Result<SignedCookies, Error> {
+        CloudFrontSigner::create_canned_policy_signed_cookies(self, resource, expiry)
+    }
+
+    fn sign_policy(&self, policy: &Policy) -> Result<SignedPolicy, Error> {
+        CloudFrontSigner::sign_policy(self, policy)
+    }
+}
+
+#[cfg(feature = "testing")]
+impl UrlSigner for crate::testing::MockSigner {
+    fn sign_url(&self, resource: &str, expiry: u64) -> Result<SignedUrl, Error> {
+        crate::testing::MockSigner::sign_url(self, resource, expiry)
+    }
+
+    fn create_canned_policy_signed_cookies(
+        &self,
+        resource: &str,
+        expiry: u64,
+    ) -> Result<SignedCookies, Error> {
+        crate::testing::MockSigner::create_canned_policy_signed_cookies(self, resource, expiry)
+    }
+
+    fn sign_policy(&self, policy: &Policy) -> Result<SignedPolicy, Error> {
+        crate::testing::MockSigner::sign_policy(self, policy)
+    }
+}
+
+impl UrlSigner for crate::sandbox::SandboxedSigner {
+    fn sign_url(&self, resource: &str, expiry: u64) -> Result<SignedUrl, Error> {
+        crate::sandbox::SandboxedSigner::sign_url(self, resource, expiry)
+    }
+
+    fn create_canned_policy_signed_cookies(
+        &self,
+        resource: &str,
+        expiry: u64,
+    ) -> Result<SignedCookies, Error> {
+        crate::sandbox::SandboxedSigner::create_canned_policy_signed_cookies(self, resource, expiry)
+    }
+
+    fn sign_policy(&self, policy: &Policy) -> Result<SignedPolicy, Error> {
+        crate::sandbox::SandboxedSigner::sign_policy(self, policy)
+    }
+}
+
+impl UrlSigner for crate::ReloadingSigner {
+    fn sign_url(&self, resource: &str, expiry: u64) -> Result<SignedUrl, Error> {
+        crate::ReloadingSigner::sign_url(self, resource, expiry)
+    }
+
+    fn create_canned_policy_signed_cookies(
+        &self,
+        resource: &str,
+        expiry: u64,
+    ) -> Result<SignedCookies, Error> {
+        crate::ReloadingSigner::create_canned_policy_signed_cookies(self, resource, expiry)
+    }
+
+    fn sign_policy(&self, policy: &Policy) -> Result<SignedPolicy, Error> {
+        crate::ReloadingSigner::sign_policy(self, policy)
+    }
+}
+
+impl UrlSigner for crate::CanarySigner {
+    fn sign_url(&self, resource: &str, expiry: u64) -> Result<SignedUrl, Error> {
+        crate::CanarySigner::sign_url(self, resource, expiry)
+    }
+
+    fn create_canned_policy_signed_cookies(
+        &self,
+        resource: &str,
+        expiry: u64,
+    ) -> Result<SignedCookies, Error> {
+        crate::CanarySigner::create_canned_policy_signed_cookies(self, resource, expiry)
+    }
+
+    fn sign_policy(&self, policy: &Policy) -> Result<SignedPolicy, Error> {
+        crate::CanarySigner::sign_policy(self, policy)
+    }
+}
+
+impl<T: UrlSigner + ?Sized> UrlSigner for Arc<T> {
+    fn sign_url(&self, resource: &str, expiry: u64) -> Result<SignedUrl, Error> {
+        (**self).sign_url(resource, expiry)
+    }
+
+    fn create_canned_policy_signed_cookies(
+        &self,
+        resource: &str,
+        expiry: u64,
+    ) -> Result<SignedCookies, Error> {
+        (**self).create_canned_policy_signed_cookies(resource, expiry)
+    }
+
+    fn sign_policy(&self, policy: &Policy) -> Result<SignedPolicy, Error> {
+        (**self).sign_policy(policy)
+    }
+}
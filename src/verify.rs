@@ -0,0 +1,456 @@
+/*
+MIT License
+
+Copyright (c) 2020 Martin Karlsen Jensen
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+//! Parsing and verification of signed URLs
+
+use crate::keys::{key_pair_id_format, KeyPairIdFormat};
+use crate::resource::wildcard_matches;
+use crate::{base64, generate_canned_policy, Error, Policy, PolicyKind, DEFAULT_MAX_URL_LENGTH};
+use openssl::hash::MessageDigest;
+use openssl::pkey::{PKey, Public};
+use openssl::rsa::Rsa;
+use openssl::sign::Verifier;
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The longest URL that is parsed, well above what CloudFront accepts, to bound the work done on untrusted input
+const MAX_URL_LENGTH: usize = 4 * DEFAULT_MAX_URL_LENGTH;
+
+/// A signed URL split into the resource and the parameters added by CloudFront signing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedSignedUrl {
+    resource: String,
+    kind: PolicyKind,
+    policy: Vec<u8>,
+    signature: Vec<u8>,
+    key_pair_id: String,
+}
+
+impl ParsedSignedUrl {
+    /// Parses a signed URL
+    ///
+    /// Never panics on malformed input, which is usually controlled by viewers. URLs far longer
+    /// than CloudFront accepts fail with [`Error::UrlTooLong`].
+    ///
+    /// # Arguments
+    /// * `url` - The signed URL eg. https://example.cloudfront.net/flowerpot.png?Expires=1579532331&Signature=...&Key-Pair-Id=APKAIEXAMPLE
+    pub fn parse(url: &str) -> Result<ParsedSignedUrl, Error> {
+        let invalid = |message: &str| Error::InvalidSignedUrl(message.to_string());
+
+        if url.len() > MAX_URL_LENGTH {
+            return Err(Error::UrlTooLong {
+                len: url.len(),
+                limit: MAX_URL_LENGTH,
+            });
+        }
+
+        let (base, query) = url
+            .split_once('?')
+            .ok_or_else(|| invalid("the URL has no query string"))?;
+        let mut remaining = Vec::new();
+        let mut expires = None;
+        let mut policy = None;
+        let mut signature = None;
+        let mut key_pair_id = None;
+
+        for parameter in query.split('&') {
+            let (name, value) = parameter.split_once('=').unwrap_or((parameter, ""));
+            let slot = match name {
+                "Expires" => &mut expires,
+                "Policy" => &mut policy,
+                "Signature" => &mut signature,
+                "Key-Pair-Id" => &mut key_pair_id,
+                _ => {
+                    remaining.push(parameter);
+                    continue;
+                }
+            };
+
+            if slot.replace(percent_decode(value)?).is_some() {
+                return Err(Error::InvalidSignedUrl(format!(
+                    "the {} parameter appears more than once",
+                    name
+                )));
+            }
+        }
+
+        let resource = if remaining.is_empty() {
+            base.to_string()
+        } else {
+            format!("{}?{}", base, remaining.join("&"))
+        };
+        let (kind, policy) = match (expires, policy) {
+            (Some(expires), None) => {
+                let expires = expires
+                    .parse()
+                    .map_err(|_| invalid("the Expires parameter is not a unix timestamp"))?;
+
+                (
+                    PolicyKind::Canned,
+                    generate_canned_policy(&resource, expires),
+                )
+            }
+            (None, Some(policy)) => (PolicyKind::Custom, base64::decode(&policy)?),
+            (Some(_), Some(_)) => {
+                return Err(invalid(
+                    "the URL has both an Expires and a Policy parameter",
+                ))
+            }
+            (None, None) => {
+                return Err(invalid(
+                    "the URL has neither an Expires nor a Policy parameter",
+                ))
+            }
+        };
+
+        Ok(ParsedSignedUrl {
+            resource,
+            kind,
+            policy,
+            signature: base64::decode(
+                &signature.ok_or_else(|| invalid("the URL has no Signature parameter"))?,
+            )?,
+            key_pair_id: key_pair_id
+                .ok_or_else(|| invalid("the URL has no Key-Pair-Id parameter"))?,
+        })
+    }
+
+    /// Returns the URL without the parameters added by signing
+    pub fn resource(&self) -> &str {
+        &self.resource
+    }
+
+    /// Returns whether the URL was signed with a canned or a custom policy
+    pub fn kind(&self) -> PolicyKind {
+        self.kind
+    }
+
+    /// Returns the signed policy document. For canned policies it is reconstructed from the resource and expiry
+    pub fn policy_json(&self) -> &[u8] {
+        &self.policy
+    }
+
+    /// Parses the signed policy document
+    pub fn policy(&self) -> Result<Policy, Error> {
+        Policy::from_json(&self.policy)
+    }
+
+    /// Returns the decoded signature
+    pub fn signature(&self) -> &[u8] {
+        &self.signature
+    }
+
+    /// Returns the key pair ID the URL claims to be signed with
+    pub fn key_pair_id(&self) -> &str {
+        &self.key_pair_id
+    }
+
+    /// Checks whether the signature was created over the policy by the private key matching `public_key`
+    ///
+    /// # Arguments
+    /// * `public_key` - The public key uploaded to CloudFront
+    pub fn verify(&self, public_key: &PKey<Public>) -> Result<bool, Error> {
+        verify_signature(&self.policy, &self.signature, public_key)
+    }
+}
+
+/// Checks whether the signature was created over the policy document by the private key matching `public_key`
+pub(crate) fn verify_signature(
+    policy: &[u8],
+    signature: &[u8],
+    public_key: &PKey<Public>,
+) -> Result<bool, Error> {
+    let mut verifier = Verifier::new(MessageDigest::sha1(), public_key).map_err(|e| {
+        error!("Could not create verifier due to {}", e);
+        Error::Unknown
+    })?;
+
+    verifier.update(policy).map_err(|e| {
+        error!("Could not update verifier due to {}", e);
+        Error::Unknown
+    })?;
+
+    // OpenSSL reports a malformed signature as an error rather than a mismatch
+    Ok(verifier.verify(signature).unwrap_or(false))
+}
+
+/// Why CloudFront would refuse a signed URL, as found by [`diagnose`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Diagnosis {
+    /// Nothing is wrong with the URL. The signature is only verified when a public key is given
+    Valid {
+        /// Absolute time the URL expires, given in the form of a unix timestamp in UTC
+        expires_at: u64,
+        /// Whether the signature was verified
+        signature_verified: bool,
+    },
+    /// The policy covering the URL has expired
+    Expired {
+        /// Absolute time the URL expired, given in the form of a unix timestamp in UTC
+        expired_at: u64,
+    },
+    /// The policy covering the URL only grants access from a later time
+    NotYetValid {
+        /// Absolute time access starts, given in the form of a unix timestamp in UTC
+        valid_from: u64,
+    },
+    /// The signature was not created over the policy by the private key matching the public key
+    SignatureMismatch,
+    /// No statement of the policy covers the URL
+    ResourceMismatch {
+        /// The URL without the parameters added by signing
+        url: String,
+        /// The resources of the statements
+        resources: Vec<String>,
+    },
+    /// The URL, one of its parameters or the policy could not be decoded
+    EncodingError(String),
+    /// The key pair ID is in neither format CloudFront issues, see [`crate::keys::KeyPairIdFormat`]
+    ///
+    /// Only reported without a public key, as the signature decides otherwise.
+    KeyIdUnknown(String),
+}
+
+impl fmt::Display for Diagnosis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Diagnosis::Valid {
+                expires_at,
+                signature_verified: true,
+            } => write!(f, "the URL is valid until {}", expires_at),
+            Diagnosis::Valid {
+                expires_at,
+                signature_verified: false,
+            } => write!(
+                f,
+                "the URL is valid until {}, but the signature was not verified",
+                expires_at
+            ),
+            Diagnosis::Expired { expired_at } => write!(f, "the URL expired at {}", expired_at),
+            Diagnosis::NotYetValid { valid_from } => {
+                write!(f, "the URL is not valid before {}", valid_from)
+            }
+            Diagnosis::SignatureMismatch => write!(
+                f,
+                "the signature does not match, the URL was changed after signing or signed with another key"
+            ),
+            Diagnosis::ResourceMismatch { url, resources } => write!(
+                f,
+                "the policy covers {} but not {}",
+                resources.join(", "),
+                url
+            ),
+            Diagnosis::EncodingError(message) => write!(f, "the URL is malformed: {}", message),
+            Diagnosis::KeyIdUnknown(key_pair_id) => {
+                write!(
+                    f,
+                    "the key pair ID {} is in no format CloudFront issues, give the public key to check the signature",
+                    key_pair_id
+                )
+            }
+        }
+    }
+}
+
+/// Explains why CloudFront would refuse a signed URL at the current time, see [`diagnose_at`]
+///
+/// # Arguments
+/// * `url` - The signed URL
+/// * `public_key` - The public key uploaded to CloudFront, if the signature should be verified
+pub fn diagnose(url: &str, public_key: Option<&PKey<Public>>) -> Diagnosis {
+    diagnose_at(url, public_key, SystemTime::now())
+}
+
+/// Explains why CloudFront would refuse a signed URL at the given time
+///
+/// The checks follow the order in which a refusal is most likely: the encoding of the URL, the
+/// signature, the resources of the policy and finally its dates. The first problem found is
+/// returned. IP address conditions are not checked. Without a public key the format of the key
+/// pair ID is checked instead of the signature, as a hint. Signers accept key pair IDs in other
+/// formats with a warning, so with a public key only the signature decides.
+///
+/// # Arguments
+/// * `url` - The signed URL
+/// * `public_key` - The public key uploaded to CloudFront, if the signature should be verified
+/// * `now` - The time the URL is requested
+/// # Example
+/// ```
+/// # #[cfg(feature = "sign")]
+/// # {
+/// use cloudfront_policy_signer::verify::{diagnose_at, Diagnosis};
+/// use cloudfront_policy_signer::CloudFrontSigner;
+/// use std::time::{Duration, UNIX_EPOCH};
+///
+/// let signer = CloudFrontSigner::new("examples/key.pem", "K2JCJMDEHXQW5F").unwrap();
+/// let signed_url = signer
+///     .sign_url("https://example.cloudfront.net/flowerpot.png", 1700000000)
+///     .unwrap();
+/// let now = UNIX_EPOCH + Duration::from_secs(1800000000);
+///
+/// assert_eq!(
+///     diagnose_at(signed_url.as_str(), None, now),
+///     Diagnosis::Expired { expired_at: 1700000000 }
+/// );
+/// # }
+/// ```
+///
+/// A key pair ID in an unknown format is only reported when the signature cannot be checked:
+/// ```
+/// # #[cfg(feature = "sign")]
+/// # {
+/// use cloudfront_policy_signer::verify::{diagnose_at, parse_public_key, Diagnosis};
+/// use cloudfront_policy_signer::CloudFrontSigner;
+/// use std::time::{Duration, UNIX_EPOCH};
+///
+/// let signer = CloudFrontSigner::new("examples/key.pem", "APKAIEXAMPLE").unwrap();
+/// let public_key = parse_public_key(signer.public_key_pem().unwrap().as_bytes()).unwrap();
+/// let signed_url = signer
+///     .sign_url("https://example.cloudfront.net/flowerpot.png", 1700000000)
+///     .unwrap();
+/// let now = UNIX_EPOCH + Duration::from_secs(1600000000);
+///
+/// assert_eq!(
+///     diagnose_at(signed_url.as_str(), None, now),
+///     Diagnosis::KeyIdUnknown("APKAIEXAMPLE".to_string())
+/// );
+/// assert_eq!(
+///     diagnose_at(signed_url.as_str(), Some(&public_key), now),
+///     Diagnosis::Valid { expires_at: 1700000000, signature_verified: true }
+/// );
+/// # }
+/// ```
+pub fn diagnose_at(url: &str, public_key: Option<&PKey<Public>>, now: SystemTime) -> Diagnosis {
+    let parsed = match ParsedSignedUrl::parse(url) {
+        Ok(parsed) => parsed,
+        Err(e) => return Diagnosis::EncodingError(e.to_string()),
+    };
+
+    match public_key {
+        Some(public_key) => {
+            if !parsed.verify(public_key).unwrap_or(false) {
+                return Diagnosis::SignatureMismatch;
+            }
+        }
+        None => {
+            if key_pair_id_format(parsed.key_pair_id()) == KeyPairIdFormat::Unrecognized {
+                return Diagnosis::KeyIdUnknown(parsed.key_pair_id().to_string());
+            }
+        }
+    }
+
+    let policy = match parsed.policy() {
+        Ok(policy) => policy,
+        Err(e) => return Diagnosis::EncodingError(e.to_string()),
+    };
+    let covering = policy
+        .statements
+        .iter()
+        .filter(|statement| wildcard_matches(&statement.resource, parsed.resource()))
+        .map(|statement| &statement.condition)
+        .collect::<Vec<_>>();
+
+    if covering.is_empty() {
+        return Diagnosis::ResourceMismatch {
+            url: parsed.resource().to_string(),
+            resources: policy
+                .statements
+                .into_iter()
+                .map(|statement| statement.resource)
+                .collect(),
+        };
+    }
+
+    let now = now
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since_epoch| since_epoch.as_secs());
+    let valid = covering
+        .iter()
+        .filter(|condition| {
+            now < condition.date_less_than
+                && condition.date_greater_than.is_none_or(|start| now >= start)
+        })
+        .map(|condition| condition.date_less_than)
+        .max();
+    let pending = covering
+        .iter()
+        .filter(|condition| now < condition.date_less_than)
+        .filter_map(|condition| condition.date_greater_than)
+        .min();
+
+    match (valid, pending) {
+        (Some(expires_at), _) => Diagnosis::Valid {
+            expires_at,
+            signature_verified: public_key.is_some(),
+        },
+        (None, Some(valid_from)) => Diagnosis::NotYetValid { valid_from },
+        (None, None) => Diagnosis::Expired {
+            expired_at: covering
+                .iter()
+                .map(|condition| condition.date_less_than)
+                .max()
+                .unwrap_or_default(),
+        },
+    }
+}
+
+/// Parses a PEM-encoded RSA public key, either as SubjectPublicKeyInfo (`BEGIN PUBLIC KEY`) or PKCS#1 (`BEGIN RSA PUBLIC KEY`)
+///
+/// # Arguments
+/// * `key` - An array of bytes containing the public key
+pub fn parse_public_key(key: &[u8]) -> Result<PKey<Public>, Error> {
+    Rsa::public_key_from_pem(key)
+        .or_else(|_| Rsa::public_key_from_pem_pkcs1(key))
+        .and_then(PKey::from_rsa)
+        .map_err(|e| {
+            error!("Could not parse RSA public key due to {}", e);
+            Error::PublicKeyParseError
+        })
+}
+
+/// Decodes `%XX` escapes in a query parameter value
+fn percent_decode(value: &str) -> Result<String, Error> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let byte = bytes
+                .get(i + 1..i + 3)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| Error::InvalidSignedUrl("invalid percent-encoding".to_string()))?;
+
+            decoded.push(byte);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    String::from_utf8(decoded)
+        .map_err(|_| Error::InvalidSignedUrl("a parameter is not valid UTF-8".to_string()))
+}